@@ -0,0 +1,62 @@
+//! A minimal implementation of the remote signer protocol, backed by a key held in memory. It's meant for
+//! testing `SIGNER=remote` locally:
+//!
+//! ```bash
+//! SPONSOR_PRIV_KEY=<key> cargo run -p sui-sponsor-api --example mock_signer
+//! ```
+use std::{env, io::Result, str::FromStr};
+use actix_web::{web, App, HttpServer, HttpResponse, error::ErrorBadRequest};
+use serde::{Deserialize, Serialize};
+use shared_crypto::intent::IntentMessage;
+use sui_types::{base_types::SuiAddress, transaction::TransactionData};
+use sui_sponsor_common::{
+  utils::config::KeyPair,
+  services::signer::{Signer, keypair::KeyPairSigner},
+};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SignRequest {
+  address: SuiAddress,
+  intent_message: String,
+}
+
+#[derive(Serialize)]
+struct SignResponse {
+  signature: String,
+}
+
+async fn sign(
+  signer: web::Data<KeyPairSigner>,
+  body: web::Json<SignRequest>,
+) -> actix_web::Result<HttpResponse> {
+  if body.address != signer.address() {
+    return Err(ErrorBadRequest("unknown address"))
+  }
+
+  let bytes = base64::decode(&body.intent_message).map_err(ErrorBadRequest)?;
+  let msg: IntentMessage<TransactionData> = bcs::from_bytes(&bytes).map_err(ErrorBadRequest)?;
+  let signature = signer.sign(&msg).await.map_err(ErrorBadRequest)?;
+
+  Ok(HttpResponse::Ok().json(SignResponse {
+    signature: base64::encode(signature.as_ref()),
+  }))
+}
+
+#[actix_web::main]
+async fn main() -> Result<()> {
+  let keypair = KeyPair::from_str(&env::var("SPONSOR_PRIV_KEY").expect("SPONSOR_PRIV_KEY")).expect("valid key");
+  let port = env::var("PORT").unwrap_or("4100".to_string());
+  let signer = web::Data::new(KeyPairSigner::new(keypair));
+
+  println!("Mock signer for {} listening on port {}", signer.address(), port);
+
+  HttpServer::new(move || {
+    App::new()
+      .app_data(signer.clone())
+      .route("/sign", web::post().to(sign))
+  })
+  .bind(format!("0.0.0.0:{}", port))?
+  .run()
+  .await
+}
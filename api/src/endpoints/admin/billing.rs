@@ -0,0 +1,40 @@
+use actix_web::{web, HttpResponse};
+use serde::Deserialize;
+use eyre::{Result, ContextCompat};
+use crate::utils::{error::Error, auth::Operator};
+use sui_sponsor_common::{
+  utils::store::Store,
+  ledger::billing::{BillingQuery, Period, ReportFormat, billing_report, render},
+};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Query {
+  format: Option<ReportFormat>,
+  period: Period,
+  // UTC dates (YYYY-MM-DD). `from` is inclusive and `to` exclusive
+  from: Option<String>,
+  to: Option<String>,
+}
+
+pub async fn exec(
+  _operator: Operator,
+  store: web::Data<Store>,
+  query: web::Query<Query>,
+) -> Result<HttpResponse, Error> {
+  let ledger = store.ledger.as_ref().context("the ledger is not enabled")?;
+  let format = query.format.unwrap_or(ReportFormat::Json);
+  let billing_query = BillingQuery {
+    period: query.period,
+    from: query.from.clone(),
+    to: query.to.clone(),
+  };
+
+  let report = render(&billing_report(&**ledger, &billing_query).await?, format)?;
+  let content_type = match format {
+    ReportFormat::Csv => "text/csv",
+    ReportFormat::Json => "application/json",
+  };
+
+  Ok(HttpResponse::Ok().content_type(content_type).body(report))
+}
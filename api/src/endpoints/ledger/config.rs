@@ -0,0 +1,11 @@
+use actix_web::{web};
+use super::{
+  totals
+};
+
+// Operator endpoints; each handler requires the `Operator` token
+pub fn config(cfg: &mut web::ServiceConfig) {
+  cfg.service(
+    web::resource("/totals").route(web::get().to(totals::exec))
+  );
+}
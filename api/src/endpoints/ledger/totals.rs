@@ -0,0 +1,24 @@
+use actix_web::{web, HttpResponse};
+use serde::Serialize;
+use eyre::{Result, ContextCompat};
+use crate::utils::{error::Error, auth::Operator};
+use sui_sponsor_common::{
+  utils::store::Store,
+  ledger::{Totals, TotalsQuery},
+};
+
+#[derive(Serialize)]
+pub struct Response {
+  totals: Vec<Totals>,
+}
+
+pub async fn exec(
+  _operator: Operator,
+  store: web::Data<Store>,
+  query: web::Query<TotalsQuery>,
+) -> Result<HttpResponse, Error> {
+  let ledger = store.ledger.as_ref().context("the ledger is not enabled")?;
+  let totals = ledger.totals(&query).await?;
+
+  Ok(HttpResponse::Ok().json(Response {totals}))
+}
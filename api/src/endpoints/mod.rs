@@ -1 +1,3 @@
+pub mod admin;
+pub mod ledger;
 pub mod tx;
@@ -1,6 +1,6 @@
 use actix_web::{web};
 use super::{
-  request_gas, transmit_tx
+  request_gas, transmit_tx, estimate,
 };
 
 // TODO: protect these endpoing using the authn middleware
@@ -8,6 +8,9 @@ pub fn config(cfg: &mut web::ServiceConfig) {
   cfg.service(
     web::resource("/gas").route(web::post().to(request_gas::exec))
   );
+  cfg.service(
+    web::resource("/estimate").route(web::post().to(estimate::exec))
+  );
   cfg.service(
     web::resource("/submit").route(web::post().to(transmit_tx::exec))
   );
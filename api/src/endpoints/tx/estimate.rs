@@ -0,0 +1,35 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+use eyre::{eyre, Result};
+use sui_types::{transaction::TransactionKind, base_types::SuiAddress};
+use crate::utils::{error::Error, auth::tenant};
+use sui_sponsor_common::{
+  map_err,
+  utils::store::Store,
+  services::sponsor::Estimate,
+};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Body {
+  tx_data: String,
+  sender: SuiAddress,
+}
+
+#[derive(Serialize)]
+pub struct Response {
+  estimate: Estimate,
+}
+
+pub async fn exec(
+  req: HttpRequest,
+  store: web::Data<Store>,
+  body: web::Json<Body>,
+) -> Result<HttpResponse, Error> {
+  let tenant = tenant(&req, &store)?;
+  let tx_data = map_err!(base64::decode(&body.tx_data))?;
+  let tx_data: TransactionKind = map_err!(bcs::from_bytes(&tx_data))?;
+  let estimate = store.sponsor.estimate(tx_data, body.sender, tenant).await?;
+
+  Ok(HttpResponse::Ok().json(Response {estimate}))
+}
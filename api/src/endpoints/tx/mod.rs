@@ -1,3 +1,4 @@
 pub mod config;
+pub mod estimate;
 pub mod request_gas;
 pub mod transmit_tx;
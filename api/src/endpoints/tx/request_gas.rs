@@ -1,8 +1,8 @@
-use actix_web::{web, HttpResponse};
+use actix_web::{web, HttpRequest, HttpResponse};
 use serde::{Deserialize, Serialize};
 use eyre::{eyre, Result};
 use sui_types::{transaction::{TransactionKind, GasData}, base_types::SuiAddress};
-use crate::utils::error::Error;
+use crate::utils::{error::Error, auth::tenant};
 use sui_sponsor_common::{
   map_err,
   utils::store::Store,
@@ -22,12 +22,14 @@ pub struct Response {
 }
 
 pub async fn exec(
+  req: HttpRequest,
   store: web::Data<Store>,
   body: web::Json<Body>,
 ) -> Result<HttpResponse, Error> {
+  let tenant = tenant(&req, &store)?;
   let tx_data = map_err!(base64::decode(&body.tx_data))?;
   let tx_data: TransactionKind = map_err!(bcs::from_bytes(&tx_data))?;
-  let gas_data = store.sponsor.request_gas(tx_data, body.sender).await?;
+  let gas_data = store.sponsor.request_gas(tx_data, body.sender, tenant).await?;
 
   Ok(HttpResponse::Ok().json(Response {gas_data}))
 }
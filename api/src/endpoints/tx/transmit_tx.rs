@@ -1,11 +1,13 @@
-use actix_web::{web, HttpResponse};
+use std::time::Instant;
+use actix_web::{web, HttpRequest, HttpResponse};
 use serde::{Deserialize, Serialize};
 use eyre::{eyre, Result, ContextCompat};
+use log::warn;
 use sui_sdk::rpc_types::SuiTransactionBlockResponse;
-use sui_types::{transaction::{TransactionData}, crypto::{Signature, ToFromBytes}};
-use crate::utils::error::Error;
+use sui_types::{transaction::{TransactionData}, crypto::ToFromBytes, signature::GenericSignature};
+use crate::utils::{error::Error, auth::tenant};
 use sui_sponsor_common::{
-  utils::store::Store, map_err, helpers::tx::TxManager
+  utils::store::Store, map_err, helpers::tx::TxManager, ledger::LedgerEntry,
 };
 
 #[derive(Deserialize)]
@@ -22,16 +24,48 @@ pub struct Response {
 }
 
 pub async fn exec(
+  req: HttpRequest,
   store: web::Data<Store>,
   body: web::Json<Body>,
 ) -> Result<HttpResponse, Error> {
   let sig_data = map_err!(base64::decode(&body.signature))?;
-  let sig = map_err!(Signature::from_bytes(&sig_data))?;
+  let sig = map_err!(GenericSignature::from_bytes(&sig_data))?;
   let tx_block_bytes = map_err!(base64::decode(&body.transaction_block_bytes))?;
   let tx_data: TransactionData = map_err!(bcs::from_bytes(&tx_block_bytes))?;
   let gas_object_id = TxManager::extract_gas_objects_ids(&tx_data);
-  let sponsor_sig = store.sponsor.sign_tx(&tx_data).await?;
-  let response = store.tx_manager.send_tx(tx_data, vec![sig, sponsor_sig]).await?;
+  let gas_owner = TxManager::extract_gas_owner(&tx_data);
+  let sender = TxManager::extract_sender(&tx_data);
+  let tenant = tenant(&req, &store)?;
+
+  // Checks the transaction against the policy, and that its gas coin was handed out to its sender by this instance,
+  // before anything else. Nothing below, be it the dry run or the release of the gas coin, may run otherwise
+  let sponsor_sig = store.sponsor.sign_tx(&tx_data, tenant).await?;
+  TxManager::verify_signatures(&tx_data, vec![sig.clone(), sponsor_sig.clone()])?;
+
+  // Reject transactions that would fail without submitting them. The gas coin can be used by another one
+  // straight away
+  if let Some(error) = store.sponsor.dry_run_failure(&tx_data, tenant).await? {
+    store.sponsor
+    .gas_object_processed(gas_owner, *gas_object_id.get(0).context("No Gas coin found")?)
+    .await?;
+
+    return Err(Error::ExecutionFailed(error))
+  }
+
+  // The outcome of each execution feeds the congestion detection of the gas price policy
+  let started_at = Instant::now();
+  let response = store.tx_manager.send_tx(tx_data, vec![sig, sponsor_sig]).await;
+  store.gas_meter.record_execution(started_at.elapsed(), response.is_ok());
+  let response = response?;
+
+  // Recording is best effort; the transaction has been executed either way
+  if let (Some(ledger), Some(gas_coin)) = (&store.ledger, gas_object_id.first()) {
+    if let Some(entry) = LedgerEntry::from_response(&response, sender, gas_owner, *gas_coin, tenant) {
+      if let Err(error) = ledger.record(&entry).await {
+        warn!("Failed to record transaction {} in the ledger: {:?}", entry.digest, error);
+      }
+    }
+  }
 
   let http_response;
 
@@ -50,7 +84,7 @@ pub async fn exec(
   // return the Gas Coin used for the payment back to the queue. We get the first gas object because
   // We know that we only use on Gas Coin in GasData
   store.sponsor
-  .gas_object_processed(*gas_object_id.get(0).context("No Gas coin found")?)
+  .gas_object_processed(gas_owner, *gas_object_id.get(0).context("No Gas coin found")?)
   .await?;
 
   Ok(HttpResponse::Ok().json(http_response))
@@ -6,9 +6,11 @@ use actix_cors::Cors;
 use actix_web::{middleware, web, http, App, HttpServer};
 use env_logger::Env;
 use actix_middleware::firebase_auth::AuthnMiddlewareFactory;
-use sui_sponsor_common::utils::store::Store;
+use sui_sponsor_common::{utils::store::Store, services::policy::API_KEY_HEADER};
 use sui_sponsor_api::{
-  endpoints::tx::config::config as TxConfig,
+  endpoints::{
+    tx::config::config as TxConfig, ledger::config::config as LedgerConfig, admin::config::config as AdminConfig,
+  },
 };
 
 #[actix_web::main]
@@ -23,7 +25,7 @@ async fn main() -> Result<()> {
     dotenv::from_filename(".env").expect("cannot load env from a file");
   }
 
-  let store = Store::new().await;
+  let store = Store::new().await.with_ledger();
   let port = store.config.port;
   let cors_origin = store.config.cors_config.as_ref().unwrap().origin.clone();
   let firebase_api_key = store.config.firebase_api_key.clone();
@@ -42,6 +44,7 @@ async fn main() -> Result<()> {
     .allowed_methods(vec!["GET", "POST", "PUT"])
     .allowed_headers(vec![http::header::AUTHORIZATION, http::header::ACCEPT])
     .allowed_header(http::header::CONTENT_TYPE)
+    .allowed_header(API_KEY_HEADER)
     .max_age(3600);
 
     App::new()
@@ -49,6 +52,8 @@ async fn main() -> Result<()> {
       .wrap(cors)
       .wrap(middleware::Logger::default())
       .service(web::scope("/tx").configure(TxConfig))
+      .service(web::scope("/ledger").configure(LedgerConfig))
+      .service(web::scope("/admin").configure(AdminConfig))
   })
   .bind(format!("0.0.0.0:{}", port.unwrap()))?
   .run()
@@ -0,0 +1,46 @@
+use std::future::{Ready, ready};
+use actix_web::{web, dev::Payload, http::header, FromRequest, HttpRequest};
+use sui_sponsor_common::{utils::store::Store, services::policy::{API_KEY_HEADER, hash_api_key}};
+use super::error::Error;
+
+/// Returns the tenant whose API key the given request carries. Requests without an API key have no tenant, so the
+/// default policy applies to them.
+pub fn tenant<'a>(req: &HttpRequest, store: &'a Store) -> Result<Option<&'a str>, Error> {
+  let api_key = req.headers().get(API_KEY_HEADER)
+  .map(|v| v.to_str())
+  .transpose()
+  .map_err(|_| Error::Unauthorized("malformed API key".to_string()))?;
+
+  store.policy.authenticate(api_key).map_err(|error| Error::Unauthorized(error.to_string()))
+}
+
+/// Guards the operator endpoints. Requests must carry `ADMIN_AUTH_TOKEN` as a bearer token; if it's not set, the
+/// endpoints reject all requests.
+pub struct Operator;
+
+impl FromRequest for Operator {
+  type Error = Error;
+  type Future = Ready<Result<Self, Self::Error>>;
+
+  fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+    ready(authorize_operator(req))
+  }
+}
+
+fn authorize_operator(req: &HttpRequest) -> Result<Operator, Error> {
+  let unauthorized = || Error::Unauthorized("invalid admin token".to_string());
+  let store = req.app_data::<web::Data<Store>>().ok_or_else(unauthorized)?;
+  let expected = store.config.admin_auth_token.as_ref().ok_or_else(unauthorized)?;
+
+  let token = req.headers().get(header::AUTHORIZATION)
+  .and_then(|v| v.to_str().ok())
+  .and_then(|v| v.strip_prefix("Bearer "))
+  .ok_or_else(unauthorized)?;
+
+  // Compare the hashes, which leak nothing about the token, rather than the tokens themselves
+  if hash_api_key(token) != hash_api_key(expected) {
+    return Err(unauthorized())
+  }
+
+  Ok(Operator)
+}
@@ -1,4 +1,4 @@
-use actix_web::ResponseError;
+use actix_web::{ResponseError, http::StatusCode};
 use thiserror::Error;
 
 // A generc error wrapper makes Actix expected errors comatbible with the eyre::Report error so we can directly use the ? operator
@@ -6,9 +6,22 @@ use thiserror::Error;
 pub enum Error {
   #[error("Generic Error")]
   GenericError(String),
+  // The dry run of the transaction failed with the given execution error
+  #[error("Transaction would fail: {0}")]
+  ExecutionFailed(String),
+  #[error("Unauthorized: {0}")]
+  Unauthorized(String),
 }
 
-impl ResponseError for Error {}
+impl ResponseError for Error {
+  fn status_code(&self) -> StatusCode {
+    match self {
+      Error::GenericError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+      Error::ExecutionFailed(_) => StatusCode::UNPROCESSABLE_ENTITY,
+      Error::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+    }
+  }
+}
 
 impl From<eyre::ErrReport> for Error {
   fn from(error: eyre::ErrReport) -> Self {
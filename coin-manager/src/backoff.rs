@@ -0,0 +1,45 @@
+use tokio::time::Duration;
+
+const INITIAL_DELAY: Duration = Duration::from_secs(1);
+const MAX_DELAY: Duration = Duration::from_secs(300);
+
+/// Exponential backoff used to space out retries of transient failures. The delay doubles after each
+/// failed attempt up to `max` and goes back to `initial` once `reset` is called.
+pub struct Backoff {
+  initial: Duration,
+  max: Duration,
+  current: Duration,
+}
+
+impl Default for Backoff {
+  fn default() -> Self {
+    Self::new(INITIAL_DELAY, MAX_DELAY)
+  }
+}
+
+impl Backoff {
+  /// The default backoff with its max delay capped at the given duration
+  pub fn capped(max: Duration) -> Self {
+    Self::new(INITIAL_DELAY, MAX_DELAY.min(max))
+  }
+
+  pub fn new(initial: Duration, max: Duration) -> Self {
+    Self {
+      initial,
+      max,
+      current: initial,
+    }
+  }
+
+  /// Returns the delay to wait before the next attempt
+  pub fn next_delay(&mut self) -> Duration {
+    let delay = self.current;
+    self.current = (self.current * 2).min(self.max);
+
+    delay
+  }
+
+  pub fn reset(&mut self) {
+    self.current = self.initial;
+  }
+}
@@ -1,29 +1,37 @@
-use std::{sync::Arc, str::FromStr};
-use eyre::{Result, ensure, eyre, ContextCompat};
+use std::{future::Future, sync::{Arc, Mutex, atomic::{AtomicU32, Ordering}}, time::Instant};
+use eyre::{Result, ensure};
 use shared_crypto::intent::Intent;
 use sui_sdk::{
   SuiClient,
   rpc_types::{Coin},
 };
-use sui_types::{
-  base_types::{SuiAddress, ObjectID}, transaction::{Command, ObjectArg, TransactionData},
-  programmable_transaction_builder::ProgrammableTransactionBuilder, Identifier, SUI_FRAMEWORK_PACKAGE_ID, coin, TypeTag,
-};
-use log::info;
+use sui_types::base_types::{SuiAddress, ObjectID};
+use log::{info, warn, error};
 use tokio::time::{sleep, Duration};
 use sui_sponsor_common::{
-  storage::{redis::ConnectionPool}, map_err,
+  storage::{redis::ConnectionPool, redlock::{RedLock, Lock}},
   helpers::{object::get_created_objects, tx::TxManager},
-  gas_pool::coin_object_producer::CoinObjectProducer,
-  services::{wallet::Wallet, gas_meter::GasMeter}
+  gas_pool::{
+    PoolKeys, LEGACY_GAS_KEY_PREFIX, now_millis,
+    coin_object_producer::CoinObjectProducer,
+  },
+  services::{wallet::Wallet, gas_meter::GasMeter, rotation::{Rotation, load_rotations}},
+};
+use crate::{
+  error::{Error, Failure}, backoff::Backoff, reconciler::Reconciler,
+  plan::{RebalancePlan, PoolState, PoolTargets, fetch_coins}, sizing::{AdaptiveSizing, PoolSize},
+  treasury::TreasuryMonitor, sweep::Sweep, transfer::{transfer_sui, TRANSFER_GAS_BUDGET},
 };
 
-const GAS_KEY_PREFIX: &str = "gas:";
-// This is roughly how much we need to split into 100 coins for the first time.
-// Here is an example https://suiexplorer.com/txblock/BMU7d8QJpRQQ9oXZkUPGufUHsfZcc1tWaKtBpCkWjDBC?network=devnet.
-// Subsequent calls will require way lower gas because there is a storage rebate from merging coins into one. Here
-// is an example of a subsequent tx https://suiexplorer.com/txblock/6SrtMgLUwRv1Xw8YqGmmHHv8c6EVxYnABQXQW5CNSyfq?network=devnet
-const GAS_BUDGET: u64 = 150_000_000;
+// The number of elements Redis should return on each SCAN/SSCAN iteration
+pub const SCAN_COUNT: usize = 1000;
+// How often we check the status of the pool when everything is healthy
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+// Time we give the fullnode to catch up with the latest object versions before retrying after a version conflict
+const VERSION_CONFLICT_DELAY: Duration = Duration::from_secs(1);
+// The pool is only shrunk once it's this much larger than the target, so that it doesn't flap with every change
+// in throughput
+const SHRINK_THRESHOLD: f64 = 1.25;
 
 /// The role of CoinManager is to merge small coins into a single one and the split those into smaller ones.
 /// Those smaller coins will be added into the Gas Pool and later consumer by the GasPool service.
@@ -32,18 +40,32 @@ const GAS_BUDGET: u64 = 150_000_000;
 /// their balance is getting low over time. At some point each such Gas coin will be so small that it cannot be used
 /// in any sponsored transaction. CoinManager will make sure to clear up those dust coins and recreate big enough coins
 /// which are added back to the Gas Pool
+///
+/// Each sponsor account has its own Gas Pool shard, and thus its own CoinManager.
 pub struct CoinManager {
   api: Arc<SuiClient>,
   wallet: Arc<Wallet>,
   gas_meter: Arc<GasMeter>,
   tx_manager: Arc<TxManager>,
   redis_pool: Arc<ConnectionPool>,
+  redlock: Arc<RedLock>,
   coin_object_producer: Arc<CoinObjectProducer>,
-  max_capacity: usize,
-  min_pool_count: usize,
-  // The minimum balance each coin that is created and added to the Gas Pool should have
-  coin_balance_deposit: u64,
+  keys: PoolKeys,
+  reconciler: Reconciler,
+  // Validity of the leader lease in milliseconds. It's renewed before each rebalance cycle and while it's running
+  leader_lease_ttl: usize,
+  // When the leader lease we hold expires, if any
+  lease_deadline: Mutex<Option<Instant>>,
+  // How often the pool is reconciled with the chain state
+  reconcile_interval: Duration,
+  // The configured pool size. When adaptive sizing is enabled it's scaled on each cycle
+  pool_size: PoolSize,
+  adaptive_sizing: Option<AdaptiveSizing>,
+  treasury: TreasuryMonitor,
+  sweep: Option<Sweep>,
   sponsor: SuiAddress,
+  // Number of consecutive executions that have failed. Reset to 0 after a successful execution
+  consecutive_failures: Arc<AtomicU32>,
 }
 
 impl CoinManager {
@@ -53,10 +75,16 @@ impl CoinManager {
     gas_meter: Arc<GasMeter>,
     tx_manager: Arc<TxManager>,
     redis_pool: Arc<ConnectionPool>,
+    redlock: Arc<RedLock>,
     coin_object_producer: Arc<CoinObjectProducer>,
-    max_capacity: usize,
-    min_pool_count: usize,
-    coin_balance_deposit: u64,
+    keys: PoolKeys,
+    reconciler: Reconciler,
+    leader_lease_ttl: usize,
+    reconcile_interval: Duration,
+    pool_size: PoolSize,
+    adaptive_sizing: Option<AdaptiveSizing>,
+    treasury: TreasuryMonitor,
+    sweep: Option<Sweep>,
     sponsor: SuiAddress,
   ) -> Self {
     Self {
@@ -65,157 +93,189 @@ impl CoinManager {
       gas_meter,
       tx_manager,
       redis_pool,
+      redlock,
       coin_object_producer,
-      max_capacity,
-      min_pool_count,
-      coin_balance_deposit,
-      sponsor
+      keys,
+      reconciler,
+      leader_lease_ttl,
+      lease_deadline: Mutex::new(None),
+      reconcile_interval,
+      pool_size,
+      adaptive_sizing,
+      treasury,
+      sweep,
+      sponsor,
+      consecutive_failures: Arc::new(AtomicU32::new(0)),
     }
   }
 
-  /// read the gas pool coins from Redis
-  async fn get_pool_coins(&self) -> Result<Vec<String>> {
-    // check the numbet of Gas coins in the pool
+  /// Returns a handle to the number of consecutive failed executions. It can be read while `run` is in progress
+  /// e.g. by a health check.
+  pub fn consecutive_failures(&self) -> Arc<AtomicU32> {
+    Arc::clone(&self.consecutive_failures)
+  }
+
+  /// Older versions tracked pool membership using individual `gas:<coin_id>` keys. This will move any such
+  /// keys into the `GAS_POOL_KEY` set and delete them. It's a noop if there are no legacy keys left. Those versions
+  /// only supported a single sponsor, so only unsharded deployments are migrated.
+  async fn migrate_legacy_pool_keys(&self) -> Result<()> {
+    if !self.keys.is_unsharded() {return Ok(())}
+
     let mut conn = self.redis_pool.connection().await?;
-    let gas_coins = conn.keys(format!("{GAS_KEY_PREFIX}*")).await?;
+    let legacy_keys = conn.scan(format!("{LEGACY_GAS_KEY_PREFIX}*"), SCAN_COUNT).await?;
 
-    Ok(gas_coins)
+    if legacy_keys.is_empty() {return Ok(())}
+    info!("Migrating {} legacy gas pool keys", legacy_keys.len());
+    self.ensure_leader()?;
+
+    let coin_ids = legacy_keys.iter()
+    .map(|key| key.trim_start_matches(LEGACY_GAS_KEY_PREFIX).to_string())
+    .collect::<Vec<_>>();
+
+    conn.sadd(self.keys.pool.clone(), &coin_ids).await?;
+
+    for key in legacy_keys {
+      conn.delete(key).await?;
+    }
+
+    Ok(())
   }
 
-  // It will find the smallest coins that has just enough balance to pay the rebalance_coin transaction block gas cost
-  fn get_gas_payment_coin_index(input_coins: &Vec<Coin>) -> Result<usize> {
-    // TODO: We need to calculate the amount of gas cost that will be required to pay for the rebalance_coin
-    // transaction block;
-    let total_gas_cost = GAS_BUDGET;
-    
-    // find the smallest big enough coin
-    let pos = input_coins.iter()
+  /// Fetches all coins that belong to the sponsor. It will return a sorted array of coins according to their balance
+  pub async fn fetch_coins(&self) -> Result<Vec<Coin>> {
+    fetch_coins(&self.api, self.sponsor).await
+  }
+
+  /// Builds the rebalance plan for the current state of the sponsor account and the Gas Pool
+  async fn plan(&self, pool: &PoolState, coins: Vec<Coin>, targets: &PoolTargets) -> Result<RebalancePlan> {
+    let gas_price = self.gas_meter.gas_price().await?;
+
+    RebalancePlan::new(
+      &self.api,
+      self.sponsor,
+      coins,
+      pool,
+      targets,
+      gas_price,
+    ).await
+  }
+
+  /// Shrinks the pool down to the given number of coins. The lowest balance pool coins are marked as retiring;
+  /// the api removes them from the pool once they are returned and the next rebalance merges them.
+  async fn retire_excess_coins(&self, pool: &PoolState, coins: &[Coin], target_count: usize) -> Result<()> {
+    let mut conn = self.redis_pool.connection().await?;
+    let retiring = conn.sscan(self.keys.retiring.as_str(), SCAN_COUNT).await?;
+    let active_count = pool.members.difference(&retiring).count();
+
+    if (active_count as f64) <= target_count as f64 * SHRINK_THRESHOLD {return Ok(())}
+
+    // coins are sorted by balance in descending order
+    let to_retire = coins.iter()
     .rev()
-    .position(|c| c.balance >= total_gas_cost)
-    .context("no gas payment coin found")?;
+    .filter(|c| pool.contains(c))
+    .map(|c| c.coin_object_id.to_hex_uncompressed())
+    .filter(|c| !retiring.contains(c))
+    .take(active_count - target_count)
+    .collect::<Vec<_>>();
 
-    // Get the original index not the reverse
-    Ok(input_coins.len() - 1 - pos)
+    info!("Shrinking the gas pool by {} coins", to_retire.len());
+    self.ensure_leader()?;
+    conn.sadd(self.keys.retiring.clone(), &to_retire).await
   }
 
-  /// Fetches all coins that belong to the sponsor. It will return a sorted array of coins according to their balance
-  async fn fetch_coins(&self) -> Result<Vec<Coin>> {
-    let mut coins = vec![];
-    let mut cursor = None;
+  /// Returns the pool size for this cycle
+  async fn current_pool_size(&self) -> Result<PoolSize> {
+    let Some(adaptive_sizing) = &self.adaptive_sizing else {
+      return Ok(self.pool_size)
+    };
 
-    loop {
-      let response = self.api.coin_read_api().get_coins(
-        self.sponsor,
-        None,
-        cursor,
-        None,
-      )
-      .await?;
-
-      coins.extend(response.data);
-
-      if !response.has_next_page {break}
-      cursor = response.next_cursor;
-    }
+    let (pool_size, throughput) = adaptive_sizing.resize(&self.redis_pool, &self.keys, &self.pool_size).await?;
+    info!(
+      "Throughput {:.2} checkouts/s, average hold time {:.0}ms. Target pool size {}",
+      throughput.checkouts_per_sec, throughput.avg_hold_millis, pool_size.targets.count,
+    );
 
-    coins.sort_by(|a, b| b.balance.cmp(&a.balance));
-    Ok(coins)
+    Ok(pool_size)
   }
 
   /// It will add all newly created coin object ids to Redis, as well as, push
   /// to the distrubuted queue to be consumed by the Gas Pool.
+  ///
+  /// The coins are first recorded, in a single Redis transaction, both as pool members (so the next rebalance
+  /// will not merge them) and in the outbox. Only then are they published to RabbitMQ and removed from the outbox.
+  /// If the process dies in between, the registration is completed by `flush_outbox` once an instance becomes leader.
   async fn process_new_coins(&self, new_coins: Vec<ObjectID>) -> Result<()> {
-    // Push objects to the pool (i.e. RabbitMQ)
-    for coin in new_coins.iter() {
-      self.coin_object_producer.new_coin_object(coin.to_hex_uncompressed()).await?;
-    }
+    if new_coins.is_empty() {return Ok(())}
 
     let new_coins = new_coins.iter()
-    .map(|c| format!("{}{}", GAS_KEY_PREFIX, c.to_hex_uncompressed()))
+    .map(|c| c.to_hex_uncompressed())
     .collect::<Vec<_>>();
 
-    let len = new_coins.len();
+    self.ensure_leader()?;
     let mut conn = self.redis_pool.connection().await?;
-    // the value is irrelevant; we just use number 1 as a convention
-    conn.mset(&new_coins, &vec!["1".to_string(); len]).await?;
-    
+    conn.sadd_all(&[&self.keys.pool, &self.keys.outbox], &new_coins).await?;
+
+    self.flush_outbox().await
+  }
+
+  /// Pushes all coins left in the outbox to the pool (i.e. RabbitMQ). Each coin is removed from the outbox right
+  /// after it's published. Note that if we crash between these two steps the coin will be published again.
+  async fn flush_outbox(&self) -> Result<()> {
+    let mut conn = self.redis_pool.connection().await?;
+    let pending_coins = conn.sscan(self.keys.outbox.as_str(), SCAN_COUNT).await?;
+
+    if pending_coins.is_empty() {return Ok(())}
+    info!("Publishing {} coins from the outbox", pending_coins.len());
+
+    for coin in pending_coins {
+      self.ensure_leader()?;
+      self.coin_object_producer.new_coin_object(coin.clone()).await?;
+      conn.srem(self.keys.outbox.as_str(), coin.as_str()).await?;
+    }
+
     Ok(())
   }
 
-  /// It will first merge all user coins (except for those that are still in the Gas Pool) into the master coin.
-  /// Then it split the master coin into MAX_POOL_CAPACITY - CURRENT_POOL_COUNT equal coins; thus rebalancing
-  /// Sponsor's coins and keeping Gas Pool liquid.
-  async fn rebalance_coins(
-    &self,
-    mut input_coins: Vec<Coin>,
-    gas_pool_coin_count: usize,
-  ) -> Result<Vec<ObjectID>> {
-    info!("Rebalancing coins...");
+  /// Takes the pool coins that are running low out of circulation, so that the rebalance can merge them and
+  /// replace them with new coins. They are removed from the pool index first; from then on the api drops their
+  /// queue entries instead of handing them out. Coins that were checked out in the meantime are left alone until
+  /// they are returned, and merged by a later rebalance. If the rebalance fails, the claimed coins are merged by
+  /// the next one as well.
+  async fn claim_low_coins(&self, pool: &mut PoolState, coins: &[Coin], threshold: u64) -> Result<()> {
+    let low_coins = pool.low_coins(coins, threshold);
+    if low_coins.is_empty() {return Ok(())}
 
-    let gas_price = self.gas_meter.gas_price().await?;
-    let mut ptb = ProgrammableTransactionBuilder::new();
-    
-    // Use the first coin as the master coin
-    // The master coin and gas payment cannot be used in the input coins that will be merged so we should
-    // remove both from the list
-    let master_coin_arg = map_err!(ptb.obj(ObjectArg::ImmOrOwnedObject(input_coins[0].object_ref())))?;
-    input_coins.remove(0);
-
-    let gas_payment_index = Self::get_gas_payment_coin_index(&input_coins)?;
-    let gas_payment = input_coins[gas_payment_index].object_ref();
-    input_coins.remove(gas_payment_index);
-
-    // 1. Merge all these coins into the master coin 
-    // If the sponsor has only one coin the input_coins (which exclude the master coin) will be empty and thus
-    // we can skip the merge step in this iteration.
-    if input_coins.len() > 0 {
-      let input_coin_args = input_coins.into_iter()
-      .map(|c| ptb.obj(ObjectArg::ImmOrOwnedObject(c.object_ref())).expect("coin object ref"))
-      .collect::<Vec<_>>();
-
-      let merge_coin_cmd = Command::MergeCoins(master_coin_arg, input_coin_args);
-      ptb.command(merge_coin_cmd);
+    self.ensure_leader()?;
+    let mut conn = self.redis_pool.connection().await?;
+    conn.srem_all(&[&self.keys.pool, &self.keys.retiring], &low_coins).await?;
+
+    // Checkouts recorded before the coins were removed from the index show up here
+    pool.checked_out.extend(conn.hgetall(self.keys.checkouts.as_str()).await?.into_keys());
+    for coin in &low_coins {
+      pool.members.remove(coin);
     }
 
-    // 2. Split the master coin into MAX_POOL_CAPACITY - CURRENT_POOL_COUNT each having `coin_balance_deposit`
-    let new_coin_count = self.max_capacity - gas_pool_coin_count;
-    let amounts = vec![self.coin_balance_deposit; new_coin_count]
-    .into_iter()
-    .map(|a| ptb.pure(a).expect("pure arg"))
+    let claimed = low_coins.into_iter()
+    .filter(|c| !pool.checked_out.contains(c))
     .collect::<Vec<_>>();
 
-    // We could in theory use one single `Command::SplitCoins`. The issue is that `ptb.transfer_arg` on the
-    // newly created coin was failing. Instead we just create multiple individual calls.
-    let sui_coin_arg_type = map_err!(TypeTag::from_str("0x2::sui::SUI"))?;
-    for amount in amounts {
-      let new_coin_result = ptb.programmable_move_call(
-        SUI_FRAMEWORK_PACKAGE_ID,
-        coin::COIN_MODULE_NAME.to_owned(),
-        map_err!(Identifier::from_str("split"))?,
-        vec![sui_coin_arg_type.clone()],
-        vec![master_coin_arg, amount],
-      );
-  
-      ptb.transfer_arg(self.sponsor, new_coin_result);
-    }
+    info!("Replacing {} pool coins that are running low", claimed.len());
+    pool.replace(claimed);
 
-    let pt = ptb.finish();
-    let tx_data = TransactionData::new_programmable(
-      self.sponsor,
-      vec![gas_payment],
-      pt,
-      GAS_BUDGET,
-      gas_price,
-    );
+    Ok(())
+  }
 
-    self.api
-    .read_api()
-    .dry_run_transaction_block(tx_data.clone())
-    .await?;
+  /// It will first merge all user coins (except for those that are still in the Gas Pool) into the master coin,
+  /// including the pool coins that are running low. Then it splits the master coin into enough new coins to
+  /// reach the pool targets; thus rebalancing Sponsor's coins and keeping Gas Pool liquid.
+  async fn rebalance_coins(&self, plan: RebalancePlan) -> Result<Vec<ObjectID>> {
+    info!("Rebalancing coins...");
+    ensure!(plan.dry_run_errors.is_empty(), Error::RebalanceFailed(plan.dry_run_errors));
 
-    let signature = self.wallet.sign(&tx_data, Intent::sui_transaction())?;
-    let response = self.tx_manager.send_tx(tx_data, vec![signature]).await?;
-    ensure!(!TxManager::has_errors(&response), "rebalancing failed");
+    let signature = self.wallet.sign(&plan.tx_data, Intent::sui_transaction()).await?;
+    self.ensure_leader()?;
+    let response = self.tx_manager.send_tx(plan.tx_data, vec![signature]).await?;
+    ensure!(!TxManager::has_errors(&response), Error::RebalanceFailed(TxManager::get_errors(&response)));
 
     let new_objects = get_created_objects(&response);
     info!("Suceccessfully rebalanced. Number of new coins {}", new_objects.len());
@@ -224,24 +284,15 @@ impl CoinManager {
   }
 
   /// Main execution logic
-  async fn execute(&mut self, current_coins: Vec<String>) -> Result<()> {
-    // 1. Load all coins that belong to the sponsor account
-    let coins = self.fetch_coins().await?;
-    let non_empty_coins = coins.iter().filter(|c| c.balance > 0).count();
-    ensure!(non_empty_coins > 1, "Sponsor MUST have at least two coins");
-    
-    // 2. Exclude the ones that are currently in the Gas Pool
-    let input_coins = coins.into_iter()
-    .filter(|coin| {
-      let (c, _, _) = coin.object_ref();
-      let key = format!("{}{}", GAS_KEY_PREFIX, c.to_hex_uncompressed());
-      !current_coins.contains(&key)
-    })
-    .collect::<Vec<_>>();
-  
+  async fn execute(&self, mut pool: PoolState, coins: Vec<Coin>, targets: &PoolTargets) -> Result<()> {
+    // 1. Take the pool coins that are running low out of circulation
+    self.claim_low_coins(&mut pool, &coins, targets.top_up_threshold).await?;
+
+    // 2. Plan the rebalance of the sponsor coins excluding the ones that are currently in the Gas Pool
+    let plan = self.plan(&pool, coins, targets).await?;
+
     // 3. Rebalance coins
-    let gas_pool_coin_count = current_coins.len();
-    let new_coins = self.rebalance_coins(input_coins, gas_pool_coin_count).await?;
+    let new_coins = self.rebalance_coins(plan).await?;
     
     // 4. Store the new coins into the pool (RabbitMQ) and Redis
     self.process_new_coins(new_coins).await?;
@@ -249,17 +300,307 @@ impl CoinManager {
     Ok(())
   }
 
-  /// A loop that periodically checks if the number of Gas coins in the pool is lower than our capacity
-  pub async fn run(&mut self) -> Result<()> {
+  /// Checks if the number of Gas coins in the pool, or their aggregate balance, is lower than our minimum and
+  /// rebalances if needed. The sponsor balance is checked by the treasury monitor first, so that it can be
+  /// funded even when the rebalance keeps failing for lack of funds.
+  async fn check_pool(&self) -> Result<()> {
+    info!("Checking coin pool status");
+
+    let pool_size = self.current_pool_size().await?;
+    let pool = PoolState::load(&self.redis_pool, &self.keys).await?;
+    let coins = self.fetch_coins().await?;
+    self.ensure_leader()?;
+    self.treasury.check(&coins).await;
+
+    if pool.members.len() < pool_size.min_count || pool.balance(&coins) < pool_size.min_balance {
+      self.execute(pool, coins, &pool_size.targets).await?;
+    } else if self.adaptive_sizing.is_some() {
+      self.retire_excess_coins(&pool, &coins, pool_size.targets.count).await?;
+    }
+
+    Ok(())
+  }
+
+  /// Repairs any drift between the sponsor coins on chain, the pool index in Redis and the queue
+  async fn reconcile(&self) -> Result<()> {
+    let coins = self.fetch_coins().await?;
+    self.ensure_leader()?;
+    self.reconciler.reconcile(&coins).await?;
+
+    Ok(())
+  }
+
+  /// Transfers the sponsor funds above the working balance to the cold address. The coins outside of the pool are
+  /// all used as gas payment, so they are merged by the transfer as well.
+  async fn sweep_excess_funds(&self, sweep: &Sweep) -> Result<()> {
+    let pool = PoolState::load(&self.redis_pool, &self.keys).await?;
+    let coins = self.fetch_coins().await?;
+    let Some(amount) = sweep.amount(&pool, &coins) else {return Ok(())};
+
+    let free_coins = coins.into_iter()
+    .filter(|c| !pool.contains(c))
+    .collect::<Vec<_>>();
+
+    let gas_price = self.gas_meter.gas_price().await?;
+    self.ensure_leader()?;
+    let response = transfer_sui(
+      &self.wallet,
+      &self.tx_manager,
+      free_coins,
+      sweep.cold_address,
+      Some(amount),
+      gas_price,
+    ).await?;
+
+    info!("Swept {} MIST to the cold address {}. Tx {}", amount, sweep.cold_address, response.digest);
+
+    Ok(())
+  }
+
+  /// Retires the pool of a sponsor that is being rotated out. The api no longer hands out its coins, so the queued
+  /// ones are removed from the pool straight away and the checked out ones as they are returned. Once no coin is in
+  /// use, the whole balance is transferred to the new sponsor.
+  async fn drain(&self, rotation: &Rotation) -> Result<()> {
+    self.ensure_leader()?;
+    let purged = self.reconciler.purge_queue().await?;
+    if !purged.is_empty() {
+      info!("Removed {} coins from the pool of the rotated out sponsor", purged.len());
+    }
+
+    let checkouts = self.reconciler.get_checkouts().await?;
+    if !checkouts.is_empty() {
+      info!("Waiting for {} checked out coins to be returned before completing the rotation", checkouts.len());
+      return Ok(())
+    }
+
+    if !rotation.is_past_grace_period(now_millis()) {return Ok(())}
+
+    let coins = self.fetch_coins().await?;
+    let balance = coins.iter().map(|c| c.balance).sum::<u64>();
+
+    // Whatever is left can't even pay for the transfer
+    if balance <= TRANSFER_GAS_BUDGET {
+      info!("Rotation to {} is complete. The key of {} can be removed", rotation.to, self.sponsor);
+      return Ok(())
+    }
+
+    self.ensure_leader()?;
+    let mut conn = self.redis_pool.connection().await?;
+    conn.delete(self.keys.pool.as_str()).await?;
+    conn.delete(self.keys.outbox.as_str()).await?;
+    conn.delete(self.keys.retiring.as_str()).await?;
+
+    let gas_price = self.gas_meter.gas_price().await?;
+    self.ensure_leader()?;
+    let response = transfer_sui(
+      &self.wallet,
+      &self.tx_manager,
+      coins,
+      rotation.to,
+      None,
+      gas_price,
+    ).await?;
+
+    info!("Transferred the balance of {} to {}. Tx {}", self.sponsor, rotation.to, response.digest);
+
+    Ok(())
+  }
+
+  /// A single cycle of the main loop. When `recover` is set, i.e. on the first cycle after this instance becomes
+  /// the leader, legacy pool keys are migrated and registrations left half-done by a previous run are completed
+  /// before checking the pool. When `reconcile` is set the pool is reconciled with the chain
+  /// state before checking it. When `sweep` is set any excess funds are swept once the pool has been refilled.
+  ///
+  /// If the sponsor is being rotated out, its pool is drained instead.
+  async fn cycle(&self, recover: bool, reconcile: bool, sweep: bool) -> Result<()> {
+    if let Some(rotation) = load_rotations(&self.redis_pool).await?.get(&self.sponsor) {
+      return self.drain(rotation).await
+    }
+
+    if recover {
+      self.migrate_legacy_pool_keys().await?;
+      self.flush_outbox().await?;
+    }
+
+    if reconcile {
+      self.reconcile().await?;
+    }
+
+    self.check_pool().await?;
+
+    if let (true, Some(sweep)) = (sweep, &self.sweep) {
+      self.sweep_excess_funds(sweep).await?;
+    }
+
+    Ok(())
+  }
+
+  /// Records when the given lease, requested at the given time, expires
+  fn set_lease_deadline(&self, lease: Option<&Lock<'_>>, requested_at: Instant) {
+    *self.lease_deadline.lock().unwrap() = lease.map(|lease| {
+      requested_at + Duration::from_millis(lease.validity_time as u64)
+    });
+  }
+
+  /// Fails if the leader lease has expired, or is about to. It's called before every write, so that an instance
+  /// that lost the lease while it was busy doesn't touch a pool that the new leader is managing.
+  fn ensure_leader(&self) -> Result<()> {
+    let margin = Duration::from_millis(self.leader_lease_ttl as u64 / 10);
+    let deadline = *self.lease_deadline.lock().unwrap();
+    ensure!(deadline.map_or(false, |deadline| Instant::now() + margin < deadline), Error::LeaseExpired);
+
+    Ok(())
+  }
+
+  /// Renews the leader lease we already hold or tries to acquire a new one. Returns None if another instance
+  /// is the leader.
+  async fn renew_lease<'a>(&'a self, lease: Option<Lock<'a>>) -> Option<Lock<'a>> {
+    let requested_at = Instant::now();
+
+    if let Some(lease) = lease {
+      match self.redlock.extend(&lease, self.leader_lease_ttl).await {
+        Ok(lease) => {
+          self.set_lease_deadline(Some(&lease), requested_at);
+          return Some(lease)
+        },
+        Err(error) => warn!("Lost the leader lease: {:?}", error),
+      }
+    }
+
+    let lease = self.redlock.lock(self.keys.leader_lock.as_bytes(), self.leader_lease_ttl).await.ok();
+    if lease.is_some() {
+      info!("Acquired the leader lease");
+    }
+
+    self.set_lease_deadline(lease.as_ref(), requested_at);
+    lease
+  }
+
+  /// Runs the given future while extending the leader lease every third of its validity, so that the lease doesn't
+  /// expire during long operations e.g. waiting for a transaction or backing off. Fails with `Error::LeaseExpired`
+  /// if the lease can't be extended, in which case the future is dropped before it writes anything else.
+  async fn with_lease<'a, T>(&'a self, lease: &mut Lock<'a>, future: impl Future<Output = T>) -> Result<T> {
+    tokio::pin!(future);
+    let renewal_interval = Duration::from_millis(self.leader_lease_ttl as u64 / 3);
+
+    loop {
+      tokio::select! {
+        output = &mut future => return Ok(output),
+        _ = sleep(renewal_interval) => {
+          let requested_at = Instant::now();
+
+          match self.redlock.extend(lease, self.leader_lease_ttl).await {
+            Ok(extended) => {
+              self.set_lease_deadline(Some(&extended), requested_at);
+              *lease = extended;
+            },
+            Err(error) => {
+              warn!("Lost the leader lease: {:?}", error);
+              self.set_lease_deadline(None, requested_at);
+
+              return Err(Error::LeaseExpired.into())
+            },
+          }
+        },
+      }
+    }
+  }
+
+  /// A loop that periodically checks the status of the pool. Transient errors, including the sponsor running out of
+  /// funds, are retried with exponential backoff and version conflicts are retried straight away; since each execution
+  /// fetches the sponsor coins from scratch, the retry will use the latest object versions. Only fatal errors will exit
+  /// the loop.
+  ///
+  /// Multiple instances can run at the same time. Before each cycle the instance must acquire (or renew) the leader
+  /// lease; those that fail to do so stay idle so that only one instance merges and splits the sponsor coins. The
+  /// lease is extended while the cycle, and the wait after it, are in progress and the backoff is capped at half of
+  /// its validity. Every write checks that the lease is still valid.
+  pub async fn run(&self) -> Result<()> {
+    let mut backoff = Backoff::capped(Duration::from_millis(self.leader_lease_ttl as u64 / 2));
+    let mut lease = None;
+    // Whether the outbox has been flushed since this instance became the leader
+    let mut recovered = false;
+    let mut last_reconciled: Option<Instant> = None;
+    let mut last_swept: Option<Instant> = None;
+
     loop {
-      info!("Checking coin pool status");
-      let pool_coins = self.get_pool_coins().await?;
+      lease = self.renew_lease(lease).await;
+
+      let Some(current_lease) = lease.as_mut() else {
+        recovered = false;
+        info!("Another coin manager instance holds the leader lease");
+        sleep(POLL_INTERVAL).await;
+        continue;
+      };
+
+      let reconcile = last_reconciled.map_or(true, |t| t.elapsed() >= self.reconcile_interval);
+      let sweep = self.sweep.as_ref().map_or(false, |sweep| {
+        last_swept.map_or(true, |t| t.elapsed() >= sweep.interval)
+      });
+
+      let result = self.with_lease(current_lease, self.cycle(!recovered, reconcile, sweep))
+      .await
+      .and_then(|result| result);
+
+      let delay = match result {
+        Ok(_) => {
+          recovered = true;
+          if reconcile {
+            last_reconciled = Some(Instant::now());
+          }
+
+          if sweep {
+            last_swept = Some(Instant::now());
+          }
+
+          self.consecutive_failures.store(0, Ordering::Relaxed);
+          backoff.reset();
+          POLL_INTERVAL
+        },
+        Err(error) => {
+          let failures = self.consecutive_failures.fetch_add(1, Ordering::Relaxed) + 1;
+
+          match Failure::classify(&error) {
+            Failure::Fatal => {
+              error!("Coin manager stopped after {} consecutive failures: {:?}", failures, error);
+
+              // Let other instances take over straight away instead of waiting for the lease to expire
+              if let Some(lease) = lease.take() {
+                self.redlock.unlock(lease).await;
+              }
+
+              return Err(error)
+            },
+            Failure::LostLease => {
+              warn!("Stopped the cycle since the leader lease has expired: {:?}", error);
+              lease = None;
+              recovered = false;
+              continue;
+            },
+            Failure::VersionConflict => {
+              warn!("Version conflict ({} consecutive failures). Retrying with fresh coins: {:?}", failures, error);
+              VERSION_CONFLICT_DELAY
+            },
+            Failure::InsufficientFunds => {
+              let delay = backoff.next_delay();
+              error!("Sponsor is out of funds ({} consecutive failures). Retrying in {:?}: {:?}", failures, delay, error);
+              delay
+            },
+            Failure::Transient => {
+              let delay = backoff.next_delay();
+              warn!("Execution failed ({} consecutive failures). Retrying in {:?}: {:?}", failures, delay, error);
+              delay
+            },
+          }
+        },
+      };
 
-      if pool_coins.len() < self.min_pool_count {
-        self.execute(pool_coins).await?;
+      // Keep the lease while waiting, so that another instance doesn't take over in the middle of a backoff
+      let Some(current_lease) = lease.as_mut() else {continue};
+      if self.with_lease(current_lease, sleep(delay)).await.is_err() {
+        lease = None;
+        recovered = false;
       }
-      
-      sleep(Duration::from_secs(10)).await;
     }
   }
 }
@@ -0,0 +1,72 @@
+use eyre::Report;
+use thiserror::Error;
+
+// Errors that are raised by the rebalance logic itself. Any other error (RPC, Redis, RabbitMQ) is propagated as is
+#[derive(Error, Debug)]
+pub enum Error {
+  #[error("Sponsor MUST have at least two coins")]
+  NotEnoughCoins,
+  #[error("no gas payment coin found")]
+  NoGasPaymentCoin,
+  #[error("not enough SUI outside of the gas pool")]
+  InsufficientFunds,
+  #[error("rebalancing failed: {0:?}")]
+  RebalanceFailed(Vec<String>),
+  #[error("the leader lease has expired")]
+  LeaseExpired,
+}
+
+// Substrings of the errors returned by the fullnode when one of the input objects is not at the version we used
+// when we built the transaction i.e. someone else has mutated or locked it in the meantime.
+const VERSION_CONFLICT_MARKERS: [&str; 4] = [
+  "ObjectVersionUnavailableForConsumption",
+  "is not available for consumption",
+  "already locked by a different transaction",
+  "ObjectNotFound",
+];
+
+/// Determines how the coin manager loop should react to an error returned from a single execution
+#[derive(Debug, PartialEq, Eq)]
+pub enum Failure {
+  /// Something that will most likely succeed if we try again later e.g. an RPC hiccup or a Redis timeout
+  Transient,
+  /// One of the coins we used was mutated after we fetched it. We can retry straight away with fresh coins
+  VersionConflict,
+  /// The sponsor has run out of SUI. Retrying will only help once it's funded, either by the operator or by
+  /// the treasury auto-funding
+  InsufficientFunds,
+  /// The leader lease has expired, so another instance may be rebalancing the pool. This instance must stop
+  /// writing and compete for the lease again
+  LostLease,
+  /// Retrying will not help; the operator must intervene
+  Fatal,
+}
+
+impl Failure {
+  pub fn classify(error: &Report) -> Self {
+    if let Some(error) = error.downcast_ref::<Error>() {
+      return match error {
+        Error::NotEnoughCoins => Failure::Fatal,
+        Error::NoGasPaymentCoin | Error::InsufficientFunds => Failure::InsufficientFunds,
+        Error::LeaseExpired => Failure::LostLease,
+        Error::RebalanceFailed(errors) => {
+          if errors.iter().any(|e| Self::is_version_conflict(e)) {
+            Failure::VersionConflict
+          } else {
+            Failure::Transient
+          }
+        },
+      }
+    }
+
+    if Self::is_version_conflict(&format!("{:?}", error)) {
+      return Failure::VersionConflict
+    }
+
+    Failure::Transient
+  }
+
+  fn is_version_conflict(error: &str) -> bool {
+    VERSION_CONFLICT_MARKERS.iter().any(|marker| error.contains(marker))
+  }
+}
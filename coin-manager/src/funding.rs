@@ -0,0 +1,53 @@
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+use sui_types::base_types::SuiAddress;
+
+#[derive(Serialize)]
+struct FundRequest {
+  sponsor: SuiAddress,
+  // MIST
+  amount: u64,
+}
+
+#[derive(Deserialize)]
+struct FundResponse {
+  // Digest of the transfer transaction
+  digest: String,
+}
+
+/// Asks an external funding service to transfer SUI from the treasury to a sponsor. The service is the only one
+/// that holds the treasury key and decides whether to honour each request (e.g. it only funds known sponsors, up
+/// to a daily limit), so the coin manager can't move treasury funds on its own. It must expose `POST /fund`; see
+/// the README for the protocol.
+pub struct FundingService {
+  client: reqwest::Client,
+  url: String,
+  auth_token: Option<String>,
+}
+
+impl FundingService {
+  pub fn new(url: String, auth_token: Option<String>) -> Self {
+    Self {
+      client: reqwest::Client::new(),
+      url,
+      auth_token,
+    }
+  }
+
+  /// Requests a transfer of the given amount to the sponsor and returns the digest of the transaction
+  pub async fn fund(&self, sponsor: SuiAddress, amount: u64) -> Result<String> {
+    let mut request = self.client.post(format!("{}/fund", self.url)).json(&FundRequest {sponsor, amount});
+    if let Some(auth_token) = &self.auth_token {
+      request = request.bearer_auth(auth_token);
+    }
+
+    let response = request
+    .send()
+    .await?
+    .error_for_status()?
+    .json::<FundResponse>()
+    .await?;
+
+    Ok(response.digest)
+  }
+}
@@ -1 +1,12 @@
 pub mod coin_manager;
+pub mod error;
+pub mod backoff;
+pub mod plan;
+pub mod reconciler;
+pub mod queue_inspector;
+pub mod sizing;
+pub mod notifier;
+pub mod transfer;
+pub mod treasury;
+pub mod funding;
+pub mod sweep;
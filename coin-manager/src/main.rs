@@ -1,11 +1,187 @@
 
 use std::{
-  env, panic, process, sync::Arc,
+  env, panic, process, sync::Arc, str::FromStr,
 };
-use eyre::Result;
+use eyre::{Result, eyre, ensure};
 use env_logger::Env;
-use sui_sponsor_common::{utils::store::Store};
-use sui_sponsor_coin_manager::coin_manager::CoinManager;
+use envconfig::Envconfig;
+use sui_sdk::SuiClientBuilder;
+use sui_sponsor_common::{
+  utils::{store::Store, config::Config},
+  storage::redis::ConnectionPool,
+  services::{
+    gas_meter::GasMeter, wallet::sponsor_wallets, shard::Shard,
+    rotation::{load_rotations, start_rotation},
+  },
+  gas_pool::PoolKeys,
+};
+use sui_types::base_types::SuiAddress;
+use tokio::{time::Duration, task::JoinSet};
+use sui_sponsor_coin_manager::{
+  coin_manager::CoinManager,
+  plan::{RebalancePlan, PoolState, PoolTargets, fetch_coins},
+  reconciler::Reconciler,
+  queue_inspector::QueueInspector,
+  sizing::{AdaptiveSizing, PoolSize},
+  notifier::{Notifier, LogNotifier, WebhookNotifier},
+  treasury::{TreasuryMonitor, Thresholds, AutoFunding},
+  funding::FundingService,
+  sweep::Sweep,
+};
+
+// Default validity of the leader lease in milliseconds
+const DEFAULT_LEADER_LEASE_TTL: usize = 60_000;
+// Default interval, in seconds, between two pool reconciliations
+const DEFAULT_RECONCILE_INTERVAL: u64 = 300;
+// Default number of minutes of stats adaptive sizing is based on
+const DEFAULT_POOL_SIZING_WINDOW: u64 = 5;
+// Default ratio between the pool size and the average number of coins in use
+const DEFAULT_POOL_HEADROOM: f64 = 2.0;
+// Default runway, in seconds, below which warning and critical alerts are raised
+const DEFAULT_RUNWAY_WARNING_THRESHOLD: u64 = 24 * 60 * 60;
+const DEFAULT_RUNWAY_CRITICAL_THRESHOLD: u64 = 4 * 60 * 60;
+// Default number of seconds the spend rate is computed over
+const DEFAULT_SPEND_RATE_WINDOW: u64 = 60 * 60;
+// Default min number of seconds between two fundings from the treasury
+const DEFAULT_AUTO_FUND_COOLDOWN: u64 = 60 * 60;
+// Default number of seconds between two sweeps to the cold address
+const DEFAULT_SWEEP_INTERVAL: u64 = 60 * 60;
+
+fn pool_targets(config: &Config) -> PoolTargets {
+  let gas_pool = &config.gas_pool;
+  let count = gas_pool.max_capacity.unwrap();
+  let coin_balance = gas_pool.coin_balance_deposit.unwrap();
+
+  PoolTargets {
+    count,
+    balance: gas_pool.target_pool_balance.unwrap_or(count as u64 * coin_balance),
+    coin_balance,
+    top_up_threshold: gas_pool.top_up_threshold.unwrap_or(coin_balance / 2),
+  }
+}
+
+fn treasury_monitor(store: &Store, shard: &Shard, targets: &PoolTargets) -> TreasuryMonitor {
+  let config = &store.config.treasury;
+  let mut notifiers: Vec<Box<dyn Notifier>> = vec![Box::new(LogNotifier)];
+
+  if let Some(url) = &config.alert_webhook_url {
+    notifiers.push(Box::new(WebhookNotifier::new(url.clone())));
+  }
+
+  // By default alert once the sponsor can no longer refill the pool twice, and then once it can't refill it at all
+  let critical_balance = config.balance_critical_threshold.unwrap_or(targets.balance);
+  let thresholds = Thresholds {
+    warning_balance: config.balance_warning_threshold.unwrap_or(2 * critical_balance),
+    critical_balance,
+    warning_runway: Duration::from_secs(config.runway_warning_threshold.unwrap_or(DEFAULT_RUNWAY_WARNING_THRESHOLD)),
+    critical_runway: Duration::from_secs(config.runway_critical_threshold.unwrap_or(DEFAULT_RUNWAY_CRITICAL_THRESHOLD)),
+  };
+
+  let auto_funding = config.funding_url.clone().map(|url| AutoFunding {
+    service: FundingService::new(url, config.funding_auth_token.clone()),
+    threshold: config.auto_fund_threshold.unwrap_or(critical_balance),
+    amount: config.auto_fund_amount.expect("AUTO_FUND_AMOUNT must be set when TREASURY_FUNDING_URL is"),
+    cooldown: Duration::from_secs(config.auto_fund_cooldown.unwrap_or(DEFAULT_AUTO_FUND_COOLDOWN)),
+  });
+
+  TreasuryMonitor::new(
+    Arc::clone(&store.redis_pool),
+    shard.sponsor(),
+    notifiers,
+    thresholds,
+    Duration::from_secs(config.spend_rate_window.unwrap_or(DEFAULT_SPEND_RATE_WINDOW)),
+    auto_funding,
+  )
+}
+
+fn sweep(config: &Config) -> Option<Sweep> {
+  let config = &config.treasury;
+  let cold_address = config.cold_address?;
+  let working_balance = config.working_balance.expect("WORKING_BALANCE must be set when COLD_TREASURY_ADDRESS is");
+
+  // Otherwise every funding would be swept straight away
+  assert!(
+    config.auto_fund_threshold.map_or(true, |threshold| threshold < working_balance),
+    "AUTO_FUND_THRESHOLD must be lower than WORKING_BALANCE",
+  );
+
+  Some(Sweep {
+    cold_address,
+    working_balance,
+    interval: Duration::from_secs(config.sweep_interval.unwrap_or(DEFAULT_SWEEP_INTERVAL)),
+  })
+}
+
+fn parse_address(address: &str) -> Result<SuiAddress> {
+  SuiAddress::from_str(address).map_err(|e| eyre!(e.to_string()))
+}
+
+/// Prints the rebalance the coin manager would execute right now as JSON. Nothing is signed and the only
+/// Redis access is reading the pool state; RabbitMQ is not touched at all. When there are multiple sponsors
+/// the address of the one to plan for must be given.
+async fn print_plan(sponsor: Option<String>) -> Result<()> {
+  let config = Config::init_from_env()?;
+  let api = Arc::new(SuiClientBuilder::default().build(&config.sui.rpc).await?);
+  let sponsors = sponsor_wallets(&config.sui)?.iter().map(|w| w.address()).collect::<Vec<_>>();
+
+  let sponsor = match sponsor {
+    Some(sponsor) => parse_address(&sponsor)?,
+    None => {
+      ensure!(sponsors.len() == 1, "there are {} sponsors; pass the address of the one to plan for", sponsors.len());
+      sponsors[0]
+    },
+  };
+  ensure!(sponsors.contains(&sponsor), "{} is not a sponsor", sponsor);
+
+  let redis_pool = Arc::new(ConnectionPool::new(&config.redis.host, &config.redis.password, config.redis.port));
+  let unsharded_sponsor = PoolKeys::claim_unsharded_sponsor(&redis_pool, sponsors[0]).await?;
+  let mut pool = PoolState::load(&redis_pool, &PoolKeys::new(sponsor, unsharded_sponsor)).await?;
+  let targets = pool_targets(&config);
+
+  let coins = fetch_coins(&api, sponsor).await?;
+  // The coin manager takes these out of the pool before the rebalance; here it's only done in memory
+  let low_coins = pool.low_coins(&coins, targets.top_up_threshold);
+  pool.replace(low_coins);
+
+  let gas_price = GasMeter::new(Arc::clone(&api), Arc::clone(&redis_pool), Default::default()).gas_price().await?;
+
+  let plan = RebalancePlan::new(
+    &api,
+    sponsor,
+    coins,
+    &pool,
+    &targets,
+    gas_price,
+  ).await?;
+
+  println!("{}", serde_json::to_string_pretty(&plan)?);
+
+  Ok(())
+}
+
+/// Starts rotating the `from` sponsor out in favour of `to`. Both keys must be configured, in both the api and the
+/// coin manager, until the rotation completes.
+async fn rotate(from: Option<String>, to: Option<String>) -> Result<()> {
+  let usage = || eyre!("usage: rotate <old sponsor address> <new sponsor address>");
+  let from = parse_address(&from.ok_or_else(usage)?)?;
+  let to = parse_address(&to.ok_or_else(usage)?)?;
+
+  let config = Config::init_from_env()?;
+  let sponsors = sponsor_wallets(&config.sui)?.iter().map(|w| w.address()).collect::<Vec<_>>();
+  ensure!(from != to, "the new sponsor must be different from the old one");
+  ensure!(sponsors.contains(&from), "{} is not a sponsor", from);
+  ensure!(sponsors.contains(&to), "{} is not a sponsor; add its key to the config first", to);
+
+  let redis_pool = ConnectionPool::new(&config.redis.host, &config.redis.password, config.redis.port);
+  let rotations = load_rotations(&redis_pool).await?;
+  ensure!(!rotations.contains_key(&from), "{} is already being rotated out", from);
+  ensure!(!rotations.contains_key(&to), "{} is being rotated out itself", to);
+
+  start_rotation(&redis_pool, from, to).await?;
+  println!("Started rotating {} out in favour of {}", from, to);
+
+  Ok(())
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -19,24 +195,70 @@ async fn main() -> Result<()> {
     dotenv::from_filename(".env").expect("cannot load env from a file");
   }
 
+  match env::args().nth(1).as_deref() {
+    Some("plan") => return print_plan(env::args().nth(2)).await,
+    Some("rotate") => return rotate(env::args().nth(2), env::args().nth(3)).await,
+    _ => {},
+  }
+
   let store = Store::new().await;
   env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
 
-  let sponsor_address = store.wallet.address();
-  let mut coin_manager = CoinManager::new(
-    Arc::clone(&store.rpc_client),
-    Arc::clone(&store.wallet),
-    Arc::clone(&store.gas_meter),
-    Arc::clone(&store.tx_manager),
-    Arc::clone(&store.redis_pool),
-    Arc::clone(&store.coin_object_producer),
-    store.config.gas_pool.max_capacity.unwrap(),
-    store.config.gas_pool.min_pool_count.unwrap(),
-    store.config.gas_pool.coin_balance_deposit.unwrap(),
-    sponsor_address,
-  );
+  let gas_pool_config = &store.config.gas_pool;
+  let min_pool_count = gas_pool_config.min_pool_count.unwrap();
+  let coin_balance_deposit = gas_pool_config.coin_balance_deposit.unwrap();
+  let pool_size = PoolSize {
+    targets: pool_targets(&store.config),
+    min_count: min_pool_count,
+    min_balance: gas_pool_config.min_pool_balance.unwrap_or(min_pool_count as u64 * coin_balance_deposit),
+  };
+  let adaptive_sizing = gas_pool_config.adaptive_sizing.unwrap_or(false).then(|| AdaptiveSizing {
+    window_minutes: gas_pool_config.sizing_window.unwrap_or(DEFAULT_POOL_SIZING_WINDOW),
+    headroom: gas_pool_config.headroom.unwrap_or(DEFAULT_POOL_HEADROOM),
+  });
+
+  let queue_inspector = store.config.rabbitmq.management_uri.clone()
+  .map(|uri| QueueInspector::try_new(uri, &store.config.rabbitmq.uri).map(Arc::new))
+  .transpose()?;
 
-  coin_manager.run().await?;
+  // Each shard has its own coin manager. They run independently from each other
+  let mut coin_managers = JoinSet::new();
+
+  for shard in store.shards.iter() {
+    let reconciler = Reconciler::try_new(
+      Arc::clone(&store.redis_pool),
+      shard.keys.clone(),
+      Arc::clone(&shard.coin_object_producer),
+      queue_inspector.clone(),
+      &store.config.rabbitmq.uri,
+    ).await?;
+
+    let coin_manager = CoinManager::new(
+      Arc::clone(&store.rpc_client),
+      Arc::clone(&shard.wallet),
+      Arc::clone(&store.gas_meter),
+      Arc::clone(&store.tx_manager),
+      Arc::clone(&store.redis_pool),
+      Arc::clone(&store.redlock),
+      Arc::clone(&shard.coin_object_producer),
+      shard.keys.clone(),
+      reconciler,
+      store.config.coin_manager.leader_lease_ttl.unwrap_or(DEFAULT_LEADER_LEASE_TTL),
+      Duration::from_secs(store.config.coin_manager.reconcile_interval.unwrap_or(DEFAULT_RECONCILE_INTERVAL)),
+      pool_size,
+      adaptive_sizing,
+      treasury_monitor(&store, shard, &pool_size.targets),
+      sweep(&store.config),
+      shard.sponsor(),
+    );
+
+    coin_managers.spawn(async move {coin_manager.run().await});
+  }
+
+  // A fatal error in any of the shards stops the process
+  while let Some(result) = coin_managers.join_next().await {
+    result??;
+  }
 
   Ok(())
 }
@@ -0,0 +1,75 @@
+use async_trait::async_trait;
+use eyre::Result;
+use log::{warn, error};
+use serde::Serialize;
+use sui_types::base_types::SuiAddress;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+  Warning,
+  Critical,
+}
+
+/// Raised by the treasury monitor when the sponsor balance, or its projected runway, is running low
+#[derive(Serialize, Debug)]
+pub struct Alert {
+  pub severity: Severity,
+  pub sponsor: SuiAddress,
+  // Total SUI (in MIST) owned by the sponsor including the Gas Pool coins
+  pub balance: u64,
+  // Seconds until the sponsor runs out of SUI at the current spend rate. None if nothing has been spent
+  // recently
+  pub runway_secs: Option<u64>,
+  pub message: String,
+}
+
+/// Delivers alerts to the operator
+#[async_trait]
+pub trait Notifier: Send + Sync {
+  async fn notify(&self, alert: &Alert) -> Result<()>;
+}
+
+/// Writes alerts to the log. It's always enabled
+pub struct LogNotifier;
+
+#[async_trait]
+impl Notifier for LogNotifier {
+  async fn notify(&self, alert: &Alert) -> Result<()> {
+    match alert.severity {
+      Severity::Warning => warn!("Treasury alert: {:?}", alert),
+      Severity::Critical => error!("Treasury alert: {:?}", alert),
+    }
+
+    Ok(())
+  }
+}
+
+/// POSTs each alert, as JSON, to the given url e.g. a Slack or PagerDuty webhook
+pub struct WebhookNotifier {
+  client: reqwest::Client,
+  url: String,
+}
+
+impl WebhookNotifier {
+  pub fn new(url: String) -> Self {
+    Self {
+      client: reqwest::Client::new(),
+      url,
+    }
+  }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+  async fn notify(&self, alert: &Alert) -> Result<()> {
+    self.client
+    .post(&self.url)
+    .json(alert)
+    .send()
+    .await?
+    .error_for_status()?;
+
+    Ok(())
+  }
+}
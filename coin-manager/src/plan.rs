@@ -0,0 +1,294 @@
+use std::{str::FromStr, collections::HashSet};
+use eyre::{Result, ensure, eyre};
+use serde::Serialize;
+use sui_sdk::{
+  SuiClient,
+  rpc_types::{Coin, SuiTransactionBlockEffects, SuiExecutionStatus},
+};
+use sui_types::{
+  base_types::{SuiAddress, ObjectID}, transaction::{Command, ObjectArg, TransactionData},
+  programmable_transaction_builder::ProgrammableTransactionBuilder, Identifier, SUI_FRAMEWORK_PACKAGE_ID, coin, TypeTag,
+};
+use sui_sponsor_common::{
+  map_err, services::gas_meter::GasMeter, storage::redis::ConnectionPool,
+  gas_pool::PoolKeys,
+};
+use crate::{error::Error, coin_manager::SCAN_COUNT};
+
+// This is roughly how much we need to split into 100 coins for the first time.
+// Here is an example https://suiexplorer.com/txblock/BMU7d8QJpRQQ9oXZkUPGufUHsfZcc1tWaKtBpCkWjDBC?network=devnet.
+// Subsequent calls will require way lower gas because there is a storage rebate from merging coins into one. Here
+// is an example of a subsequent tx https://suiexplorer.com/txblock/6SrtMgLUwRv1Xw8YqGmmHHv8c6EVxYnABQXQW5CNSyfq?network=devnet
+pub const GAS_BUDGET: u64 = 150_000_000;
+// Max number of coins a single rebalance creates. Each one takes two commands (split and transfer), so this keeps
+// the transaction well within the limits on the number of commands and inputs. Any remaining deficit is filled by
+// the next rebalance.
+pub const MAX_NEW_COINS: usize = 400;
+
+/// The desired state of the Gas Pool after a rebalance
+#[derive(Clone, Copy, Serialize)]
+pub struct PoolTargets {
+  // Number of coins the pool should hold
+  pub count: usize,
+  // Aggregate balance of all pool coins. If it can't be reached with `count` coins, more coins will be created
+  pub balance: u64,
+  // Balance of each new coin
+  pub coin_balance: u64,
+  // Pool coins with a balance lower than this are replaced with new coins
+  pub top_up_threshold: u64,
+}
+
+impl PoolTargets {
+  /// The number of coins, each of `coin_balance`, that a pool with the given coin count and balance needs to reach
+  /// both the count and the balance target. A single rebalance creates at most `MAX_NEW_COINS`.
+  pub fn new_coin_count(&self, pool_count: usize, pool_balance: u64) -> usize {
+    let count_deficit = self.count.saturating_sub(pool_count);
+    let balance_deficit = self.balance.saturating_sub(pool_balance);
+    let coin_balance = self.coin_balance.max(1);
+    let balance_deficit_count = balance_deficit / coin_balance + u64::from(balance_deficit % coin_balance != 0);
+
+    count_deficit
+    .max(balance_deficit_count as usize)
+    .min(MAX_NEW_COINS)
+  }
+}
+
+/// The coins that are currently in the Gas Pool, as tracked in Redis
+pub struct PoolState {
+  pub members: HashSet<String>,
+  // Coins that are currently checked out by the api. These must not be touched
+  pub checked_out: HashSet<String>,
+  // Pool coins that have been taken out of the pool to be merged and replaced with new coins
+  pub replaced: HashSet<String>,
+}
+
+impl PoolState {
+  /// Reads the state of the given pool shard from Redis. This is a read only operation
+  pub async fn load(redis_pool: &ConnectionPool, keys: &PoolKeys) -> Result<Self> {
+    let mut conn = redis_pool.connection().await?;
+    let members = conn.sscan(keys.pool.as_str(), SCAN_COUNT).await?;
+    let checked_out = conn.hgetall(keys.checkouts.as_str()).await?.into_keys().collect();
+
+    Ok(Self {members, checked_out, replaced: HashSet::new()})
+  }
+
+  pub fn contains(&self, coin: &Coin) -> bool {
+    self.members.contains(&coin.coin_object_id.to_hex_uncompressed())
+  }
+
+  pub fn is_checked_out(&self, coin: &Coin) -> bool {
+    self.checked_out.contains(&coin.coin_object_id.to_hex_uncompressed())
+  }
+
+  /// The pool coins whose balance dropped below the given threshold and are not checked out
+  pub fn low_coins(&self, coins: &[Coin], threshold: u64) -> Vec<String> {
+    coins.iter()
+    .filter(|c| c.balance < threshold && self.contains(c) && !self.is_checked_out(c))
+    .map(|c| c.coin_object_id.to_hex_uncompressed())
+    .collect()
+  }
+
+  /// Takes the given coins out of the pool, so that the rebalance merges them like any other coin outside of it
+  pub fn replace(&mut self, coins: impl IntoIterator<Item = String>) {
+    for coin in coins {
+      self.members.remove(&coin);
+      self.replaced.insert(coin);
+    }
+  }
+
+  /// Aggregate balance of the given coins that are part of the pool
+  pub fn balance(&self, coins: &[Coin]) -> u64 {
+    coins.iter()
+    .filter(|c| self.contains(c))
+    .map(|c| c.balance)
+    .sum()
+  }
+}
+
+/// Describes what a single rebalance will do. It is built and dry-run without signing anything, so it can
+/// either be printed for inspection or signed and executed by the CoinManager.
+#[derive(Serialize)]
+pub struct RebalancePlan {
+  pub sponsor: SuiAddress,
+  // The largest coin; all other non-pool coins are merged into it and the new pool coins are split from it
+  pub master_coin: ObjectID,
+  pub gas_coin: ObjectID,
+  pub merged_coins: Vec<ObjectID>,
+  // Aggregate balance of the pool coins before the rebalance
+  pub pool_balance: u64,
+  // Pool coins that are running low. They are merged with the rest and replaced by new coins
+  pub replaced_coins: Vec<ObjectID>,
+  pub new_coin_count: usize,
+  pub new_coin_balance: u64,
+  pub gas_price: u64,
+  pub gas_budget: u64,
+  // Net gas cost (computation + storage - rebate) reported by the dry run
+  pub estimated_gas_cost: u64,
+  // The execution errors reported by the dry run. Empty if the transaction would succeed
+  pub dry_run_errors: Vec<String>,
+  #[serde(skip)]
+  pub tx_data: TransactionData,
+}
+
+impl RebalancePlan {
+  /// Builds the rebalance transaction for the given sponsor coins. The coins that are currently in the Gas Pool,
+  /// or checked out, are not merged. Pool coins that are running low must have been taken out of the pool with
+  /// `PoolState::replace` first; they are merged and replaced by new coins.
+  pub async fn new(
+    api: &SuiClient,
+    sponsor: SuiAddress,
+    coins: Vec<Coin>,
+    pool: &PoolState,
+    targets: &PoolTargets,
+    gas_price: u64,
+  ) -> Result<Self> {
+    let non_empty_coins = coins.iter().filter(|c| c.balance > 0).count();
+    ensure!(non_empty_coins > 1, Error::NotEnoughCoins);
+
+    // Exclude the ones that are currently in the Gas Pool or in use
+    let pool_balance = pool.balance(&coins);
+    let mut input_coins = coins.into_iter()
+    .filter(|c| !pool.contains(c) && !pool.is_checked_out(c))
+    .collect::<Vec<_>>();
+
+    let replaced_coins = input_coins.iter()
+    .filter(|c| pool.replaced.contains(&c.coin_object_id.to_hex_uncompressed()))
+    .map(|c| c.coin_object_id)
+    .collect::<Vec<_>>();
+
+    ensure!(!input_coins.is_empty(), Error::InsufficientFunds);
+    let mut ptb = ProgrammableTransactionBuilder::new();
+
+    // Use the first coin as the master coin
+    // The master coin and gas payment cannot be used in the input coins that will be merged so we should
+    // remove both from the list
+    let master_coin = input_coins.remove(0);
+    let master_coin_arg = map_err!(ptb.obj(ObjectArg::ImmOrOwnedObject(master_coin.object_ref())))?;
+
+    let gas_payment_index = get_gas_payment_coin_index(&input_coins)?;
+    let gas_coin = input_coins.remove(gas_payment_index);
+
+    // 1. Merge all these coins into the master coin
+    // If the sponsor has only one coin the input_coins (which exclude the master coin) will be empty and thus
+    // we can skip the merge step in this iteration.
+    let merged_coins = input_coins.iter().map(|c| c.coin_object_id).collect::<Vec<_>>();
+
+    if input_coins.len() > 0 {
+      let input_coin_args = input_coins.into_iter()
+      .map(|c| ptb.obj(ObjectArg::ImmOrOwnedObject(c.object_ref())).expect("coin object ref"))
+      .collect::<Vec<_>>();
+
+      let merge_coin_cmd = Command::MergeCoins(master_coin_arg, input_coin_args);
+      ptb.command(merge_coin_cmd);
+    }
+
+    let sui_coin_arg_type = map_err!(TypeTag::from_str("0x2::sui::SUI"))?;
+
+    // 2. Split the master coin into enough coins, each having `coin_balance`, to reach both the count and
+    // the balance target of the pool.
+    let new_coin_count = targets.new_coin_count(pool.members.len(), pool_balance);
+
+    let amounts = vec![targets.coin_balance; new_coin_count]
+    .into_iter()
+    .map(|a| ptb.pure(a).expect("pure arg"))
+    .collect::<Vec<_>>();
+
+    // We could in theory use one single `Command::SplitCoins`. The issue is that `ptb.transfer_arg` on the
+    // newly created coin was failing. Instead we just create multiple individual calls.
+    for amount in amounts {
+      let new_coin_result = ptb.programmable_move_call(
+        SUI_FRAMEWORK_PACKAGE_ID,
+        coin::COIN_MODULE_NAME.to_owned(),
+        map_err!(Identifier::from_str("split"))?,
+        vec![sui_coin_arg_type.clone()],
+        vec![master_coin_arg, amount],
+      );
+
+      ptb.transfer_arg(sponsor, new_coin_result);
+    }
+
+    let pt = ptb.finish();
+    let tx_data = TransactionData::new_programmable(
+      sponsor,
+      vec![gas_coin.object_ref()],
+      pt,
+      GAS_BUDGET,
+      gas_price,
+    );
+
+    let mut plan = Self {
+      sponsor,
+      master_coin: master_coin.coin_object_id,
+      gas_coin: gas_coin.coin_object_id,
+      merged_coins,
+      pool_balance,
+      replaced_coins,
+      new_coin_count,
+      new_coin_balance: targets.coin_balance,
+      gas_price,
+      gas_budget: GAS_BUDGET,
+      estimated_gas_cost: 0,
+      dry_run_errors: vec![],
+      tx_data,
+    };
+
+    plan.dry_run(api).await?;
+    Ok(plan)
+  }
+
+  async fn dry_run(&mut self, api: &SuiClient) -> Result<()> {
+    let response = api
+    .read_api()
+    .dry_run_transaction_block(self.tx_data.clone())
+    .await?;
+
+    let SuiTransactionBlockEffects::V1(effects) = &response.effects;
+    if let SuiExecutionStatus::Failure {error} = &effects.status {
+      self.dry_run_errors = vec![error.clone()];
+    }
+
+    self.estimated_gas_cost = GasMeter::total_gas_used(response.effects)?;
+
+    Ok(())
+  }
+}
+
+// It will find the smallest coins that has just enough balance to pay the rebalance_coin transaction block gas cost
+fn get_gas_payment_coin_index(input_coins: &Vec<Coin>) -> Result<usize> {
+  // TODO: We need to calculate the amount of gas cost that will be required to pay for the rebalance_coin
+  // transaction block;
+  let total_gas_cost = GAS_BUDGET;
+
+  // find the smallest big enough coin
+  let pos = input_coins.iter()
+  .rev()
+  .position(|c| c.balance >= total_gas_cost)
+  .ok_or(Error::NoGasPaymentCoin)?;
+
+  // Get the original index not the reverse
+  Ok(input_coins.len() - 1 - pos)
+}
+
+/// Fetches all coins that belong to the given address. It will return a sorted array of coins according to their balance
+pub async fn fetch_coins(api: &SuiClient, owner: SuiAddress) -> Result<Vec<Coin>> {
+  let mut coins = vec![];
+  let mut cursor = None;
+
+  loop {
+    let response = api.coin_read_api().get_coins(
+      owner,
+      None,
+      cursor,
+      None,
+    )
+    .await?;
+
+    coins.extend(response.data);
+
+    if !response.has_next_page {break}
+    cursor = response.next_cursor;
+  }
+
+  coins.sort_by(|a, b| b.balance.cmp(&a.balance));
+  Ok(coins)
+}
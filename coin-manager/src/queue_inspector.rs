@@ -0,0 +1,94 @@
+use eyre::Result;
+use borsh::BorshDeserialize;
+use log::error;
+use reqwest::Url;
+use serde::Deserialize;
+use serde_json::json;
+use sui_sponsor_common::gas_pool::coin_object_producer::NewCoinObject;
+
+#[derive(Deserialize)]
+struct QueueInfo {
+  messages_ready: usize,
+}
+
+#[derive(Deserialize)]
+struct Message {
+  payload: String,
+  payload_encoding: String,
+}
+
+/// Lists the messages of a queue through the RabbitMQ management API. The messages are fetched with the
+/// `ack_requeue_true` mode i.e. the broker puts them back in the same request, so consumers miss them for a few
+/// milliseconds at most; unlike a regular consumer that would have to hold them until it's done.
+pub struct QueueInspector {
+  client: reqwest::Client,
+  // The url of the management API e.g. http://localhost:15672
+  uri: String,
+  vhost: String,
+  user: String,
+  password: Option<String>,
+}
+
+impl QueueInspector {
+  /// The credentials and the vhost are the ones of the given AMQP uri
+  pub fn try_new(uri: String, rabbitmq_uri: &str) -> Result<Self> {
+    let rabbitmq_uri = Url::parse(rabbitmq_uri)?;
+    let vhost = match rabbitmq_uri.path().trim_start_matches('/') {
+      "" => "%2F".to_string(),
+      vhost => vhost.to_string(),
+    };
+
+    Ok(Self {
+      client: reqwest::Client::new(),
+      uri: uri.trim_end_matches('/').to_string(),
+      vhost,
+      user: rabbitmq_uri.username().to_string(),
+      password: rabbitmq_uri.password().map(ToString::to_string),
+    })
+  }
+
+  /// Returns the ids of the coins in the ready messages of the given queue. A coin that's queued more than once is
+  /// returned more than once.
+  pub async fn queued_coins(&self, queue: &str) -> Result<Vec<String>> {
+    let url = format!("{}/api/queues/{}/{}", self.uri, self.vhost, queue);
+    let queue_info = self.client.get(&url)
+    .basic_auth(&self.user, self.password.as_ref())
+    .send()
+    .await?
+    .error_for_status()?
+    .json::<QueueInfo>()
+    .await?;
+
+    if queue_info.messages_ready == 0 {return Ok(vec![])}
+
+    let messages = self.client.post(format!("{url}/get"))
+    .basic_auth(&self.user, self.password.as_ref())
+    .json(&json!({
+      "count": queue_info.messages_ready,
+      "ackmode": "ack_requeue_true",
+      "encoding": "base64",
+      "truncate": 1024,
+    }))
+    .send()
+    .await?
+    .error_for_status()?
+    .json::<Vec<Message>>()
+    .await?;
+
+    let mut coins = vec![];
+
+    for message in messages {
+      let payload = match message.payload_encoding.as_str() {
+        "base64" => base64::decode(&message.payload)?,
+        _ => message.payload.into_bytes(),
+      };
+
+      match NewCoinObject::try_from_slice(&payload) {
+        Ok(msg) => coins.push(msg.id),
+        Err(error) => error!("Malformed coin object message: {:?}", error),
+      }
+    }
+
+    Ok(coins)
+  }
+}
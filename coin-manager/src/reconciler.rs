@@ -0,0 +1,252 @@
+use std::{sync::{Arc, Mutex}, collections::HashSet};
+use eyre::Result;
+use borsh::BorshDeserialize;
+use serde::Serialize;
+use log::{info, warn, error};
+use sui_sdk::rpc_types::Coin;
+use amqp_helpers::{
+  Delivery, consumer::pull_consumer::{PullConsumer, NextItem},
+  BasicAckOptions,
+};
+use sui_sponsor_common::{
+  storage::redis::ConnectionPool,
+  gas_pool::{
+    PoolKeys, now_millis,
+    coin_object_producer::{CoinObjectProducer, NewCoinObject},
+  },
+};
+use crate::{coin_manager::SCAN_COUNT, queue_inspector::QueueInspector};
+
+// Checkouts older than this are considered abandoned (e.g. the api instance holding them died) and are cleared.
+// RabbitMQ will have redelivered the corresponding messages once the api connection was closed.
+const STALE_CHECKOUT_MILLIS: u64 = 10 * 60 * 1000;
+
+/// Summary of the drift found, and repaired, by a single reconciliation run
+#[derive(Serialize, Default, Debug)]
+pub struct ReconcileReport {
+  // Pool coins that are no longer owned by the sponsor. They are removed from Redis
+  pub stale_pool_coins: Vec<String>,
+  // Pool coins that are neither in the queue nor checked out. They are published again
+  pub requeued_coins: Vec<String>,
+  // Queued coins that are no longer owned by the sponsor or no longer part of the pool index. The api drops
+  // their messages when it pulls them
+  pub dropped_queue_entries: Vec<String>,
+  // Coins that are in the queue more than once. The api drops the extra entry when it pulls a coin that's
+  // already checked out, so they are never handed out twice at the same time
+  pub duplicates: Vec<String>,
+}
+
+impl ReconcileReport {
+  pub fn has_drift(&self) -> bool {
+    !self.stale_pool_coins.is_empty()
+    || !self.requeued_coins.is_empty()
+    || !self.dropped_queue_entries.is_empty()
+    || !self.duplicates.is_empty()
+  }
+}
+
+/// The pool coins that are no longer owned by the sponsor
+pub fn stale_coins<'a>(pool_coins: &'a HashSet<String>, on_chain: &HashSet<String>) -> Vec<&'a String> {
+  pool_coins.iter().filter(|c| !on_chain.contains(*c)).collect()
+}
+
+/// The coins listed in the queue, sorted by what the api does with them when it pulls them
+#[derive(Default, Debug)]
+pub struct QueueDiff {
+  // Pool coins that are queued, once, as expected
+  pub queued: HashSet<String>,
+  // Coins that are no longer owned by the sponsor or no longer part of the pool index
+  pub dropped: Vec<String>,
+  // Coins that are queued more than once. Only the extra entries are listed
+  pub duplicates: Vec<String>,
+}
+
+impl QueueDiff {
+  pub fn new(queued_coins: Vec<String>, on_chain: &HashSet<String>, pool_coins: &HashSet<String>) -> Self {
+    let mut diff = Self::default();
+
+    for coin in queued_coins {
+      if !on_chain.contains(&coin) || !pool_coins.contains(&coin) {
+        diff.dropped.push(coin);
+      } else if !diff.queued.insert(coin.clone()) {
+        diff.duplicates.push(coin);
+      }
+    }
+
+    diff
+  }
+}
+
+/// The pool coins that are owned by the sponsor but are neither queued, checked out nor waiting in the outbox
+pub fn missing_coins(
+  pool_coins: &HashSet<String>,
+  on_chain: &HashSet<String>,
+  queued: &HashSet<String>,
+  checkouts: &HashSet<String>,
+  outbox: &HashSet<String>,
+) -> HashSet<String> {
+  pool_coins.iter()
+  .filter(|c| on_chain.contains(*c))
+  .filter(|c| !queued.contains(*c) && !checkouts.contains(*c) && !outbox.contains(*c))
+  .cloned()
+  .collect()
+}
+
+/// Verifies that the sponsor coins on chain, the pool index in Redis and the messages in the `coin_object` queue
+/// of a single shard agree with each other and repairs any drift between them.
+///
+/// The messages in the queue are listed through the RabbitMQ management API, which doesn't hold them, so the api
+/// can keep pulling coins during the reconciliation. Without it only the pool index is checked against the chain.
+pub struct Reconciler {
+  redis_pool: Arc<ConnectionPool>,
+  keys: PoolKeys,
+  // Only used to purge the queue of a sponsor that's being rotated out
+  coin_object_consumer: PullConsumer,
+  coin_object_producer: Arc<CoinObjectProducer>,
+  queue_inspector: Option<Arc<QueueInspector>>,
+  // Pool coins that could not be found in the previous run. A coin that is being returned by the api can be in
+  // flight in the retry queue and thus invisible to us; so we only requeue coins that are missing in two
+  // consecutive runs.
+  missing_coins: Mutex<HashSet<String>>,
+}
+
+impl Reconciler {
+  pub async fn try_new(
+    redis_pool: Arc<ConnectionPool>,
+    keys: PoolKeys,
+    coin_object_producer: Arc<CoinObjectProducer>,
+    queue_inspector: Option<Arc<QueueInspector>>,
+    rabbitmq_uri: &str,
+  ) -> Result<Self> {
+    let coin_object_consumer = PullConsumer::new(rabbitmq_uri, &keys.queue).await?;
+
+    Ok(Self {
+      redis_pool,
+      keys,
+      coin_object_consumer,
+      coin_object_producer,
+      queue_inspector,
+      missing_coins: Mutex::new(HashSet::new()),
+    })
+  }
+
+  /// Reads the ids of the coins currently checked out by the api and clears the abandoned ones
+  pub async fn get_checkouts(&self) -> Result<HashSet<String>> {
+    let mut conn = self.redis_pool.connection().await?;
+    let checkouts = conn.hgetall(self.keys.checkouts.as_str()).await?;
+    let now = now_millis();
+    let mut active = HashSet::new();
+
+    for (coin, checked_out_at) in checkouts {
+      let checked_out_at = checked_out_at.parse::<u64>().unwrap_or(0);
+
+      if now.saturating_sub(checked_out_at) > STALE_CHECKOUT_MILLIS {
+        warn!("Clearing abandoned checkout of coin {}", coin);
+        conn.hdel(self.keys.checkouts.as_str(), coin.as_str()).await?;
+      } else {
+        active.insert(coin);
+      }
+    }
+
+    Ok(active)
+  }
+
+  /// Consumes all ready messages from the queue without acking them. The api can't pull any coin while they are
+  /// held, so it's only used for the queue of a sponsor that's being rotated out.
+  async fn drain_queue(&self) -> Result<Vec<(String, Delivery)>> {
+    let mut deliveries = vec![];
+
+    while let Some(NextItem {delivery, ..}) = self.coin_object_consumer.next().await? {
+      match NewCoinObject::try_from_slice(&delivery.data) {
+        Ok(msg) => deliveries.push((msg.id, delivery)),
+        Err(error) => {
+          // Nothing can consume this message so there is no point keeping it around
+          error!("Dropping malformed coin object message: {:?}", error);
+          delivery.ack(BasicAckOptions::default()).await?;
+        },
+      }
+    }
+
+    Ok(deliveries)
+  }
+
+  /// Removes all ready messages from the queue and returns the ids of their coins
+  pub async fn purge_queue(&self) -> Result<Vec<String>> {
+    let mut coins = vec![];
+
+    for (coin, delivery) in self.drain_queue().await? {
+      delivery.ack(BasicAckOptions::default()).await?;
+      coins.push(coin);
+    }
+
+    Ok(coins)
+  }
+
+  /// Compares the given sponsor coins, as returned by `CoinManager::fetch_coins`, with the pool index and the queue
+  /// and repairs any drift.
+  pub async fn reconcile(&self, coins: &[Coin]) -> Result<ReconcileReport> {
+    info!("Reconciling the gas pool");
+
+    let mut report = ReconcileReport::default();
+    let on_chain = coins.iter()
+    .map(|c| c.coin_object_id.to_hex_uncompressed())
+    .collect::<HashSet<_>>();
+
+    let mut conn = self.redis_pool.connection().await?;
+    let pool_coins = conn.sscan(self.keys.pool.as_str(), SCAN_COUNT).await?;
+    let outbox = conn.sscan(self.keys.outbox.as_str(), SCAN_COUNT).await?;
+
+    // 1. Remove the pool coins that no longer exist (or are no longer ours) from the index
+    for coin in stale_coins(&pool_coins, &on_chain) {
+      conn.srem(self.keys.pool.as_str(), coin.as_str()).await?;
+      conn.srem(self.keys.outbox.as_str(), coin.as_str()).await?;
+      conn.srem(self.keys.retiring.as_str(), coin.as_str()).await?;
+      report.stale_pool_coins.push(coin.clone());
+    }
+
+    let Some(queue_inspector) = &self.queue_inspector else {
+      return Ok(Self::finish(report))
+    };
+
+    // Read the checkouts both before and after listing the queue. A coin checked out by the api in the meantime
+    // will show up in the latter.
+    let mut checkouts = self.get_checkouts().await?;
+    let queued_coins = queue_inspector.queued_coins(&self.keys.queue).await?;
+    checkouts.extend(self.get_checkouts().await?);
+
+    // 2. Go through the queued coins. Those that are no longer part of the pool, or are queued more than once, are
+    // dropped by the api when it pulls them; they are only reported here
+    let queue = QueueDiff::new(queued_coins, &on_chain, &pool_coins);
+    report.dropped_queue_entries = queue.dropped;
+    report.duplicates = queue.duplicates;
+
+    // 3. Pool coins that are nowhere to be found are published again. Coins still in the outbox will be
+    // published by the coin manager itself.
+    let missing = missing_coins(&pool_coins, &on_chain, &queue.queued, &checkouts, &outbox);
+
+    let previously_missing = std::mem::replace(&mut *self.missing_coins.lock().unwrap(), missing.clone());
+
+    for coin in missing.intersection(&previously_missing) {
+      self.coin_object_producer.new_coin_object(coin.clone()).await?;
+      report.requeued_coins.push(coin.clone());
+    }
+
+    // Requeued coins are accounted for now
+    self.missing_coins.lock().unwrap().retain(|c| !report.requeued_coins.contains(c));
+
+    Ok(Self::finish(report))
+  }
+
+  /// Logs the drift found by a reconciliation run
+  fn finish(report: ReconcileReport) -> ReconcileReport {
+    if report.has_drift() {
+      warn!("Gas pool drift found: {:?}", report);
+    }
+
+    if !report.duplicates.is_empty() {
+      error!("Possible equivocation. Coins queued more than once: {:?}", report.duplicates);
+    }
+
+    report
+  }
+}
@@ -0,0 +1,57 @@
+use eyre::Result;
+use sui_sponsor_common::{
+  storage::redis::ConnectionPool, gas_pool::{PoolKeys, stats::{load_throughput, Throughput}},
+};
+use crate::plan::PoolTargets;
+
+/// Computes the pool size from the observed request throughput instead of using static values
+#[derive(Clone, Copy)]
+pub struct AdaptiveSizing {
+  // Number of minutes of stats the throughput is computed over
+  pub window_minutes: u64,
+  // The pool should hold `headroom` times the number of coins that are in use on average
+  pub headroom: f64,
+}
+
+/// The targets and triggers the coin manager uses in a single cycle
+#[derive(Clone, Copy)]
+pub struct PoolSize {
+  pub targets: PoolTargets,
+  pub min_count: usize,
+  pub min_balance: u64,
+}
+
+impl AdaptiveSizing {
+  /// Scales the configured pool size to the recent throughput. The target coin count is bounded by the configured
+  /// min and max (i.e. `MIN_POOL_COUNT` and `MAX_POOL_CAPACITY`). The min count and balances are scaled by the same
+  /// factor so that the configured ratios are kept.
+  pub fn size(&self, throughput: &Throughput, configured: &PoolSize) -> PoolSize {
+    let max_count = configured.targets.count;
+    let min_count = configured.min_count.min(max_count);
+    let count = ((throughput.coins_in_use() * self.headroom).ceil() as usize).clamp(min_count, max_count);
+    let scale = |value: u64| (value as u128 * count as u128 / max_count.max(1) as u128) as u64;
+
+    PoolSize {
+      targets: PoolTargets {
+        count,
+        balance: scale(configured.targets.balance),
+        ..configured.targets
+      },
+      min_count: (min_count * count / max_count.max(1)).max(1),
+      min_balance: scale(configured.min_balance),
+    }
+  }
+
+  /// Loads the recent throughput from Redis and sizes the pool for it
+  pub async fn resize(
+    &self,
+    redis_pool: &ConnectionPool,
+    keys: &PoolKeys,
+    configured: &PoolSize,
+  ) -> Result<(PoolSize, Throughput)> {
+    let mut conn = redis_pool.connection().await?;
+    let throughput = load_throughput(&mut conn, keys, self.window_minutes).await?;
+
+    Ok((self.size(&throughput, configured), throughput))
+  }
+}
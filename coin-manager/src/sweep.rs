@@ -0,0 +1,31 @@
+use sui_sdk::rpc_types::Coin;
+use sui_types::base_types::SuiAddress;
+use tokio::time::Duration;
+use crate::{plan::PoolState, transfer::TRANSFER_GAS_BUDGET};
+
+// Excess below this (1 SUI) is left on the sponsor; it's not worth a transaction
+pub const MIN_SWEEP_AMOUNT: u64 = 1_000_000_000;
+
+/// Limits how much SUI sits on the hot sponsor account. Anything above the working balance is periodically
+/// transferred to a cold address.
+pub struct Sweep {
+  pub cold_address: SuiAddress,
+  // Total balance, including the Gas Pool coins, that is kept on the sponsor
+  pub working_balance: u64,
+  pub interval: Duration,
+}
+
+impl Sweep {
+  /// Returns the amount that should be transferred to the cold address, if any. Only the coins outside of the
+  /// Gas Pool can be swept and they must also cover the gas of the transfer.
+  pub fn amount(&self, pool: &PoolState, coins: &[Coin]) -> Option<u64> {
+    let total_balance = coins.iter().map(|c| c.balance).sum::<u64>();
+    let free_balance = total_balance - pool.balance(coins);
+
+    let amount = total_balance
+    .saturating_sub(self.working_balance)
+    .min(free_balance.saturating_sub(TRANSFER_GAS_BUDGET));
+
+    (amount >= MIN_SWEEP_AMOUNT).then_some(amount)
+  }
+}
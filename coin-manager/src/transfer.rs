@@ -0,0 +1,61 @@
+use eyre::{Result, ensure, eyre};
+use shared_crypto::intent::Intent;
+use sui_sdk::rpc_types::{Coin, SuiTransactionBlockResponse};
+use sui_types::{
+  base_types::SuiAddress, transaction::TransactionData,
+  programmable_transaction_builder::ProgrammableTransactionBuilder,
+};
+use sui_sponsor_common::{map_err, helpers::tx::TxManager, services::wallet::Wallet};
+use crate::error::Error;
+
+// A plain SUI transfer costs a small fraction of this
+pub const TRANSFER_GAS_BUDGET: u64 = 10_000_000;
+// Max number of coins that can be used as gas payment in a single transaction
+const MAX_GAS_PAYMENT_COINS: usize = 256;
+
+/// Sends SUI from the owner of the given coins to the recipient. The coins are used as gas payment and the amount
+/// is split from the gas coin, so they must be sorted by balance in descending order (see `fetch_coins`). When no
+/// amount is given, everything except the gas cost is transferred.
+pub async fn transfer_sui(
+  wallet: &Wallet,
+  tx_manager: &TxManager,
+  coins: Vec<Coin>,
+  recipient: SuiAddress,
+  amount: Option<u64>,
+  gas_price: u64,
+) -> Result<SuiTransactionBlockResponse> {
+  let required = amount.unwrap_or(0) + TRANSFER_GAS_BUDGET;
+  let mut payment = vec![];
+  let mut payment_balance = 0;
+
+  for coin in coins.into_iter().filter(|c| c.balance > 0).take(MAX_GAS_PAYMENT_COINS) {
+    // When transferring everything we use as many coins as we can
+    if amount.is_some() && payment_balance >= required {break}
+
+    payment_balance += coin.balance;
+    payment.push(coin.object_ref());
+  }
+
+  ensure!(payment_balance >= required, Error::InsufficientFunds);
+
+  let mut ptb = ProgrammableTransactionBuilder::new();
+
+  match amount {
+    Some(amount) => map_err!(ptb.pay_sui(vec![recipient], vec![amount]))?,
+    None => ptb.pay_all_sui(recipient),
+  }
+
+  let tx_data = TransactionData::new_programmable(
+    wallet.address(),
+    payment,
+    ptb.finish(),
+    TRANSFER_GAS_BUDGET,
+    gas_price,
+  );
+
+  let signature = wallet.sign(&tx_data, Intent::sui_transaction()).await?;
+  let response = tx_manager.send_tx(tx_data, vec![signature]).await?;
+  ensure!(!TxManager::has_errors(&response), eyre!("transfer failed: {:?}", TxManager::get_errors(&response)));
+
+  Ok(response)
+}
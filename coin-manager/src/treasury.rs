@@ -0,0 +1,218 @@
+use std::{sync::{Arc, Mutex}, collections::VecDeque, time::Instant};
+use eyre::Result;
+use log::{info, warn};
+use sui_sdk::rpc_types::Coin;
+use sui_types::base_types::SuiAddress;
+use tokio::time::Duration;
+use sui_sponsor_common::{storage::redis::ConnectionPool, gas_pool::now_millis};
+use crate::{
+  notifier::{Notifier, Alert, Severity},
+  funding::FundingService,
+};
+
+// Claimed, with the auto-funding cooldown as expiry, before each transfer from the treasury to a sponsor. Being in
+// Redis it also covers a new leader taking over right after a transfer. It's released if the funding fails, so
+// that it's retried on the next cycle.
+const AUTO_FUNDING_LOCK_KEY_PREFIX: &str = "treasury:auto_funding";
+// An alert that stays at the same severity is repeated this often
+const ALERT_REPEAT_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Alerts are raised when the sponsor balance, or its projected runway, drops below these
+pub struct Thresholds {
+  pub warning_balance: u64,
+  pub critical_balance: u64,
+  pub warning_runway: Duration,
+  pub critical_runway: Duration,
+}
+
+impl Thresholds {
+  /// The severity of the alert for the given balance and runway, if any. The runway is only known once the spend
+  /// rate could be computed.
+  pub fn severity(&self, balance: u64, runway: Option<Duration>) -> Option<Severity> {
+    let below = |min_balance: u64, min_runway: Duration| {
+      balance < min_balance || runway.map_or(false, |r| r < min_runway)
+    };
+
+    if below(self.critical_balance, self.critical_runway) {
+      Some(Severity::Critical)
+    } else if below(self.warning_balance, self.warning_runway) {
+      Some(Severity::Warning)
+    } else {
+      None
+    }
+  }
+}
+
+/// Requests funds from the treasury, through the funding service, when the sponsor runs low
+pub struct AutoFunding {
+  pub service: FundingService,
+  // Sponsor balance below which the sponsor is funded
+  pub threshold: u64,
+  // Amount transferred on each funding
+  pub amount: u64,
+  // Min time between two fundings
+  pub cooldown: Duration,
+}
+
+/// Keeps track of the total sponsor balance and how fast it's being spent, alerts the operator when either is
+/// running low and optionally funds the sponsor from the treasury.
+pub struct TreasuryMonitor {
+  redis_pool: Arc<ConnectionPool>,
+  sponsor: SuiAddress,
+  notifiers: Vec<Box<dyn Notifier>>,
+  thresholds: Thresholds,
+  // The spend rate is computed over the balance samples taken within this window
+  spend_rate_window: Duration,
+  auto_funding: Option<AutoFunding>,
+  samples: Mutex<VecDeque<(Instant, u64)>>,
+  last_alert: Mutex<Option<(Severity, Instant)>>,
+}
+
+impl TreasuryMonitor {
+  pub fn new(
+    redis_pool: Arc<ConnectionPool>,
+    sponsor: SuiAddress,
+    notifiers: Vec<Box<dyn Notifier>>,
+    thresholds: Thresholds,
+    spend_rate_window: Duration,
+    auto_funding: Option<AutoFunding>,
+  ) -> Self {
+    Self {
+      redis_pool,
+      sponsor,
+      notifiers,
+      thresholds,
+      spend_rate_window,
+      auto_funding,
+      samples: Mutex::new(VecDeque::new()),
+      last_alert: Mutex::new(None),
+    }
+  }
+
+  /// Records the given balance and returns the spend rate in MIST per second. Increases in balance (i.e. fundings)
+  /// are not counted as negative spending. Returns None until we have samples spanning some time.
+  fn record_balance(&self, balance: u64) -> Option<f64> {
+    let now = Instant::now();
+    let mut samples = self.samples.lock().unwrap();
+    samples.push_back((now, balance));
+
+    while samples.front().map_or(false, |(t, _)| now.duration_since(*t) > self.spend_rate_window) {
+      samples.pop_front();
+    }
+
+    let (oldest, _) = samples.front()?;
+    let elapsed = now.duration_since(*oldest).as_secs_f64();
+    if elapsed == 0.0 {return None}
+
+    let spent = samples.iter()
+    .zip(samples.iter().skip(1))
+    .map(|((_, prev), (_, next))| prev.saturating_sub(*next))
+    .sum::<u64>();
+
+    Some(spent as f64 / elapsed)
+  }
+
+  /// Sends the alert to all notifiers. A failing notifier does not stop the rest
+  async fn notify(&self, alert: Alert) {
+    for notifier in &self.notifiers {
+      if let Err(error) = notifier.notify(&alert).await {
+        warn!("Failed to deliver treasury alert: {:?}", error);
+      }
+    }
+  }
+
+  /// Raises an alert if the sponsor is running low. The same alert is not repeated on every call; only when
+  /// the severity increases or `ALERT_REPEAT_INTERVAL` has passed.
+  async fn alert(&self, balance: u64, runway: Option<Duration>) {
+    let severity = self.thresholds.severity(balance, runway);
+
+    let should_notify = {
+      let mut last_alert = self.last_alert.lock().unwrap();
+
+      match (severity, *last_alert) {
+        (None, None) => false,
+        (None, Some(_)) => {
+          info!("Sponsor balance is healthy again: {} MIST", balance);
+          *last_alert = None;
+          false
+        },
+        (Some(severity), last) => {
+          let notify = last.map_or(true, |(last_severity, sent_at)| {
+            severity > last_severity || sent_at.elapsed() >= ALERT_REPEAT_INTERVAL
+          });
+
+          if notify {
+            *last_alert = Some((severity, Instant::now()));
+          }
+
+          notify
+        },
+      }
+    };
+
+    if let (Some(severity), true) = (severity, should_notify) {
+      let message = match runway {
+        Some(runway) => format!(
+          "Sponsor balance is {} MIST; it will run out in {:.1} hours", balance, runway.as_secs_f64() / 3600.0,
+        ),
+        None => format!("Sponsor balance is {} MIST", balance),
+      };
+
+      self.notify(Alert {
+        severity,
+        sponsor: self.sponsor,
+        balance,
+        runway_secs: runway.map(|r| r.as_secs()),
+        message,
+      }).await;
+    }
+  }
+
+  /// Requests `AutoFunding::amount` from the treasury for the sponsor unless we did so within the cooldown
+  async fn fund(&self, auto_funding: &AutoFunding) -> Result<()> {
+    let lock_key = format!("{AUTO_FUNDING_LOCK_KEY_PREFIX}:{}", self.sponsor);
+    let mut conn = self.redis_pool.connection().await?;
+    let claimed = conn.set_nx_ex(
+      lock_key.as_str(),
+      now_millis().to_string().as_str(),
+      auto_funding.cooldown.as_secs() as usize,
+    ).await?;
+
+    if !claimed {return Ok(())}
+
+    match auto_funding.service.fund(self.sponsor, auto_funding.amount).await {
+      Ok(digest) => {
+        info!("Funded the sponsor with {} MIST from the treasury. Tx {}", auto_funding.amount, digest);
+        Ok(())
+      },
+      Err(error) => {
+        conn.delete(lock_key.as_str()).await?;
+        Err(error)
+      },
+    }
+  }
+
+  /// Checks the given sponsor coins, as returned by `fetch_coins`, alerts if the sponsor is running low and funds
+  /// it from the treasury if auto-funding is enabled
+  pub async fn check(&self, coins: &[Coin]) {
+    let balance = coins.iter().map(|c| c.balance).sum::<u64>();
+    let runway = self.record_balance(balance)
+    .filter(|rate| *rate > 0.0)
+    .and_then(|rate| Duration::try_from_secs_f64(balance as f64 / rate).ok());
+
+    self.alert(balance, runway).await;
+
+    let Some(auto_funding) = &self.auto_funding else {return};
+    if balance >= auto_funding.threshold {return}
+
+    if let Err(error) = self.fund(auto_funding).await {
+      self.notify(Alert {
+        severity: Severity::Critical,
+        sponsor: self.sponsor,
+        balance,
+        runway_secs: runway.map(|r| r.as_secs()),
+        message: format!("Auto-funding from the treasury failed: {:?}", error),
+      }).await;
+    }
+  }
+}
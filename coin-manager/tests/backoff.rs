@@ -0,0 +1,57 @@
+use std::time::Duration;
+use sui_sponsor_coin_manager::backoff::Backoff;
+
+fn secs(secs: u64) -> Duration {
+  Duration::from_secs(secs)
+}
+
+#[test]
+fn doubles_the_delay_after_each_attempt() {
+  let mut backoff = Backoff::new(secs(1), secs(60));
+  let delays = (0..4).map(|_| backoff.next_delay()).collect::<Vec<_>>();
+
+  assert_eq!(delays, vec![secs(1), secs(2), secs(4), secs(8)]);
+}
+
+#[test]
+fn caps_the_delay_at_the_max() {
+  let mut backoff = Backoff::new(secs(1), secs(5));
+  let delays = (0..5).map(|_| backoff.next_delay()).collect::<Vec<_>>();
+
+  assert_eq!(delays, vec![secs(1), secs(2), secs(4), secs(5), secs(5)]);
+}
+
+#[test]
+fn starts_over_once_reset() {
+  let mut backoff = Backoff::new(secs(1), secs(60));
+  backoff.next_delay();
+  backoff.next_delay();
+  backoff.reset();
+
+  assert_eq!(backoff.next_delay(), secs(1));
+}
+
+#[test]
+fn defaults_to_one_second_up_to_five_minutes() {
+  let mut backoff = Backoff::default();
+  assert_eq!(backoff.next_delay(), secs(1));
+
+  let last = (0..20).map(|_| backoff.next_delay()).last().unwrap();
+  assert_eq!(last, secs(300));
+}
+
+#[test]
+fn caps_the_default_backoff_at_the_given_max() {
+  let mut backoff = Backoff::capped(secs(3));
+  let delays = (0..3).map(|_| backoff.next_delay()).collect::<Vec<_>>();
+
+  assert_eq!(delays, vec![secs(1), secs(2), secs(3)]);
+}
+
+#[test]
+fn never_exceeds_the_default_max_when_capped_above_it() {
+  let mut backoff = Backoff::capped(secs(3600));
+  let last = (0..20).map(|_| backoff.next_delay()).last().unwrap();
+
+  assert_eq!(last, secs(300));
+}
@@ -0,0 +1,54 @@
+use eyre::eyre;
+use sui_sponsor_coin_manager::error::{Error, Failure};
+
+// The errors the fullnode returns when an input object is no longer at the version the transaction was built with
+const VERSION_CONFLICTS: [&str; 4] = [
+  "Transaction execution failed: ObjectVersionUnavailableForConsumption { provided_obj_ref: .. }",
+  "Object 0xabc is not available for consumption, its current version: 5",
+  "Object 0xabc is already locked by a different transaction",
+  "Error checking transaction input objects: [ObjectNotFound { object_id: 0xabc, version: None }]",
+];
+
+#[test]
+fn classifies_rebalance_failures_caused_by_version_conflicts() {
+  for conflict in VERSION_CONFLICTS {
+    let error = eyre!(Error::RebalanceFailed(vec!["InsufficientGas".to_string(), conflict.to_string()]));
+
+    assert_eq!(Failure::classify(&error), Failure::VersionConflict, "{conflict}");
+  }
+}
+
+#[test]
+fn classifies_other_errors_caused_by_version_conflicts() {
+  for conflict in VERSION_CONFLICTS {
+    assert_eq!(Failure::classify(&eyre!(conflict.to_string())), Failure::VersionConflict, "{conflict}");
+  }
+}
+
+#[test]
+fn classifies_other_rebalance_failures_as_transient() {
+  let error = eyre!(Error::RebalanceFailed(vec!["InsufficientGas".to_string()]));
+
+  assert_eq!(Failure::classify(&error), Failure::Transient);
+}
+
+#[test]
+fn classifies_unknown_errors_as_transient() {
+  assert_eq!(Failure::classify(&eyre!("connection reset by peer")), Failure::Transient);
+}
+
+#[test]
+fn classifies_a_sponsor_without_enough_coins_as_fatal() {
+  assert_eq!(Failure::classify(&eyre!(Error::NotEnoughCoins)), Failure::Fatal);
+}
+
+#[test]
+fn classifies_an_expired_lease_as_lost() {
+  assert_eq!(Failure::classify(&eyre!(Error::LeaseExpired)), Failure::LostLease);
+}
+
+#[test]
+fn classifies_a_sponsor_out_of_funds_as_insufficient_funds() {
+  assert_eq!(Failure::classify(&eyre!(Error::InsufficientFunds)), Failure::InsufficientFunds);
+  assert_eq!(Failure::classify(&eyre!(Error::NoGasPaymentCoin)), Failure::InsufficientFunds);
+}
@@ -0,0 +1,75 @@
+mod support;
+
+use sui_sponsor_coin_manager::plan::{PoolTargets, MAX_NEW_COINS};
+use support::{coin, id, pool};
+
+const COIN_BALANCE: u64 = 1_000;
+
+fn targets(count: usize, balance: u64) -> PoolTargets {
+  PoolTargets {
+    count,
+    balance,
+    coin_balance: COIN_BALANCE,
+    top_up_threshold: COIN_BALANCE / 2,
+  }
+}
+
+#[test]
+fn creates_the_coins_missing_to_reach_the_count_target() {
+  assert_eq!(targets(10, 10 * COIN_BALANCE).new_coin_count(4, 10 * COIN_BALANCE), 6);
+}
+
+#[test]
+fn creates_the_coins_missing_to_reach_the_balance_target() {
+  // The pool has enough coins, but they are running low
+  assert_eq!(targets(10, 10 * COIN_BALANCE).new_coin_count(10, 7 * COIN_BALANCE), 3);
+}
+
+#[test]
+fn rounds_a_partial_balance_deficit_up_to_a_whole_coin() {
+  assert_eq!(targets(10, 10 * COIN_BALANCE).new_coin_count(10, 10 * COIN_BALANCE - 1), 1);
+}
+
+#[test]
+fn creates_enough_coins_for_the_larger_of_both_deficits() {
+  assert_eq!(targets(10, 10 * COIN_BALANCE).new_coin_count(8, 5 * COIN_BALANCE), 5);
+  assert_eq!(targets(10, 10 * COIN_BALANCE).new_coin_count(2, 9 * COIN_BALANCE), 8);
+}
+
+#[test]
+fn creates_nothing_when_both_targets_are_met() {
+  assert_eq!(targets(10, 10 * COIN_BALANCE).new_coin_count(12, 11 * COIN_BALANCE), 0);
+}
+
+#[test]
+fn caps_the_new_coins_of_a_single_rebalance() {
+  assert_eq!(targets(10 * MAX_NEW_COINS, 0).new_coin_count(0, 0), MAX_NEW_COINS);
+  assert_eq!(targets(0, u64::MAX).new_coin_count(0, 0), MAX_NEW_COINS);
+}
+
+#[test]
+fn finds_the_low_pool_coins_that_are_not_checked_out() {
+  let low = coin(COIN_BALANCE / 4);
+  let low_checked_out = coin(COIN_BALANCE / 4);
+  let low_outside = coin(COIN_BALANCE / 4);
+  let full = coin(COIN_BALANCE);
+  let coins = vec![low.clone(), low_checked_out.clone(), low_outside.clone(), full.clone()];
+  let pool = pool(&[&low, &low_checked_out, &full], &[&low_checked_out]);
+
+  assert_eq!(pool.low_coins(&coins, COIN_BALANCE / 2), vec![id(&low)]);
+}
+
+#[test]
+fn counts_only_the_pool_coins_in_the_pool_balance() {
+  let member = coin(COIN_BALANCE);
+  let outside = coin(5 * COIN_BALANCE);
+  let mut pool = pool(&[&member], &[]);
+  let coins = vec![member.clone(), outside];
+
+  assert_eq!(pool.balance(&coins), COIN_BALANCE);
+
+  // Replaced coins are merged like any coin outside of the pool
+  pool.replace(vec![id(&member)]);
+  assert_eq!(pool.balance(&coins), 0);
+  assert!(pool.replaced.contains(&id(&member)));
+}
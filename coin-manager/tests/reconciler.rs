@@ -0,0 +1,65 @@
+use std::collections::HashSet;
+use sui_sponsor_coin_manager::reconciler::{QueueDiff, stale_coins, missing_coins};
+
+fn coins(ids: &[&str]) -> HashSet<String> {
+  ids.iter().map(ToString::to_string).collect()
+}
+
+fn queue(ids: &[&str]) -> Vec<String> {
+  ids.iter().map(ToString::to_string).collect()
+}
+
+#[test]
+fn finds_the_pool_coins_that_are_no_longer_on_chain() {
+  let pool_coins = coins(&["a", "b", "c"]);
+  let on_chain = coins(&["a", "c", "d"]);
+
+  assert_eq!(stale_coins(&pool_coins, &on_chain), vec!["b"]);
+}
+
+#[test]
+fn drops_queued_coins_that_are_not_on_chain_or_not_in_the_pool() {
+  let on_chain = coins(&["a", "b", "c"]);
+  let pool_coins = coins(&["a", "b", "d"]);
+  let diff = QueueDiff::new(queue(&["a", "c", "d", "b"]), &on_chain, &pool_coins);
+
+  assert_eq!(diff.queued, coins(&["a", "b"]));
+  assert_eq!(diff.dropped, vec!["c", "d"]);
+  assert!(diff.duplicates.is_empty());
+}
+
+#[test]
+fn reports_the_extra_entries_of_coins_queued_more_than_once() {
+  let on_chain = coins(&["a", "b"]);
+  let pool_coins = coins(&["a", "b"]);
+  let diff = QueueDiff::new(queue(&["a", "b", "a", "a"]), &on_chain, &pool_coins);
+
+  assert_eq!(diff.queued, coins(&["a", "b"]));
+  assert_eq!(diff.duplicates, vec!["a", "a"]);
+  assert!(diff.dropped.is_empty());
+}
+
+#[test]
+fn finds_the_pool_coins_that_are_nowhere_to_be_found() {
+  let pool_coins = coins(&["queued", "checked_out", "outbox", "missing", "stale"]);
+  let on_chain = coins(&["queued", "checked_out", "outbox", "missing"]);
+
+  let missing = missing_coins(
+    &pool_coins,
+    &on_chain,
+    &coins(&["queued"]),
+    &coins(&["checked_out"]),
+    &coins(&["outbox"]),
+  );
+
+  // Stale coins are removed from the pool rather than published again
+  assert_eq!(missing, coins(&["missing"]));
+}
+
+#[test]
+fn finds_nothing_missing_when_every_pool_coin_is_accounted_for() {
+  let pool_coins = coins(&["a", "b"]);
+  let missing = missing_coins(&pool_coins, &pool_coins, &coins(&["a"]), &coins(&["b"]), &HashSet::new());
+
+  assert!(missing.is_empty());
+}
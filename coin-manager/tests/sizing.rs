@@ -0,0 +1,77 @@
+use sui_sponsor_common::gas_pool::stats::Throughput;
+use sui_sponsor_coin_manager::{plan::PoolTargets, sizing::{AdaptiveSizing, PoolSize}};
+
+fn sizing(headroom: f64) -> AdaptiveSizing {
+  AdaptiveSizing {window_minutes: 5, headroom}
+}
+
+fn configured(min_count: usize) -> PoolSize {
+  PoolSize {
+    targets: PoolTargets {
+      count: 100,
+      balance: 100_000,
+      coin_balance: 1_000,
+      top_up_threshold: 500,
+    },
+    min_count,
+    min_balance: 10_000,
+  }
+}
+
+fn throughput(checkouts_per_sec: f64, avg_hold_millis: f64) -> Throughput {
+  Throughput {checkouts_per_sec, avg_hold_millis}
+}
+
+#[test]
+fn sizes_the_pool_for_the_coins_in_use_times_the_headroom() {
+  // 10 checkouts per second, each held for 2 seconds, keep 20 coins in use
+  let size = sizing(2.0).size(&throughput(10.0, 2_000.0), &configured(10));
+
+  assert_eq!(size.targets.count, 40);
+  assert_eq!(size.targets.balance, 40_000);
+  assert_eq!(size.min_count, 4);
+  assert_eq!(size.min_balance, 4_000);
+}
+
+#[test]
+fn rounds_the_coin_count_up() {
+  let size = sizing(1.5).size(&throughput(7.5, 2_000.0), &configured(10));
+
+  assert_eq!(size.targets.count, 23);
+}
+
+#[test]
+fn keeps_at_least_the_configured_min_count() {
+  let size = sizing(2.0).size(&throughput(0.0, 0.0), &configured(10));
+
+  assert_eq!(size.targets.count, 10);
+  assert_eq!(size.targets.balance, 10_000);
+  assert_eq!(size.min_count, 1);
+  assert_eq!(size.min_balance, 1_000);
+}
+
+#[test]
+fn never_exceeds_the_configured_max_count() {
+  let size = sizing(2.0).size(&throughput(1_000.0, 1_000.0), &configured(10));
+
+  assert_eq!(size.targets.count, 100);
+  assert_eq!(size.targets.balance, 100_000);
+  assert_eq!(size.min_count, 10);
+  assert_eq!(size.min_balance, 10_000);
+}
+
+#[test]
+fn bounds_the_min_count_by_the_max_count() {
+  let size = sizing(2.0).size(&throughput(0.0, 0.0), &configured(200));
+
+  assert_eq!(size.targets.count, 100);
+  assert_eq!(size.min_count, 100);
+}
+
+#[test]
+fn keeps_the_coin_balance_and_top_up_threshold() {
+  let size = sizing(2.0).size(&throughput(10.0, 2_000.0), &configured(10));
+
+  assert_eq!(size.targets.coin_balance, 1_000);
+  assert_eq!(size.targets.top_up_threshold, 500);
+}
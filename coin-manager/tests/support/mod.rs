@@ -0,0 +1,32 @@
+#![allow(dead_code)]
+
+use std::collections::HashSet;
+use sui_sdk::rpc_types::Coin;
+use sui_types::{base_types::random_object_ref, digests::TransactionDigest};
+use sui_sponsor_coin_manager::plan::PoolState;
+
+pub fn coin(balance: u64) -> Coin {
+  let (coin_object_id, version, digest) = random_object_ref();
+
+  Coin {
+    coin_type: "0x2::sui::SUI".to_string(),
+    coin_object_id,
+    version,
+    digest,
+    balance,
+    previous_transaction: TransactionDigest::random(),
+  }
+}
+
+pub fn id(coin: &Coin) -> String {
+  coin.coin_object_id.to_hex_uncompressed()
+}
+
+/// A pool holding the given coins, of which the given ones are checked out
+pub fn pool(members: &[&Coin], checked_out: &[&Coin]) -> PoolState {
+  PoolState {
+    members: members.iter().map(|c| id(c)).collect(),
+    checked_out: checked_out.iter().map(|c| id(c)).collect(),
+    replaced: HashSet::new(),
+  }
+}
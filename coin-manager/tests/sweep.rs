@@ -0,0 +1,82 @@
+mod support;
+
+use std::time::Duration;
+use sui_types::base_types::SuiAddress;
+use sui_sponsor_coin_manager::{sweep::{Sweep, MIN_SWEEP_AMOUNT}, transfer::TRANSFER_GAS_BUDGET};
+use support::{coin, pool};
+
+const SUI: u64 = 1_000_000_000;
+
+fn sweep(working_balance: u64) -> Sweep {
+  Sweep {
+    cold_address: SuiAddress::random_for_testing_only(),
+    working_balance,
+    interval: Duration::from_secs(60 * 60),
+  }
+}
+
+#[test]
+fn sweeps_nothing_at_or_below_the_working_balance() {
+  let free = coin(10 * SUI);
+  let coins = vec![free.clone()];
+  let pool = pool(&[], &[]);
+
+  assert_eq!(sweep(10 * SUI).amount(&pool, &coins), None);
+  assert_eq!(sweep(20 * SUI).amount(&pool, &coins), None);
+}
+
+#[test]
+fn sweeps_the_excess_over_the_working_balance() {
+  let pool_coin = coin(5 * SUI);
+  let free = coin(20 * SUI);
+  let coins = vec![pool_coin.clone(), free.clone()];
+  let pool = pool(&[&pool_coin], &[]);
+
+  // The pool coins count towards the working balance
+  assert_eq!(sweep(10 * SUI).amount(&pool, &coins), Some(15 * SUI));
+}
+
+#[test]
+fn never_sweeps_the_gas_pool_coins() {
+  let pool_coin = coin(20 * SUI);
+  let checked_out = coin(20 * SUI);
+  let free = coin(5 * SUI);
+  let coins = vec![pool_coin.clone(), checked_out.clone(), free.clone()];
+  let pool = pool(&[&pool_coin, &checked_out], &[&checked_out]);
+
+  // Only the free coins can be swept, and they also pay for the transfer
+  assert_eq!(sweep(10 * SUI).amount(&pool, &coins), Some(5 * SUI - TRANSFER_GAS_BUDGET));
+}
+
+#[test]
+fn sweeps_nothing_when_the_free_coins_only_cover_the_gas() {
+  let pool_coin = coin(20 * SUI);
+  let free = coin(TRANSFER_GAS_BUDGET);
+  let coins = vec![pool_coin.clone(), free.clone()];
+  let pool = pool(&[&pool_coin], &[]);
+
+  assert_eq!(sweep(0).amount(&pool, &coins), None);
+}
+
+#[test]
+fn sweeps_nothing_below_the_min_sweep_amount() {
+  let free = coin(10 * SUI + MIN_SWEEP_AMOUNT - 1);
+  let coins = vec![free.clone()];
+  let pool = pool(&[], &[]);
+
+  assert_eq!(sweep(10 * SUI).amount(&pool, &coins), None);
+}
+
+#[test]
+fn sweeps_exactly_the_min_sweep_amount() {
+  let free = coin(10 * SUI + MIN_SWEEP_AMOUNT);
+  let coins = vec![free.clone()];
+  let pool = pool(&[], &[]);
+
+  assert_eq!(sweep(10 * SUI).amount(&pool, &coins), Some(MIN_SWEEP_AMOUNT));
+}
+
+#[test]
+fn sweeps_nothing_without_any_coins() {
+  assert_eq!(sweep(0).amount(&pool(&[], &[]), &[]), None);
+}
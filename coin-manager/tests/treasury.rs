@@ -0,0 +1,47 @@
+use std::time::Duration;
+use sui_sponsor_coin_manager::{notifier::Severity, treasury::Thresholds};
+
+const HOUR: Duration = Duration::from_secs(60 * 60);
+
+fn thresholds() -> Thresholds {
+  Thresholds {
+    warning_balance: 1_000,
+    critical_balance: 100,
+    warning_runway: 24 * HOUR,
+    critical_runway: HOUR,
+  }
+}
+
+#[test]
+fn does_not_alert_above_all_thresholds() {
+  assert_eq!(thresholds().severity(1_000, None), None);
+  assert_eq!(thresholds().severity(1_000, Some(24 * HOUR)), None);
+}
+
+#[test]
+fn warns_below_the_warning_balance() {
+  assert_eq!(thresholds().severity(999, None), Some(Severity::Warning));
+  assert_eq!(thresholds().severity(100, Some(48 * HOUR)), Some(Severity::Warning));
+}
+
+#[test]
+fn warns_below_the_warning_runway() {
+  assert_eq!(thresholds().severity(10_000, Some(24 * HOUR - Duration::from_secs(1))), Some(Severity::Warning));
+}
+
+#[test]
+fn is_critical_below_the_critical_balance() {
+  assert_eq!(thresholds().severity(99, None), Some(Severity::Critical));
+  assert_eq!(thresholds().severity(0, Some(48 * HOUR)), Some(Severity::Critical));
+}
+
+#[test]
+fn is_critical_below_the_critical_runway() {
+  assert_eq!(thresholds().severity(10_000, Some(HOUR - Duration::from_secs(1))), Some(Severity::Critical));
+}
+
+#[test]
+fn reports_the_highest_severity_reached() {
+  // The balance only warrants a warning but the runway is critical
+  assert_eq!(thresholds().severity(500, Some(Duration::from_secs(60))), Some(Severity::Critical));
+}
@@ -0,0 +1,36 @@
+//! Prints the billing report of the ledger configured with `LEDGER_BACKEND` and `LEDGER_PATH`. The dates are UTC;
+//! `from` is inclusive and `to` exclusive:
+//!
+//! ```bash
+//! cargo run -p sui-sponsor-common --bin billing -- <csv|json> <day|month> [from YYYY-MM-DD] [to YYYY-MM-DD]
+//! ```
+use std::env;
+use envconfig::Envconfig;
+use eyre::{Result, ContextCompat, ensure};
+use sui_sponsor_common::{
+  utils::config::LedgerConfig,
+  ledger::{self, billing::{BillingQuery, billing_report, render}},
+};
+
+#[tokio::main]
+async fn main() -> Result<()> {
+  let args = env::args().collect::<Vec<_>>();
+  let usage = "usage: billing <csv|json> <day|month> [from YYYY-MM-DD] [to YYYY-MM-DD]";
+  ensure!(args.len() >= 3, usage);
+
+  let format = args[1].parse()?;
+  let query = BillingQuery {
+    period: args[2].parse()?,
+    from: args.get(3).cloned(),
+    to: args.get(4).cloned(),
+  };
+
+  let config = LedgerConfig::init_from_env()?;
+  let backend = config.backend.context("LEDGER_BACKEND is not set")?;
+  let path = config.path.context("LEDGER_PATH is not set")?;
+  let ledger = ledger::open(backend, &path)?;
+
+  print!("{}", render(&billing_report(&*ledger, &query).await?, format)?);
+
+  Ok(())
+}
@@ -0,0 +1,41 @@
+//! Creates an encrypted keystore, as loaded by `SPONSOR_KEYSTORE`, from a key in a Sui CLI keystore:
+//!
+//! ```bash
+//! cargo run -p sui-sponsor-common --bin keystore -- encrypt ~/.sui/sui_config/sui.keystore sponsor.keystore [address]
+//! ```
+use std::{env, fs, io::Write, os::unix::fs::OpenOptionsExt, str::FromStr};
+use eyre::{Result, eyre, ensure};
+use zeroize::Zeroizing;
+use sui_types::base_types::SuiAddress;
+use sui_sponsor_common::utils::keystore::{EncryptedKeystore, load_cli_keystore};
+
+fn main() -> Result<()> {
+  let args = env::args().collect::<Vec<_>>();
+  let usage = "usage: keystore encrypt <sui keystore> <output> [address]";
+  ensure!(args.len() >= 4 && args[1] == "encrypt", usage);
+
+  let address = args.get(4)
+  .map(|a| SuiAddress::from_str(a).map_err(|e| eyre!(e.to_string())))
+  .transpose()?;
+
+  let keys = Zeroizing::new(serde_json::from_str::<Vec<String>>(&Zeroizing::new(fs::read_to_string(&args[2])?))?);
+  let keypair = load_cli_keystore(&keys, address)?;
+
+  let passphrase = Zeroizing::new(rpassword::prompt_password("New passphrase: ")?);
+  let confirmation = Zeroizing::new(rpassword::prompt_password("Repeat passphrase: ")?);
+  ensure!(*passphrase == *confirmation, "passphrases do not match");
+
+  let keystore = EncryptedKeystore::encrypt(&keypair, &passphrase)?;
+
+  // Only the owner can read the keystore
+  fs::OpenOptions::new()
+  .write(true)
+  .create_new(true)
+  .mode(0o600)
+  .open(&args[3])?
+  .write_all(serde_json::to_string_pretty(&keystore)?.as_bytes())?;
+
+  println!("Encrypted the key of {} into {}", SuiAddress::from(&keypair.public()), args[3]);
+
+  Ok(())
+}
@@ -8,29 +8,35 @@ pub struct NewCoinObject {
   pub id: String,
 }
 
-pub struct CoinObjectProducer(RetryProducer);
+pub struct CoinObjectProducer {
+  producer: RetryProducer,
+  // The queue of the shard the coins are published to. It's also the name of the exchange
+  queue: String,
+  routing_key: String,
+}
 
 impl CoinObjectProducer {
-  pub async fn try_new(rabbitmq_uri: String, retry_ttl: u32) -> Result<Self> {
+  pub async fn try_new(rabbitmq_uri: String, queue: String, retry_ttl: u32) -> Result<Self> {
+    let routing_key = format!("{queue}.new");
     let producer = RetryProducer::new(
       &rabbitmq_uri,
-      &"coin_object",
-      &"coin_object",
-      &"coin_object.new",
+      &queue,
+      &queue,
+      &routing_key,
       retry_ttl,
       None
     )
     .await
     .unwrap();
 
-    Ok(Self(producer))
+    Ok(Self {producer, queue, routing_key})
   }
 
   pub async fn new_coin_object(&self, id: String) -> Result<()> {
     let msg = NewCoinObject {id};
 
-    self.0
-    .publish(&"coin_object", &"coin_object.new", &msg.try_to_vec().unwrap(), true)
+    self.producer
+    .publish(&self.queue, &self.routing_key, &msg.try_to_vec().unwrap(), true)
     .await
   }
 }
@@ -1,15 +1,16 @@
 pub mod coin_object_producer;
+pub mod stats;
 
 use std::{
-  sync::Arc, time::{Duration, SystemTime},
+  sync::Arc, str::FromStr, time::{Duration, SystemTime, UNIX_EPOCH},
 };
 use tokio::{self, time};
 use dashmap::DashMap;
 use borsh::BorshDeserialize;
-use log::info;
-use eyre::{Result, ContextCompat, ensure};
+use log::{info, warn, error};
+use eyre::{Result, ContextCompat, eyre};
 use sui_sdk::SuiClient;
-use sui_types::base_types::{ObjectRef, ObjectID};
+use sui_types::base_types::{ObjectRef, ObjectID, SuiAddress};
 use amqp_helpers::{
   Delivery, consumer::pull_consumer::{PullConsumer, NextItem},
   BasicNackOptions, BasicAckOptions,
@@ -17,16 +18,119 @@ use amqp_helpers::{
 use crate::{helpers::object::get_object_ref, storage::redis::ConnectionPool};
 use self::coin_object_producer::NewCoinObject;
 
-const GAS_KEY_PREFIX: &str = "gas:";
+/// Redis set holding the ids of all coin objects that are currently part of the Gas Pool
+///
+/// These are the key names used before sharding was introduced. See `PoolKeys` for the keys of each shard.
+pub const GAS_POOL_KEY: &str = "gas_pool";
+/// Redis set holding the ids of newly created pool coins that have not been published to RabbitMQ yet
+pub const GAS_POOL_OUTBOX_KEY: &str = "gas_pool:outbox";
+/// Redis hash with the ids of the coins that are currently checked out by a client and the unix timestamp, in
+/// milliseconds, at which they were checked out
+pub const GAS_POOL_CHECKOUTS_KEY: &str = "gas_pool:checkouts";
+/// Redis set with the pool coins the coin manager wants to shrink the pool by. These are removed from the pool
+/// the next time they are returned
+pub const GAS_POOL_RETIRING_KEY: &str = "gas_pool:retiring";
+/// Prefix of the individual keys that used to track pool membership before the `GAS_POOL_KEY` set was introduced.
+/// It is only used to migrate existing deployments.
+pub const LEGACY_GAS_KEY_PREFIX: &str = "gas:";
+/// RabbitMQ queue, and exchange, the pool coins are published to
+pub const COIN_OBJECT_QUEUE: &str = "coin_object";
+/// The RedLock resource all coin manager instances compete for. Only the holder is allowed to rebalance coins
+pub const COIN_MANAGER_LEADER_LOCK: &str = "coin_manager:leader";
+/// Redis key holding the address of the sponsor whose shard uses the unsharded keys. It's recorded the first time
+/// the service starts, so that adding, removing or reordering sponsors never moves an existing pool.
+pub const UNSHARDED_SPONSOR_KEY: &str = "gas_pool:unsharded_sponsor";
+
+/// The Redis keys and the RabbitMQ queue that hold the state of a single Gas Pool shard. Each sponsor account
+/// has its own shard.
+#[derive(Clone, Debug)]
+pub struct PoolKeys {
+  pub pool: String,
+  pub outbox: String,
+  pub checkouts: String,
+  pub retiring: String,
+  // Prefix of the per minute stats hashes
+  pub stats: String,
+  pub queue: String,
+  // RedLock resource the coin manager instances of the shard compete for
+  pub leader_lock: String,
+}
+
+impl PoolKeys {
+  /// The keys of the given sponsor's shard. The sponsor recorded in `UNSHARDED_SPONSOR_KEY` keeps using the
+  /// unsharded keys
+  pub fn new(sponsor: SuiAddress, unsharded_sponsor: SuiAddress) -> Self {
+    if sponsor == unsharded_sponsor {Self::unsharded()} else {Self::for_sponsor(sponsor)}
+  }
+
+  /// Returns the sponsor whose shard uses the unsharded keys. If none has been recorded yet, the given sponsor
+  /// is recorded.
+  pub async fn claim_unsharded_sponsor(redis_pool: &ConnectionPool, sponsor: SuiAddress) -> Result<SuiAddress> {
+    let mut conn = redis_pool.connection().await?;
+    conn.set_nx(UNSHARDED_SPONSOR_KEY, sponsor.to_string().as_str()).await?;
+
+    let unsharded_sponsor = conn.get(UNSHARDED_SPONSOR_KEY).await?;
+    SuiAddress::from_str(&unsharded_sponsor).map_err(|e| eyre!(e.to_string()))
+  }
+
+  /// Whether these are the unsharded keys
+  pub fn is_unsharded(&self) -> bool {
+    self.pool == GAS_POOL_KEY
+  }
+
+  /// The keys used before sharding was introduced. The first sponsor of a deployment keeps using them, so
+  /// existing deployments keep their pool.
+  pub fn unsharded() -> Self {
+    Self {
+      pool: GAS_POOL_KEY.to_string(),
+      outbox: GAS_POOL_OUTBOX_KEY.to_string(),
+      checkouts: GAS_POOL_CHECKOUTS_KEY.to_string(),
+      retiring: GAS_POOL_RETIRING_KEY.to_string(),
+      stats: format!("{GAS_POOL_KEY}:stats"),
+      queue: COIN_OBJECT_QUEUE.to_string(),
+      leader_lock: COIN_MANAGER_LEADER_LOCK.to_string(),
+    }
+  }
+
+  /// The keys of the shard that belongs to the given sponsor
+  pub fn for_sponsor(sponsor: SuiAddress) -> Self {
+    Self {
+      pool: format!("{GAS_POOL_KEY}:{sponsor}"),
+      outbox: format!("{GAS_POOL_OUTBOX_KEY}:{sponsor}"),
+      checkouts: format!("{GAS_POOL_CHECKOUTS_KEY}:{sponsor}"),
+      retiring: format!("{GAS_POOL_RETIRING_KEY}:{sponsor}"),
+      stats: format!("{GAS_POOL_KEY}:stats:{sponsor}"),
+      queue: format!("{COIN_OBJECT_QUEUE}.{sponsor}"),
+      leader_lock: format!("{COIN_MANAGER_LEADER_LOCK}:{sponsor}"),
+    }
+  }
+}
+
+/// Returns the current unix timestamp in milliseconds
+pub fn now_millis() -> u64 {
+  SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64
+}
+
+/// The outcome of checking out a coin pulled from the queue
+enum Checkout {
+  Available(ObjectID),
+  // The coin is already checked out i.e. it's queued more than once
+  Duplicate,
+  // The coin has been removed from the pool since it was queued
+  NotInPool,
+}
 
 struct DeliveryInfo {
   delivery: Delivery,
   created_at: SystemTime,
+  // The sender of the transaction the coin was handed out for
+  sender: SuiAddress,
 }
 
 pub struct GasPool {
   api: Arc<SuiClient>,
   redis_pool: Arc<ConnectionPool>,
+  keys: PoolKeys,
   coin_object_consumer: PullConsumer,
   // We need to delivery object to ack/nack messages we receive from RabbitMQ. The process of requesting and confirming
   // gas object is asynchronous. Client first request the GasData object which we get from the queue. Client then will sign
@@ -41,17 +145,19 @@ impl GasPool {
   pub async fn try_new(
     api: Arc<SuiClient>,
     redis_pool: Arc<ConnectionPool>,
+    keys: PoolKeys,
     rabbitmq_uri: &str,
   ) -> Self {
 
     let coin_object_consumer = PullConsumer::new(
       rabbitmq_uri,
-      "coin_object",
+      &keys.queue,
     ).await.expect("create consumer");
 
     Self {
       api,
       redis_pool,
+      keys,
       coin_object_consumer,
       pending_deliveries: DashMap::new(),
     }
@@ -89,46 +195,153 @@ impl GasPool {
 
         // remove all stalled object ids from the local pending_deliveries Map
         for object_id in staled_object_ids {
-          let _ = this.pending_deliveries.remove(&object_id).context("coin id not found");
+          if let Some((_, delivery_info)) = this.pending_deliveries.remove(&object_id) {
+            let _ = this.release_checkout(&object_id, delivery_info.created_at).await;
+          }
         }
       }
     });
   }
 
+  /// Removes the given coin from the checkouts that are tracked in Redis and records for how long it was held
+  async fn release_checkout(&self, coin_object_id: &str, checked_out_at: SystemTime) -> Result<()> {
+    let mut conn = self.redis_pool.connection().await?;
+    conn.hdel(self.keys.checkouts.as_str(), coin_object_id).await?;
+
+    // Stats are best effort; they should never fail the request
+    let hold_millis = checked_out_at.elapsed().map(|d| d.as_millis() as u64).unwrap_or(0);
+    if let Err(error) = stats::record_release(&mut conn, &self.keys, hold_millis).await {
+      warn!("Failed to record gas coin release: {:?}", error);
+    }
+
+    Ok(())
+  }
+
+  /// Number of coins this instance has handed out and are not yet returned. Used to pick the least loaded shard
+  pub fn pending_count(&self) -> usize {
+    self.pending_deliveries.len()
+  }
+
+  /// Returns true if this instance has handed out the given coin for a transaction of the given sender and the coin
+  /// has not been returned yet
+  pub fn is_checked_out_for(&self, coin_object_id: ObjectID, sender: SuiAddress) -> bool {
+    self.pending_deliveries.get(&coin_object_id.to_hex_uncompressed())
+    .map_or(false, |delivery_info| delivery_info.sender == sender)
+  }
+
+  /// Returns true if the coin manager has asked for the given coin to be removed from the pool
+  pub async fn is_retiring(&self, coin_object_id: ObjectID) -> Result<bool> {
+    let mut conn = self.redis_pool.connection().await?;
+    conn.sismember(self.keys.retiring.clone(), coin_object_id.to_hex_uncompressed()).await
+  }
+
   /// Returns the given gas coin back to the pool so it can be used in another transaction.
   /// We nack the message so it can be put back to the queue. We use a retry consumer so there is already DLX
   /// and other queue setup that will make sure msg will be put back to the queue after the nack.
   pub async fn return_gas_object(&self, coin_object_id: ObjectID) -> Result<()> {
-    let (_, delivery_info) = self.pending_deliveries.remove(&coin_object_id.to_hex_uncompressed()).context("coin id not found")?;
+    let coin_object_id_str = coin_object_id.to_hex_uncompressed();
+    let (_, delivery_info) = self.pending_deliveries.remove(&coin_object_id_str).context("coin id not found")?;
     delivery_info.delivery.nack(BasicNackOptions::default()).await?;
 
-    Ok(())
+    // Note that we remove the checkout only after the message is back in the queue. This way the coin is always
+    // either in the queue or in the checkouts, which is what the coin manager reconciliation relies on.
+    self.release_checkout(&coin_object_id_str, delivery_info.created_at).await
   }
 
   /// Core gas pool logic. It will make sure that a safe Gas Coin Object will be used. This means
   /// that we will not risk equiovocation of the Gas objects because a locking mechanism will make sure
   /// that the same Gas Coin will not be used in more than one parallel transactions
-  pub async fn gas_object(&self) -> Result<ObjectRef> {
-    let NextItem {
-      delivery,
-      retry_count: _,
-    } = self.coin_object_consumer.next().await?.context("Gas pool empty")?;
-    let new_coin_object = NewCoinObject::try_from_slice(&delivery.data).unwrap();
+  ///
+  /// The checkout is recorded in Redis only if the coin is not checked out already, by this or any other api
+  /// instance, and only if it's still part of the pool. Queue entries that fail either check are duplicates or
+  /// leftovers of coins that have been removed from the pool, so they are dropped and the next coin is tried.
+  ///
+  /// The coin is handed out for a transaction of the given sender, and only a transaction of that sender can use it.
+  pub async fn gas_object(&self, sender: SuiAddress) -> Result<ObjectRef> {
+    loop {
+      let NextItem {
+        delivery,
+        retry_count: _,
+      } = self.coin_object_consumer.next().await?.context("Gas pool empty")?;
+
+      let Ok(new_coin_object) = NewCoinObject::try_from_slice(&delivery.data) else {
+        error!("Dropping malformed coin object message");
+        delivery.ack(BasicAckOptions::default()).await?;
+        continue;
+      };
 
+      let checkout = self.check_out(&new_coin_object.id, delivery.redelivered).await;
+      let coin_object_id = match checkout {
+        Ok(Checkout::Available(coin_object_id)) => coin_object_id,
+        Ok(Checkout::Duplicate) => {
+          error!("Possible equivocation. Dropping duplicate queue entry of coin {}", new_coin_object.id);
+          delivery.ack(BasicAckOptions::default()).await?;
+          continue;
+        },
+        Ok(Checkout::NotInPool) => {
+          warn!("Dropping queue entry of coin {} that is no longer part of the pool", new_coin_object.id);
+          delivery.ack(BasicAckOptions::default()).await?;
+          continue;
+        },
+        Err(error) => {
+          // Put the coin back straight away; otherwise it would be held by this instance until it restarts
+          delivery.nack(BasicNackOptions::default()).await?;
+          return Err(error)
+        },
+      };
+
+      // Store the msg delivery so we can later nack the message i.e. put it back to the queue.
+      self.pending_deliveries.insert(new_coin_object.id.clone(), DeliveryInfo {
+        delivery,
+        created_at: SystemTime::now(),
+        sender,
+      });
+
+      return match get_object_ref(Arc::clone(&self.api), coin_object_id).await {
+        Ok(object_ref) => Ok(object_ref),
+        Err(error) => {
+          let _ = self.return_gas_object(coin_object_id).await;
+          Err(error)
+        },
+      }
+    }
+  }
+
+  /// Records the checkout of the given coin in Redis, so that the coin manager knows it's in use, unless the coin
+  /// is already checked out or it's no longer part of the pool. RabbitMQ redelivers the messages of an api instance
+  /// that dies while holding them, in which case its checkout is taken over.
+  async fn check_out(&self, coin_object_id: &str, redelivered: bool) -> Result<Checkout> {
     // This should never happen (in theory) based on the algorithm we use. There can only be one message
-    // pointing to the same coin object id in the queue. And since we add the object id back to the pool
-    // after we have confirmed the previous transaction is executed, this scenario should be consired
-    // impossible
-    ensure!(!self.pending_deliveries.contains_key(&new_coin_object.id), "Possible equivocation");
-
-    let coin_object_id = ObjectID::from_hex_literal(&new_coin_object.id)?;
-    // Store the msg delivery so we can later nack the message i.e. put it back to the queue.
-    self.pending_deliveries.insert(new_coin_object.id, DeliveryInfo {
-      delivery,
-      created_at: SystemTime::now(),
-    });
+    // pointing to the same coin object id in the queue. Still, the reconciler may find duplicates
+    if self.pending_deliveries.contains_key(coin_object_id) {return Ok(Checkout::Duplicate)}
+    let parsed_coin_object_id = ObjectID::from_hex_literal(coin_object_id)?;
+
+    let mut conn = self.redis_pool.connection().await?;
+    let (checked_out, in_pool) = conn.hsetnx_sismember(
+      self.keys.checkouts.as_str(),
+      coin_object_id,
+      now_millis().to_string().as_str(),
+      self.keys.pool.as_str(),
+      coin_object_id,
+    ).await?;
+
+    if !checked_out {
+      if !redelivered {return Ok(Checkout::Duplicate)}
 
-    get_object_ref(Arc::clone(&self.api), coin_object_id).await
+      warn!("Taking over the checkout of coin {} abandoned by another instance", coin_object_id);
+      conn.hset(self.keys.checkouts.as_str(), coin_object_id, now_millis().to_string().as_str()).await?;
+    }
+
+    if !in_pool {
+      conn.hdel(self.keys.checkouts.as_str(), coin_object_id).await?;
+      return Ok(Checkout::NotInPool)
+    }
+
+    if let Err(error) = stats::record_checkout(&mut conn, &self.keys).await {
+      warn!("Failed to record gas coin checkout: {:?}", error);
+    }
+
+    Ok(Checkout::Available(parsed_coin_object_id))
   }
 
   /// Removes the given gas coin object from the pool
@@ -137,14 +350,15 @@ impl GasPool {
     let mut conn = self.redis_pool.connection().await?;
 
     // 1. delete from Redis
-    conn.delete(format!("{GAS_KEY_PREFIX}{coin_object_id_str}")).await?;
+    conn.srem(self.keys.pool.as_str(), coin_object_id_str.as_str()).await?;
+    conn.srem(self.keys.retiring.as_str(), coin_object_id_str.as_str()).await?;
 
     // 2. remove from RabbitMQ
     let (_, delivery_info) = self.pending_deliveries.remove(&coin_object_id_str).context("coin id not found")?;
     // Ack here has the effect of the message being considered processed and thus removed from the queue
     delivery_info.delivery.ack(BasicAckOptions::default()).await?;
 
-    Ok(())
+    self.release_checkout(&coin_object_id_str, delivery_info.created_at).await
   }
 }
 
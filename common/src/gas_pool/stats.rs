@@ -0,0 +1,73 @@
+use eyre::Result;
+use crate::storage::redis::Redis;
+use super::{now_millis, PoolKeys};
+
+// Stats are aggregated in one Redis hash per minute
+const BUCKET_MILLIS: u64 = 60_000;
+// How long each bucket is kept around
+const BUCKET_TTL_SECS: usize = 60 * 60;
+const CHECKOUTS_FIELD: &str = "checkouts";
+const RELEASES_FIELD: &str = "releases";
+const HOLD_MILLIS_FIELD: &str = "hold_millis";
+
+fn bucket_key(keys: &PoolKeys, bucket: u64) -> String {
+  format!("{}:{bucket}", keys.stats)
+}
+
+fn current_bucket() -> u64 {
+  now_millis() / BUCKET_MILLIS
+}
+
+/// Records a gas coin checkout i.e. a `/tx/gas` request that was served from the pool
+pub async fn record_checkout(conn: &mut Redis, keys: &PoolKeys) -> Result<()> {
+  conn.hincrby_ex(bucket_key(keys, current_bucket()), &[(CHECKOUTS_FIELD.to_string(), 1)], BUCKET_TTL_SECS).await
+}
+
+/// Records that a checked out coin was released i.e. returned to or removed from the pool, after being held
+/// for the given time
+pub async fn record_release(conn: &mut Redis, keys: &PoolKeys, hold_millis: u64) -> Result<()> {
+  conn.hincrby_ex(
+    bucket_key(keys, current_bucket()),
+    &[(RELEASES_FIELD.to_string(), 1), (HOLD_MILLIS_FIELD.to_string(), hold_millis)],
+    BUCKET_TTL_SECS,
+  ).await
+}
+
+/// Gas pool usage over a period of time
+#[derive(Debug, Default)]
+pub struct Throughput {
+  pub checkouts_per_sec: f64,
+  // Average time a coin is held by a client between checkout and release
+  pub avg_hold_millis: f64,
+}
+
+impl Throughput {
+  /// Average number of coins that are checked out at any given time (Little's law)
+  pub fn coins_in_use(&self) -> f64 {
+    self.checkouts_per_sec * self.avg_hold_millis / 1000.0
+  }
+}
+
+/// Loads the pool usage over the last `window_minutes` complete minutes
+pub async fn load_throughput(conn: &mut Redis, keys: &PoolKeys, window_minutes: u64) -> Result<Throughput> {
+  let current = current_bucket();
+  let mut checkouts = 0;
+  let mut releases = 0;
+  let mut hold_millis = 0;
+
+  for bucket in current.saturating_sub(window_minutes)..current {
+    let stats = conn.hgetall(bucket_key(keys, bucket)).await?;
+    let field = |name: &str| stats.get(name).and_then(|v| v.parse::<u64>().ok()).unwrap_or(0);
+
+    checkouts += field(CHECKOUTS_FIELD);
+    releases += field(RELEASES_FIELD);
+    hold_millis += field(HOLD_MILLIS_FIELD);
+  }
+
+  if window_minutes == 0 {return Ok(Throughput::default())}
+
+  Ok(Throughput {
+    checkouts_per_sec: checkouts as f64 / (window_minutes * 60) as f64,
+    avg_hold_millis: if releases > 0 {hold_millis as f64 / releases as f64} else {0.0},
+  })
+}
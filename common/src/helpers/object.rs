@@ -1,5 +1,5 @@
-use std::sync::Arc;
-use eyre::Result;
+use std::{collections::HashMap, sync::Arc};
+use eyre::{Result, ContextCompat};
 use sui_sdk::{SuiClient, rpc_types::{SuiObjectDataOptions, SuiTransactionBlockResponse, ObjectChange, SuiObjectData}};
 use sui_types::base_types::{ObjectID, ObjectRef};
 
@@ -21,6 +21,23 @@ pub async fn get_object_ref(api: Arc<SuiClient>, object_id: ObjectID) -> Result<
   Ok(object.object_ref())
 }
 
+/// Returns the type of each of the given objects e.g. `0x2::coin::Coin<0x2::sui::SUI>`
+pub async fn get_object_types(api: Arc<SuiClient>, object_ids: Vec<ObjectID>) -> Result<HashMap<ObjectID, String>> {
+  api.read_api().multi_get_object_with_options(
+    object_ids,
+    SuiObjectDataOptions::new().with_type(),
+  )
+  .await?
+  .into_iter()
+  .map(|response| {
+    let object = response.into_object()?;
+    let object_type = object.type_.as_ref().context("the object has no type")?.to_string();
+
+    Ok((object.object_id, object_type))
+  })
+  .collect()
+}
+
 pub fn get_created_objects(response: &SuiTransactionBlockResponse) -> Vec<ObjectID> {
   let mut new_objects = vec![];
 
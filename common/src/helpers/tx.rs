@@ -9,7 +9,7 @@ use sui_sdk::{
 };
 use sui_types::{
   transaction::{Transaction, TransactionData}, quorum_driver_types::ExecuteTransactionRequestType,
-  crypto::Signature, base_types::ObjectID,
+  signature::GenericSignature, base_types::{ObjectID, SuiAddress},
 };
 
 pub struct TxManager {
@@ -46,15 +46,37 @@ impl TxManager {
     data.gas_data.payment.iter().map(|g| g.0).collect()
   }
 
+  /// Returns the sender of the transaction
+  pub fn extract_sender(tx_data: &TransactionData) -> SuiAddress {
+    let TransactionData::V1(data) = tx_data;
+
+    data.sender
+  }
+
+  /// Returns the owner of the gas payment i.e. the sponsor of a sponsored transaction
+  pub fn extract_gas_owner(tx_data: &TransactionData) -> SuiAddress {
+    let TransactionData::V1(data) = tx_data;
+
+    data.gas_data.owner
+  }
+
+  /// Checks that the given signatures are valid and are the ones the transaction requires i.e. those of the sender
+  /// and of the gas owner
+  pub fn verify_signatures(tx_data: &TransactionData, signatures: Vec<GenericSignature>) -> Result<()> {
+    Transaction::from_generic_sig_data(tx_data.clone(), Intent::sui_transaction(), signatures).verify()?;
+
+    Ok(())
+  }
+
   pub async fn send_tx(
     &self,
     tx_data: TransactionData,
-    signatures: Vec<Signature>
+    signatures: Vec<GenericSignature>
   ) -> Result<SuiTransactionBlockResponse> {
     let response = self.api
     .quorum_driver_api()
     .execute_transaction_block(
-      Transaction::from_data(tx_data, Intent::sui_transaction(), signatures).verify()?,
+      Transaction::from_generic_sig_data(tx_data, Intent::sui_transaction(), signatures).verify()?,
       SuiTransactionBlockResponseOptions::full_content(),
       Some(ExecuteTransactionRequestType::WaitForLocalExecution),
     )
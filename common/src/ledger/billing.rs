@@ -0,0 +1,175 @@
+use std::{collections::BTreeMap, str::FromStr};
+use chrono::{NaiveDate, Utc, TimeZone};
+use eyre::{Report, Result, eyre};
+use serde::{Deserialize, Serialize};
+use super::{Ledger, LedgerEntry, day};
+
+const MIST_PER_SUI: u64 = 1_000_000_000;
+
+/// The period each line of a billing report covers
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Period {
+  Day,
+  Month,
+}
+
+impl FromStr for Period {
+  type Err = Report;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "day" => Ok(Self::Day),
+      "month" => Ok(Self::Month),
+      _ => Err(Report::msg(format!("unknown billing period {s}"))),
+    }
+  }
+}
+
+impl Period {
+  /// The (UTC) period the given unix timestamp in milliseconds falls in i.e. YYYY-MM-DD or YYYY-MM
+  pub fn of(&self, timestamp: u64) -> String {
+    let day = day(timestamp);
+
+    match self {
+      Self::Day => day,
+      Self::Month => day.get(..7).unwrap_or_default().to_string(),
+    }
+  }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportFormat {
+  Csv,
+  Json,
+}
+
+impl FromStr for ReportFormat {
+  type Err = Report;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "csv" => Ok(Self::Csv),
+      "json" => Ok(Self::Json),
+      _ => Err(Report::msg(format!("unknown report format {s}"))),
+    }
+  }
+}
+
+/// A billing report covers the transactions executed within `[from, to)`. Both are UTC dates (YYYY-MM-DD)
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BillingQuery {
+  pub period: Period,
+  pub from: Option<String>,
+  pub to: Option<String>,
+}
+
+/// The gas a tenant was sponsored for within a single period
+#[derive(Serialize, Default, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BillingLine {
+  // None for the transactions without a tenant
+  pub tenant: Option<String>,
+  pub period: String,
+  pub tx_count: u64,
+  pub computation_cost: u64,
+  pub storage_cost: u64,
+  pub storage_rebate: u64,
+  pub net_gas_cost_mist: i64,
+  // The net gas cost as an exact decimal number of SUI
+  pub net_gas_cost_sui: String,
+}
+
+/// Converts MIST to an exact decimal number of SUI e.g. 1500000000 to 1.500000000
+pub fn mist_to_sui(mist: i64) -> String {
+  let sign = if mist < 0 {"-"} else {""};
+  let mist = mist.unsigned_abs();
+
+  format!("{sign}{}.{:09}", mist / MIST_PER_SUI, mist % MIST_PER_SUI)
+}
+
+/// Converts a UTC date (YYYY-MM-DD) to a unix timestamp in milliseconds
+fn date_millis(date: &str) -> Result<u64> {
+  let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|e| eyre!("invalid date {}: {}", date, e))?;
+  let start = Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap());
+
+  u64::try_from(start.timestamp_millis()).map_err(|_| eyre!("date {} is before 1970", date))
+}
+
+/// Aggregates the net gas cost of each tenant per period, ordered by tenant and then period
+pub fn aggregate(entries: impl IntoIterator<Item = LedgerEntry>, period: Period) -> Vec<BillingLine> {
+  let mut lines = BTreeMap::<(Option<String>, String), BillingLine>::new();
+
+  for entry in entries {
+    let key = (entry.tenant.clone(), period.of(entry.timestamp));
+    let line = lines.entry(key.clone()).or_insert_with(|| BillingLine {
+      tenant: key.0,
+      period: key.1,
+      ..Default::default()
+    });
+
+    line.tx_count += 1;
+    line.computation_cost = line.computation_cost.saturating_add(entry.computation_cost);
+    line.storage_cost = line.storage_cost.saturating_add(entry.storage_cost);
+    line.storage_rebate = line.storage_rebate.saturating_add(entry.storage_rebate);
+    line.net_gas_cost_mist = line.net_gas_cost_mist.saturating_add(entry.net_gas_cost());
+  }
+
+  lines.into_values()
+  .map(|line| BillingLine {
+    net_gas_cost_sui: mist_to_sui(line.net_gas_cost_mist),
+    ..line
+  })
+  .collect()
+}
+
+/// Builds the billing report of the given query from the ledger
+pub async fn billing_report(ledger: &dyn Ledger, query: &BillingQuery) -> Result<Vec<BillingLine>> {
+  let from = query.from.as_deref().map(date_millis).transpose()?;
+  let to = query.to.as_deref().map(date_millis).transpose()?;
+  let entries = ledger.entries(from, to).await?;
+
+  Ok(aggregate(entries, query.period))
+}
+
+/// Quotes the given CSV field if it contains a separator, a quote or a line break
+pub fn csv_field(value: &str) -> String {
+  if value.contains(|c| c == ',' || c == '"' || c == '\n' || c == '\r') {
+    format!("\"{}\"", value.replace('"', "\"\""))
+  } else {
+    value.to_string()
+  }
+}
+
+/// Renders the report in the given format
+pub fn render(lines: &[BillingLine], format: ReportFormat) -> Result<String> {
+  match format {
+    ReportFormat::Csv => Ok(to_csv(lines)),
+    ReportFormat::Json => Ok(serde_json::to_string_pretty(lines)?),
+  }
+}
+
+/// Renders the report as CSV with a header line
+pub fn to_csv(lines: &[BillingLine]) -> String {
+  let mut csv = String::from(
+    "tenant,period,tx_count,computation_cost,storage_cost,storage_rebate,net_gas_cost_mist,net_gas_cost_sui\n"
+  );
+
+  for line in lines {
+    csv.push_str(&format!(
+      "{},{},{},{},{},{},{},{}\n",
+      csv_field(line.tenant.as_deref().unwrap_or_default()),
+      line.period,
+      line.tx_count,
+      line.computation_cost,
+      line.storage_cost,
+      line.storage_rebate,
+      line.net_gas_cost_mist,
+      line.net_gas_cost_sui,
+    ));
+  }
+
+  csv
+}
@@ -0,0 +1,57 @@
+use std::{
+  fs::{File, OpenOptions}, io::{BufRead, BufReader, Write}, path::PathBuf, sync::{Arc, Mutex},
+};
+use async_trait::async_trait;
+use eyre::Result;
+use tokio::task::spawn_blocking;
+use super::{Ledger, LedgerEntry, Totals, TotalsQuery, aggregate};
+
+/// Appends each entry, as a single line of JSON, to a file. Totals are computed by reading the whole file, so it
+/// suits low volumes or shipping the file elsewhere for processing.
+pub struct JsonlLedger {
+  path: PathBuf,
+  file: Arc<Mutex<File>>,
+}
+
+impl JsonlLedger {
+  pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+    let path = path.into();
+    let file = OpenOptions::new().create(true).append(true).open(&path)?;
+
+    Ok(Self {
+      path,
+      file: Arc::new(Mutex::new(file)),
+    })
+  }
+}
+
+#[async_trait]
+impl Ledger for JsonlLedger {
+  async fn record(&self, entry: &LedgerEntry) -> Result<()> {
+    let mut line = serde_json::to_vec(entry)?;
+    line.push(b'\n');
+    let file = Arc::clone(&self.file);
+
+    // A single write per entry so that concurrent entries are never interleaved
+    spawn_blocking(move || file.lock().unwrap().write_all(&line)).await??;
+
+    Ok(())
+  }
+
+  async fn totals(&self, query: &TotalsQuery) -> Result<Vec<Totals>> {
+    Ok(aggregate(self.entries(query.from, query.to).await?, query))
+  }
+
+  async fn entries(&self, from: Option<u64>, to: Option<u64>) -> Result<Vec<LedgerEntry>> {
+    let path = self.path.clone();
+    let entries = spawn_blocking(move || -> Result<Vec<LedgerEntry>> {
+      BufReader::new(File::open(path)?)
+      .lines()
+      .filter(|line| !matches!(line, Ok(line) if line.trim().is_empty()))
+      .map(|line| Ok(serde_json::from_str(&line?)?))
+      .collect()
+    }).await??;
+
+    Ok(entries.into_iter().filter(|e| e.is_within(from, to)).collect())
+  }
+}
@@ -0,0 +1,173 @@
+pub mod billing;
+pub mod jsonl;
+pub mod sqlite;
+
+use std::{collections::BTreeMap, str::FromStr, sync::Arc};
+use async_trait::async_trait;
+use chrono::{TimeZone, Utc};
+use eyre::{Report, Result};
+use serde::{Deserialize, Serialize};
+use sui_sdk::rpc_types::{SuiTransactionBlockResponse, SuiTransactionBlockEffects};
+use sui_types::base_types::{SuiAddress, ObjectID};
+use crate::{services::gas_meter::GasMeter, gas_pool::now_millis};
+use self::{jsonl::JsonlLedger, sqlite::SqliteLedger};
+
+/// A sponsored transaction that was executed and the gas the sponsor paid for it
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LedgerEntry {
+  pub digest: String,
+  pub sender: SuiAddress,
+  pub sponsor: SuiAddress,
+  pub tenant: Option<String>,
+  pub gas_coin: ObjectID,
+  pub computation_cost: u64,
+  pub storage_cost: u64,
+  pub storage_rebate: u64,
+  pub success: bool,
+  // The execution error of a failed transaction. Failed transactions pay for gas too
+  pub error: Option<String>,
+  // Unix timestamp in milliseconds
+  pub timestamp: u64,
+}
+
+impl LedgerEntry {
+  /// Builds the entry of an executed transaction from its response. Returns None if the response carries no effects
+  pub fn from_response(
+    response: &SuiTransactionBlockResponse,
+    sender: SuiAddress,
+    sponsor: SuiAddress,
+    gas_coin: ObjectID,
+    tenant: Option<&str>,
+  ) -> Option<Self> {
+    let effects = response.effects.clone()?;
+    let SuiTransactionBlockEffects::V1(effects_v1) = &effects;
+    let error = GasMeter::execution_error(&effects_v1.status);
+    let gas_summary = GasMeter::gas_summary(effects);
+
+    Some(Self {
+      digest: response.digest.to_string(),
+      sender,
+      sponsor,
+      tenant: tenant.map(ToString::to_string),
+      gas_coin,
+      computation_cost: gas_summary.computation_cost,
+      storage_cost: gas_summary.storage_cost,
+      storage_rebate: gas_summary.storage_rebate,
+      success: error.is_none(),
+      error,
+      timestamp: response.timestamp_ms.unwrap_or_else(now_millis),
+    })
+  }
+
+  /// Computation plus storage cost minus the storage rebate. It's negative when the transaction freed more storage
+  /// than it used. Costs beyond the range of an i64 are clamped to it
+  pub fn net_gas_cost(&self) -> i64 {
+    let net_gas_cost = self.computation_cost as i128 + self.storage_cost as i128 - self.storage_rebate as i128;
+    net_gas_cost.clamp(i64::MIN as i128, i64::MAX as i128) as i64
+  }
+
+  /// Whether the entry was executed within `[from, to)`
+  pub fn is_within(&self, from: Option<u64>, to: Option<u64>) -> bool {
+    from.map_or(true, |from| self.timestamp >= from) && to.map_or(true, |to| self.timestamp < to)
+  }
+}
+
+/// Returns the UTC day, as YYYY-MM-DD, of the given unix timestamp in milliseconds
+pub fn day(timestamp: u64) -> String {
+  Utc.timestamp_millis_opt(timestamp as i64)
+  .single()
+  .map_or_else(String::new, |t| t.format("%Y-%m-%d").to_string())
+}
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupBy {
+  Sender,
+  Tenant,
+  Day,
+}
+
+/// Selects the entries executed within `[from, to)`, both unix timestamps in milliseconds, and how their totals are
+/// grouped
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TotalsQuery {
+  pub group_by: GroupBy,
+  pub from: Option<u64>,
+  pub to: Option<u64>,
+}
+
+/// The aggregate gas paid for a group of entries
+#[derive(Serialize, Default, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Totals {
+  // The sender, tenant or day of the group. None for the entries without a tenant
+  pub key: Option<String>,
+  pub tx_count: u64,
+  pub computation_cost: u64,
+  pub storage_cost: u64,
+  pub storage_rebate: u64,
+  pub net_gas_cost: i64,
+}
+
+/// Computes the totals of the given entries in memory, ordered by their key. Entries without a tenant come first
+/// when grouped by tenant. Sums beyond the range of their type are clamped to it
+pub fn aggregate(entries: impl IntoIterator<Item = LedgerEntry>, query: &TotalsQuery) -> Vec<Totals> {
+  let mut groups = BTreeMap::<Option<String>, Totals>::new();
+
+  for entry in entries.into_iter().filter(|e| e.is_within(query.from, query.to)) {
+    let key = match query.group_by {
+      GroupBy::Sender => Some(entry.sender.to_string()),
+      GroupBy::Tenant => entry.tenant.clone(),
+      GroupBy::Day => Some(day(entry.timestamp)),
+    };
+
+    let totals = groups.entry(key.clone()).or_insert_with(|| Totals {key, ..Default::default()});
+    totals.tx_count += 1;
+    totals.computation_cost = totals.computation_cost.saturating_add(entry.computation_cost);
+    totals.storage_cost = totals.storage_cost.saturating_add(entry.storage_cost);
+    totals.storage_rebate = totals.storage_rebate.saturating_add(entry.storage_rebate);
+    totals.net_gas_cost = totals.net_gas_cost.saturating_add(entry.net_gas_cost());
+  }
+
+  groups.into_values().collect()
+}
+
+/// Where the ledger entries are stored
+#[async_trait]
+pub trait Ledger: Send + Sync {
+  async fn record(&self, entry: &LedgerEntry) -> Result<()>;
+
+  async fn totals(&self, query: &TotalsQuery) -> Result<Vec<Totals>>;
+
+  /// Returns the entries executed within `[from, to)`, both unix timestamps in milliseconds
+  async fn entries(&self, from: Option<u64>, to: Option<u64>) -> Result<Vec<LedgerEntry>>;
+}
+
+#[derive(Clone, Copy)]
+pub enum LedgerBackend {
+  // An append-only file with one JSON entry per line
+  Jsonl,
+  Sqlite,
+}
+
+impl FromStr for LedgerBackend {
+  type Err = Report;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "jsonl" => Ok(Self::Jsonl),
+      "sqlite" => Ok(Self::Sqlite),
+      _ => Err(Report::msg(format!("unknown ledger backend {s}"))),
+    }
+  }
+}
+
+/// Opens the ledger of the given backend stored at the given path
+pub fn open(backend: LedgerBackend, path: &str) -> Result<Arc<dyn Ledger>> {
+  Ok(match backend {
+    LedgerBackend::Jsonl => Arc::new(JsonlLedger::open(path)?),
+    LedgerBackend::Sqlite => Arc::new(SqliteLedger::open(path)?),
+  })
+}
@@ -0,0 +1,173 @@
+use std::{str::FromStr, sync::{Arc, Mutex}};
+use async_trait::async_trait;
+use eyre::{Result, eyre};
+use rusqlite::{Connection, Row, params};
+use sui_types::base_types::{SuiAddress, ObjectID};
+use tokio::task::spawn_blocking;
+use super::{Ledger, LedgerEntry, Totals, TotalsQuery, GroupBy};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS ledger (
+  digest TEXT PRIMARY KEY,
+  sender TEXT NOT NULL,
+  sponsor TEXT NOT NULL,
+  tenant TEXT,
+  gas_coin TEXT NOT NULL,
+  computation_cost INTEGER NOT NULL,
+  storage_cost INTEGER NOT NULL,
+  storage_rebate INTEGER NOT NULL,
+  success INTEGER NOT NULL,
+  error TEXT,
+  timestamp INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS ledger_timestamp ON ledger (timestamp);
+";
+
+/// The `[from, to)` timestamp range as stored in SQLite. Timestamps are stored as i64, so larger bounds are
+/// clamped to it
+fn timestamp_range(from: Option<u64>, to: Option<u64>) -> (i64, i64) {
+  let clamp = |bound: u64| i64::try_from(bound).unwrap_or(i64::MAX);
+
+  (from.map_or(0, clamp), to.map_or(i64::MAX, clamp))
+}
+
+fn parse_address(address: &str) -> Result<SuiAddress> {
+  SuiAddress::from_str(address).map_err(|e| eyre!(e.to_string()))
+}
+
+/// Reads a row of the ledger table, with the columns in the order of the schema
+fn entry_from_row(row: &Row) -> Result<LedgerEntry> {
+  Ok(LedgerEntry {
+    digest: row.get(0)?,
+    sender: parse_address(&row.get::<_, String>(1)?)?,
+    sponsor: parse_address(&row.get::<_, String>(2)?)?,
+    tenant: row.get(3)?,
+    gas_coin: ObjectID::from_hex_literal(&row.get::<_, String>(4)?)?,
+    computation_cost: u64::try_from(row.get::<_, i64>(5)?)?,
+    storage_cost: u64::try_from(row.get::<_, i64>(6)?)?,
+    storage_rebate: u64::try_from(row.get::<_, i64>(7)?)?,
+    success: row.get(8)?,
+    error: row.get(9)?,
+    timestamp: u64::try_from(row.get::<_, i64>(10)?)?,
+  })
+}
+
+/// Stores the entries in a SQLite database. Totals are aggregated by SQLite itself
+pub struct SqliteLedger {
+  conn: Arc<Mutex<Connection>>,
+}
+
+impl SqliteLedger {
+  pub fn open(path: &str) -> Result<Self> {
+    let conn = Connection::open(path)?;
+    conn.execute_batch(SCHEMA)?;
+
+    Ok(Self {
+      conn: Arc::new(Mutex::new(conn)),
+    })
+  }
+}
+
+#[async_trait]
+impl Ledger for SqliteLedger {
+  async fn record(&self, entry: &LedgerEntry) -> Result<()> {
+    let conn = Arc::clone(&self.conn);
+    let entry = entry.clone();
+
+    spawn_blocking(move || -> Result<()> {
+      // The digest is unique so recording the same transaction twice is a noop
+      conn.lock().unwrap().execute(
+        "INSERT OR IGNORE INTO ledger VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        params![
+          entry.digest,
+          entry.sender.to_string(),
+          entry.sponsor.to_string(),
+          entry.tenant,
+          entry.gas_coin.to_hex_uncompressed(),
+          i64::try_from(entry.computation_cost)?,
+          i64::try_from(entry.storage_cost)?,
+          i64::try_from(entry.storage_rebate)?,
+          entry.success,
+          entry.error,
+          i64::try_from(entry.timestamp)?,
+        ],
+      )?;
+
+      Ok(())
+    }).await??;
+
+    Ok(())
+  }
+
+  async fn totals(&self, query: &TotalsQuery) -> Result<Vec<Totals>> {
+    let conn = Arc::clone(&self.conn);
+    let key = match query.group_by {
+      GroupBy::Sender => "sender",
+      GroupBy::Tenant => "tenant",
+      GroupBy::Day => "date(timestamp / 1000, 'unixepoch')",
+    };
+    let (from, to) = timestamp_range(query.from, query.to);
+
+    let totals = spawn_blocking(move || -> Result<Vec<Totals>> {
+      let conn = conn.lock().unwrap();
+      let mut stmt = conn.prepare(&format!(
+        "SELECT {key}, COUNT(*), SUM(computation_cost), SUM(storage_cost), SUM(storage_rebate)
+        FROM ledger WHERE timestamp >= ?1 AND timestamp < ?2
+        GROUP BY 1 ORDER BY 1"
+      ))?;
+
+      let rows = stmt.query_map(params![from, to], |row| {
+        Ok((
+          row.get::<_, Option<String>>(0)?,
+          row.get::<_, i64>(1)?,
+          row.get::<_, i64>(2)?,
+          row.get::<_, i64>(3)?,
+          row.get::<_, i64>(4)?,
+        ))
+      })?;
+
+      rows
+      .map(|row| -> Result<Totals> {
+        let (key, tx_count, computation_cost, storage_cost, storage_rebate) = row?;
+        let net_gas_cost = computation_cost.checked_add(storage_cost)
+        .and_then(|cost| cost.checked_sub(storage_rebate))
+        .ok_or_else(|| eyre!("the net gas cost of {:?} overflows", key))?;
+
+        Ok(Totals {
+          key,
+          tx_count: u64::try_from(tx_count)?,
+          computation_cost: u64::try_from(computation_cost)?,
+          storage_cost: u64::try_from(storage_cost)?,
+          storage_rebate: u64::try_from(storage_rebate)?,
+          net_gas_cost,
+        })
+      })
+      .collect()
+    }).await??;
+
+    Ok(totals)
+  }
+
+  async fn entries(&self, from: Option<u64>, to: Option<u64>) -> Result<Vec<LedgerEntry>> {
+    let conn = Arc::clone(&self.conn);
+    let (from, to) = timestamp_range(from, to);
+
+    let entries = spawn_blocking(move || -> Result<Vec<LedgerEntry>> {
+      let conn = conn.lock().unwrap();
+      let mut stmt = conn.prepare(
+        "SELECT * FROM ledger WHERE timestamp >= ?1 AND timestamp < ?2 ORDER BY timestamp"
+      )?;
+
+      let mut rows = stmt.query(params![from, to])?;
+      let mut entries = vec![];
+
+      while let Some(row) = rows.next()? {
+        entries.push(entry_from_row(row)?);
+      }
+
+      Ok(entries)
+    }).await??;
+
+    Ok(entries)
+  }
+}
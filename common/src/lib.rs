@@ -3,3 +3,4 @@ pub mod services;
 pub mod storage;
 pub mod helpers;
 pub mod gas_pool;
+pub mod ledger;
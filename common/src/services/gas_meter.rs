@@ -1,24 +1,265 @@
-use std::sync::Arc;
+use std::{sync::{Arc, RwLock, Mutex}, collections::VecDeque, time::Duration};
 use eyre::Result;
-use sui_sdk::{SuiClient, rpc_types::SuiTransactionBlockEffects};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use sui_sdk::{SuiClient, rpc_types::{SuiTransactionBlockEffects, SuiExecutionStatus}};
 use sui_types::{transaction::TransactionData, gas::GasCostSummary};
+use crate::{storage::redis::ConnectionPool, gas_pool::now_millis};
 
+/// Redis key holding the reference gas price of the current epoch, shared by all api and coin manager instances
+pub const REFERENCE_GAS_PRICE_KEY: &str = "gas_meter:reference_gas_price";
+/// Epochs don't change exactly at the expected time. Once the current one is overdue, the system state is polled
+/// this often (in milliseconds) until the new epoch starts.
+pub const EPOCH_CHANGE_POLL_MILLIS: u64 = 10_000;
+
+// Congestion is not reported before we have at least this many executions to go by
+const MIN_CONGESTION_SAMPLES: usize = 5;
+
+/// How the gas price of sponsored transactions is derived from the reference gas price. The result is never lower
+/// than the reference gas price since the network would reject the transaction.
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct GasPricePolicy {
+  pub multiplier: f64,
+  // Added, in MIST, on top of the multiplied reference gas price
+  pub tip: u64,
+  // The price never exceeds this, unless the reference gas price itself is higher
+  pub max: Option<u64>,
+  // Applied on top of the multiplier while the network is congested
+  pub congestion_multiplier: f64,
+}
+
+impl Default for GasPricePolicy {
+  fn default() -> Self {
+    Self {
+      multiplier: 1.0,
+      tip: 0,
+      max: None,
+      congestion_multiplier: 1.0,
+    }
+  }
+}
+
+impl GasPricePolicy {
+  pub fn apply(&self, reference_gas_price: u64, congested: bool) -> u64 {
+    let mut price = reference_gas_price as f64 * self.multiplier;
+    if congested {
+      price *= self.congestion_multiplier;
+    }
+
+    let max = self.max.map_or(u64::MAX, |max| max.max(reference_gas_price));
+
+    (price.ceil() as u64)
+    .saturating_add(self.tip)
+    .clamp(reference_gas_price, max)
+  }
+}
+
+/// The network is considered congested when too many of our most recent executions either failed or were slow
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CongestionDetection {
+  // Number of most recent executions that are considered
+  pub window: usize,
+  // Executions that take longer than this count as slow
+  pub slow_execution_ms: u64,
+  // Ratio of failed or slow executions above which the network is considered congested
+  pub threshold: f64,
+}
+
+impl Default for CongestionDetection {
+  fn default() -> Self {
+    Self {
+      window: 50,
+      slow_execution_ms: 5000,
+      threshold: 0.2,
+    }
+  }
+}
+
+/// The reference gas price of an epoch
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedGasPrice {
+  pub epoch: u64,
+  pub reference_gas_price: u64,
+  // Unix timestamp in milliseconds at which the next epoch is expected to start
+  pub expires_at: u64,
+}
+
+impl CachedGasPrice {
+  /// The gas price of an epoch expected to end at `epoch_end`, as seen at `now` (both unix timestamps in
+  /// milliseconds). If the epoch is already overdue, it expires after `EPOCH_CHANGE_POLL_MILLIS`.
+  pub fn new(epoch: u64, reference_gas_price: u64, epoch_end: u64, now: u64) -> Self {
+    Self {
+      epoch,
+      reference_gas_price,
+      expires_at: epoch_end.max(now + EPOCH_CHANGE_POLL_MILLIS),
+    }
+  }
+
+  pub fn is_fresh_at(&self, now: u64) -> bool {
+    now < self.expires_at
+  }
+
+  fn is_fresh(&self) -> bool {
+    self.is_fresh_at(now_millis())
+  }
+}
+
+/// The reference gas price only changes once per epoch, so it's cached both in memory and in Redis until the
+/// epoch is expected to end. This keeps a fullnode round trip off the `/tx/gas` hot path.
+///
+/// It also keeps track of the outcome of the executions of this instance in order to detect network congestion.
 #[derive(Clone)]
 pub struct GasMeter {
   api: Arc<SuiClient>,
+  redis_pool: Arc<ConnectionPool>,
+  cached_gas_price: Arc<RwLock<Option<CachedGasPrice>>>,
+  congestion_detection: CongestionDetection,
+  // Whether each of the most recent executions failed or was slow
+  executions: Arc<Mutex<VecDeque<bool>>>,
 }
 
 impl GasMeter {
-  pub fn new(api: Arc<SuiClient>) -> Self {
-    Self {api}
+  pub fn new(
+    api: Arc<SuiClient>,
+    redis_pool: Arc<ConnectionPool>,
+    congestion_detection: CongestionDetection,
+  ) -> Self {
+    Self {
+      api,
+      redis_pool,
+      cached_gas_price: Arc::new(RwLock::new(None)),
+      congestion_detection,
+      executions: Arc::new(Mutex::new(VecDeque::new())),
+    }
   }
 
+  /// Records the outcome of a transaction execution
+  pub fn record_execution(&self, duration: Duration, success: bool) {
+    let was_congested = self.is_congested();
+    let is_slow = duration.as_millis() as u64 > self.congestion_detection.slow_execution_ms;
+
+    {
+      let mut executions = self.executions.lock().unwrap();
+      executions.push_back(!success || is_slow);
+
+      while executions.len() > self.congestion_detection.window {
+        executions.pop_front();
+      }
+    }
+
+    match (was_congested, self.is_congested()) {
+      (false, true) => warn!("Network congestion detected; congestion gas prices apply"),
+      (true, false) => info!("Network congestion is over"),
+      _ => {},
+    }
+  }
+
+  /// Whether enough of the recent executions failed or were slow for the network to be considered congested
+  pub fn is_congested(&self) -> bool {
+    let executions = self.executions.lock().unwrap();
+    if executions.len() < MIN_CONGESTION_SAMPLES {return false}
+
+    let bad = executions.iter().filter(|bad| **bad).count();
+    bad as f64 / executions.len() as f64 >= self.congestion_detection.threshold
+  }
+
+  /// The gas price of sponsored transactions under the given policy
+  pub async fn sponsored_gas_price(&self, policy: &GasPricePolicy) -> Result<u64> {
+    Ok(policy.apply(self.gas_price().await?, self.is_congested()))
+  }
+
+  async fn load_shared_gas_price(&self) -> Result<Option<CachedGasPrice>> {
+    let mut conn = self.redis_pool.connection().await?;
+
+    Ok(match conn.get_opt(REFERENCE_GAS_PRICE_KEY).await? {
+      Some(value) => Some(serde_json::from_str(&value)?),
+      None => None,
+    })
+  }
+
+  async fn store_shared_gas_price(&self, gas_price: &CachedGasPrice) -> Result<()> {
+    let ttl_secs = (gas_price.expires_at.saturating_sub(now_millis()) / 1000).max(1);
+    let mut conn = self.redis_pool.connection().await?;
+
+    conn.set_ext(
+      REFERENCE_GAS_PRICE_KEY,
+      serde_json::to_string(gas_price)?.as_str(),
+      ttl_secs as usize,
+    ).await
+  }
+
+  /// Reads the reference gas price of the current epoch, and when that epoch is expected to end, from the chain
+  async fn fetch_gas_price(&self) -> Result<CachedGasPrice> {
+    let system_state = self.api.governance_api()
+    .get_latest_sui_system_state()
+    .await?;
+
+    let epoch_end = system_state.epoch_start_timestamp_ms + system_state.epoch_duration_ms;
+    let gas_price = CachedGasPrice::new(
+      system_state.epoch,
+      system_state.reference_gas_price,
+      epoch_end,
+      now_millis(),
+    );
+
+    let previous_epoch = self.cached_gas_price.read().unwrap().map(|c| c.epoch);
+    if previous_epoch != Some(gas_price.epoch) {
+      info!("Reference gas price for epoch {} is {}", gas_price.epoch, gas_price.reference_gas_price);
+    }
+
+    Ok(gas_price)
+  }
+
+  /// Returns the reference gas price of the current epoch. It's read from memory if possible, then from Redis and
+  /// only then from the chain. Redis errors are not fatal; the price is fetched from the chain instead.
   pub async fn gas_price(&self) -> Result<u64> {
-    let gas = self.api.read_api()
-    .get_reference_gas_price()
+    if let Some(cached) = self.cached_gas_price.read().unwrap().filter(CachedGasPrice::is_fresh) {
+      return Ok(cached.reference_gas_price)
+    }
+
+    let shared = self.load_shared_gas_price().await.unwrap_or_else(|error| {
+      warn!("Failed to read the reference gas price from Redis: {:?}", error);
+      None
+    });
+
+    let gas_price = match shared.filter(CachedGasPrice::is_fresh) {
+      Some(gas_price) => gas_price,
+      None => {
+        let gas_price = self.fetch_gas_price().await?;
+
+        if let Err(error) = self.store_shared_gas_price(&gas_price).await {
+          warn!("Failed to store the reference gas price in Redis: {:?}", error);
+        }
+
+        gas_price
+      },
+    };
+
+    *self.cached_gas_price.write().unwrap() = Some(gas_price);
+
+    Ok(gas_price.reference_gas_price)
+  }
+
+  /// Dry runs the given transaction and returns its gas cost together with the execution error, if it failed
+  pub async fn dry_run(&self, tx_data: TransactionData) -> Result<(GasCostSummary, Option<String>)> {
+    let tx_block_response = self.api.read_api()
+    .dry_run_transaction_block(tx_data)
     .await?;
 
-    Ok(gas)
+    let SuiTransactionBlockEffects::V1(effects) = tx_block_response.effects;
+
+    Ok((effects.gas_used, Self::execution_error(&effects.status)))
+  }
+
+  /// The error a transaction failed with, if it did
+  pub fn execution_error(status: &SuiExecutionStatus) -> Option<String> {
+    match status {
+      SuiExecutionStatus::Failure {error} => Some(error.clone()),
+      SuiExecutionStatus::Success => None,
+    }
   }
 
   pub async fn gas_budget(&self, tx_data: TransactionData) -> Result<u64> {
@@ -29,11 +270,12 @@ impl GasMeter {
     Ok(Self::total_gas_used_upper_bound(tx_block_response.effects)?)
   }
 
+  /// The net gas cost of a transaction. Transactions that delete objects, e.g. merges, can get a storage rebate
+  /// larger than their cost, in which case it's 0.
   pub fn total_gas_used(tx_block_effects: SuiTransactionBlockEffects) -> Result<u64> {
     let gas_summary = Self::gas_summary(tx_block_effects);
-    let gas_used = gas_summary.computation_cost
-    + gas_summary.storage_cost
-    - gas_summary.storage_rebate;
+    let gas_used = (gas_summary.computation_cost + gas_summary.storage_cost)
+    .saturating_sub(gas_summary.storage_rebate);
 
     Ok(gas_used)
   }
@@ -46,7 +288,7 @@ impl GasMeter {
     Ok(gas_upper_bound)
   }
 
-  fn gas_summary(tx_block_effects: SuiTransactionBlockEffects) -> GasCostSummary {
+  pub fn gas_summary(tx_block_effects: SuiTransactionBlockEffects) -> GasCostSummary {
     match tx_block_effects {
       SuiTransactionBlockEffects::V1(effects_v1) => effects_v1.gas_used,
     }
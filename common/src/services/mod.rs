@@ -1,3 +1,8 @@
 pub mod sponsor;
 pub mod gas_meter;
 pub mod wallet;
+pub mod signer;
+pub mod shard;
+pub mod rotation;
+pub mod policy;
+pub mod ptb;
@@ -0,0 +1,140 @@
+use std::{collections::HashMap, fs, path::Path};
+use eyre::{Result, eyre, ensure};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use sui_types::{base_types::ObjectID, transaction::{Command, ProgrammableMoveCall, ProgrammableTransaction}};
+use super::{gas_meter::{GasPricePolicy, CongestionDetection}, ptb::PtbRules};
+
+/// Header that carries the API key of the tenant whose policy applies to a request
+pub const API_KEY_HEADER: &str = "X-Api-Key";
+
+/// The settings that apply to the requests of a single tenant
+#[derive(Deserialize, Default, Clone, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TenantPolicy {
+  pub gas_price: GasPricePolicy,
+  // Dry run each transaction on /tx/submit and reject it, without signing, if it would fail
+  pub dry_run_before_submit: bool,
+  // Max gas budget of each Move function (package::module::function)
+  pub function_budgets: HashMap<String, u64>,
+  // Gas budget of each call to a function that's not in `function_budgets`. If not set, transactions that call such
+  // a function are not sponsored once `function_budgets` is set
+  pub default_function_budget: Option<u64>,
+  pub ptb: PtbRules,
+  // Hex encoded SHA-256 hashes of the API keys that authenticate the tenant. Not used by the default policy
+  pub api_key_hashes: Vec<String>,
+}
+
+impl TenantPolicy {
+  fn normalize(&mut self) -> Result<()> {
+    self.function_budgets = self.function_budgets.drain()
+    .map(|(name, budget)| Ok((normalize_function_name(&name)?, budget)))
+    .collect::<Result<_>>()?;
+    self.ptb.normalize()?;
+    self.api_key_hashes = self.api_key_hashes.iter()
+    .map(|hash| {
+      let hash = hash.to_lowercase();
+      ensure!(hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit()), "invalid API key hash {}", hash);
+      Ok(hash)
+    })
+    .collect::<Result<_>>()?;
+
+    Ok(())
+  }
+
+  /// The gas budget granted to the given transaction. It's the sum of the budgets of the Move calls it makes,
+  /// capped at the given max. A call to a function without a budget of its own counts as
+  /// `default_function_budget`, and if that's not set either, the transaction is granted nothing. A policy without
+  /// any budgets, or a transaction without Move calls, is granted the max.
+  pub fn granted_gas_budget(&self, ptx: &ProgrammableTransaction, max_gas_budget: u64) -> Option<u64> {
+    if self.function_budgets.is_empty() && self.default_function_budget.is_none() {return Some(max_gas_budget)}
+    let mut budget = 0u64;
+    let mut has_move_calls = false;
+
+    for cmd in &ptx.commands {
+      let Command::MoveCall(move_call) = cmd else {continue};
+      let ProgrammableMoveCall {package, module, function, ..} = &**move_call;
+      has_move_calls = true;
+
+      let function_budget = self.function_budgets.get(&function_name(package, module.as_str(), function.as_str()))
+      .copied()
+      .or(self.default_function_budget)?;
+
+      budget = budget.saturating_add(function_budget);
+    }
+
+    Some(if has_move_calls {budget.min(max_gas_budget)} else {max_gas_budget})
+  }
+}
+
+/// The hex encoded SHA-256 hash of the given API key, as set in the policy
+pub fn hash_api_key(api_key: &str) -> String {
+  Sha256::digest(api_key.as_bytes()).iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// The fully qualified name of a Move function, as used in the policy, i.e. `package::module::function`
+pub fn function_name(package: &ObjectID, module: &str, function: &str) -> String {
+  format!("{package}::{module}::{function}")
+}
+
+/// Rewrites the package id of the given function name in its canonical form, so that e.g. `0x2::coin::split` matches
+/// the calls to that function
+fn normalize_function_name(name: &str) -> Result<String> {
+  let parts = name.split("::").collect::<Vec<_>>();
+  ensure!(parts.len() == 3, "invalid Move function {}", name);
+
+  Ok(function_name(&ObjectID::from_hex_literal(parts[0])?, parts[1], parts[2]))
+}
+
+/// Sponsorship policy loaded from the JSON file at `SPONSOR_POLICY_FILE`. Requests that carry the API key of a
+/// tenant in the `API_KEY_HEADER` use the settings of that tenant; all others use the default ones.
+#[derive(Deserialize, Default, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Policy {
+  pub default: TenantPolicy,
+  pub tenants: HashMap<String, TenantPolicy>,
+  pub congestion: CongestionDetection,
+  // The tenant of each API key hash
+  #[serde(skip)]
+  api_keys: HashMap<String, String>,
+}
+
+impl Policy {
+  pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+    Self::parse(&fs::read_to_string(path)?)
+  }
+
+  pub fn parse(json: &str) -> Result<Self> {
+    let mut policy: Self = serde_json::from_str(json)?;
+    policy.default.normalize()?;
+
+    for (name, tenant) in policy.tenants.iter_mut() {
+      tenant.normalize()?;
+
+      for hash in &tenant.api_key_hashes {
+        let previous = policy.api_keys.insert(hash.clone(), name.clone());
+        ensure!(previous.is_none(), "API key hash {} is used by more than one tenant", hash);
+      }
+    }
+
+    Ok(policy)
+  }
+
+  /// Returns the tenant the given API key belongs to. Requests without an API key have no tenant, and those with an
+  /// unknown one are rejected.
+  pub fn authenticate(&self, api_key: Option<&str>) -> Result<Option<&str>> {
+    let Some(api_key) = api_key else {return Ok(None)};
+
+    self.api_keys.get(&hash_api_key(api_key))
+    .map(|tenant| Some(tenant.as_str()))
+    .ok_or_else(|| eyre!("invalid API key"))
+  }
+
+  /// Returns the settings of the given tenant. Unknown tenants are rejected
+  pub fn tenant(&self, tenant: Option<&str>) -> Result<&TenantPolicy> {
+    match tenant {
+      Some(tenant) => self.tenants.get(tenant).ok_or_else(|| eyre!("unknown tenant {}", tenant)),
+      None => Ok(&self.default),
+    }
+  }
+}
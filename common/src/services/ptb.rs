@@ -0,0 +1,165 @@
+use std::collections::{HashMap, HashSet};
+use eyre::{Result, eyre};
+use serde::Deserialize;
+use sui_types::{
+  base_types::{ObjectID, SuiAddress},
+  transaction::{Argument, CallArg, Command, ObjectArg, ProgrammableTransaction},
+  parse_sui_type_tag,
+};
+
+/// Rules over the commands of a sponsored programmable transaction and their arguments
+#[derive(Deserialize, Default, Clone, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PtbRules {
+  // Addresses, besides the sender, that TransferObjects may send objects to. Any recipient is allowed if not set
+  pub transfer_recipients: Option<HashSet<SuiAddress>>,
+  // Max number of coins that the SplitCoins commands of a transaction may create
+  pub max_split_coins: Option<usize>,
+  // Element types of the vectors that MakeMoveVec may create, e.g. 0x2::coin::Coin<0x2::sui::SUI>
+  pub make_move_vec_types: HashSet<String>,
+  // Sponsor the publishing of new packages
+  pub allow_publish: bool,
+  // Packages whose upgrades are sponsored, when they're sent by one of `upgrade_cap_owners`
+  pub upgrade_packages: HashSet<ObjectID>,
+  // Senders whose upgrades of `upgrade_packages` are sponsored i.e. the owners of the UpgradeCap of those packages
+  pub upgrade_cap_owners: HashSet<SuiAddress>,
+}
+
+impl PtbRules {
+  /// Rewrites the MakeMoveVec types in their canonical form, so that they match the types of the commands
+  pub fn normalize(&mut self) -> Result<()> {
+    self.make_move_vec_types = self.make_move_vec_types.drain()
+    .map(|type_tag| {
+      parse_sui_type_tag(&type_tag)
+      .map(|type_tag| type_tag.to_string())
+      .map_err(|e| eyre!(e.to_string()))
+    })
+    .collect::<Result<_>>()?;
+
+    Ok(())
+  }
+}
+
+/// Returns the address that the given pure input holds. Recipients computed by other commands can't be known
+/// before execution, so they're not resolved.
+fn recipient(ptx: &ProgrammableTransaction, arg: &Argument) -> Option<SuiAddress> {
+  let Argument::Input(index) = arg else {return None};
+  let Some(CallArg::Pure(bytes)) = ptx.inputs.get(*index as usize) else {return None};
+
+  bcs::from_bytes(bytes).ok()
+}
+
+fn is_recipient_allowed(ptx: &ProgrammableTransaction, arg: &Argument, sender: SuiAddress, rules: &PtbRules) -> bool {
+  let Some(allowlist) = &rules.transfer_recipients else {return true};
+
+  recipient(ptx, arg).map_or(false, |recipient| recipient == sender || allowlist.contains(&recipient))
+}
+
+/// Returns the id of the object that the given input holds
+fn input_object(ptx: &ProgrammableTransaction, arg: &Argument) -> Option<ObjectID> {
+  let Argument::Input(index) = arg else {return None};
+
+  match ptx.inputs.get(*index as usize)? {
+    CallArg::Object(ObjectArg::ImmOrOwnedObject((id, _, _))) => Some(*id),
+    CallArg::Object(ObjectArg::SharedObject {id, ..}) => Some(*id),
+    _ => None,
+  }
+}
+
+/// Returns the objects that the MakeMoveVec commands without an element type put in vectors. The type of those
+/// vectors is the type of their objects, which must be read from the chain before the transaction can be checked.
+pub fn untyped_vector_objects(ptx: &ProgrammableTransaction) -> Vec<ObjectID> {
+  ptx.commands.iter()
+  .filter_map(|cmd| match cmd {
+    Command::MakeMoveVec(None, elements) => Some(elements),
+    _ => None,
+  })
+  .flatten()
+  .filter_map(|arg| input_object(ptx, arg))
+  .collect()
+}
+
+/// The element type of a MakeMoveVec command without an explicit one i.e. the type of its objects, given the types
+/// of the transaction objects. Elements that are not object inputs, e.g. the results of other commands, can't be
+/// typed before execution, so such vectors have no known type.
+fn untyped_vector_type(
+  ptx: &ProgrammableTransaction,
+  elements: &[Argument],
+  object_types: &HashMap<ObjectID, String>,
+) -> Option<String> {
+  let types = elements.iter()
+  .map(|arg| {
+    let object_type = object_types.get(&input_object(ptx, arg)?)?;
+    parse_sui_type_tag(object_type).ok().map(|type_tag| type_tag.to_string())
+  })
+  .collect::<Option<HashSet<_>>>()?;
+
+  if types.len() == 1 {types.into_iter().next()} else {None}
+}
+
+/// Returns all the arguments of the given command
+fn arguments(cmd: &Command) -> Vec<&Argument> {
+  match cmd {
+    Command::MoveCall(move_call) => move_call.arguments.iter().collect(),
+    Command::TransferObjects(objects, recipient) => objects.iter().chain([recipient]).collect(),
+    Command::SplitCoins(coin, amounts) => [coin].into_iter().chain(amounts).collect(),
+    Command::MergeCoins(coin, coins) => [coin].into_iter().chain(coins).collect(),
+    Command::MakeMoveVec(_, elements) => elements.iter().collect(),
+    Command::Publish(_, _) => vec![],
+    Command::Upgrade(_, _, _, ticket) => vec![ticket],
+  }
+}
+
+/// Whether any command of the given transaction uses the gas coin. The gas coin belongs to the sponsor, so a
+/// sponsored transaction must never touch it, be it by splitting, merging or transferring it, or by passing it, or
+/// a vector holding it, to a Move call.
+pub fn uses_gas_coin(ptx: &ProgrammableTransaction) -> bool {
+  ptx.commands.iter()
+  .flat_map(arguments)
+  .any(|arg| *arg == Argument::GasCoin)
+}
+
+/// Checks the commands of the given transaction, and their arguments, against the given rules. No command may use
+/// the gas coin. MakeMoveVec, Publish and Upgrade are only allowed if the rules explicitly allow them.
+///
+/// MakeMoveVec commands without an element type are rejected, since their type is not known. See
+/// `is_ptb_allowed_with_object_types` to check them as well.
+pub fn is_ptb_allowed(ptx: &ProgrammableTransaction, sender: SuiAddress, rules: &PtbRules) -> bool {
+  is_ptb_allowed_with_object_types(ptx, sender, rules, &HashMap::new())
+}
+
+/// Same as `is_ptb_allowed`, but the element type of the MakeMoveVec commands without one is resolved from the
+/// given types of the `untyped_vector_objects`. This is the form the SDKs produce for vectors of objects.
+pub fn is_ptb_allowed_with_object_types(
+  ptx: &ProgrammableTransaction,
+  sender: SuiAddress,
+  rules: &PtbRules,
+  object_types: &HashMap<ObjectID, String>,
+) -> bool {
+  if uses_gas_coin(ptx) {return false};
+  let mut split_coins = 0;
+
+  for cmd in &ptx.commands {
+    let is_allowed = match cmd {
+      Command::SplitCoins(_, amounts) => {
+        split_coins += amounts.len();
+        true
+      },
+      Command::TransferObjects(_, recipient) => is_recipient_allowed(ptx, recipient, sender, rules),
+      Command::MakeMoveVec(type_tag, elements) => type_tag.as_ref()
+      .map(ToString::to_string)
+      .or_else(|| untyped_vector_type(ptx, elements, object_types))
+      .map_or(false, |type_tag| rules.make_move_vec_types.contains(&type_tag)),
+      Command::Publish(_, _) => rules.allow_publish,
+      // Anyone can pass someone else's package id, so the sender must be one of the owners of its UpgradeCap too
+      Command::Upgrade(_, _, package, _) => {
+        rules.upgrade_packages.contains(package) && rules.upgrade_cap_owners.contains(&sender)
+      },
+      Command::MoveCall(_) | Command::MergeCoins(_, _) => true,
+    };
+
+    if !is_allowed {return false}
+  }
+
+  rules.max_split_coins.map_or(true, |max| split_coins <= max)
+}
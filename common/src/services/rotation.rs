@@ -0,0 +1,111 @@
+use std::{collections::HashMap, future::Future, str::FromStr, sync::{Arc, RwLock}, time::{Duration, Instant}};
+use eyre::{Result, eyre};
+use serde::{Deserialize, Serialize};
+use sui_types::base_types::SuiAddress;
+use crate::{storage::redis::ConnectionPool, gas_pool::now_millis};
+
+/// Redis hash mapping each sponsor that is being rotated out to its `Rotation` as JSON
+pub const ROTATIONS_KEY: &str = "sponsor:rotations";
+/// How long the api uses the rotations it loaded before loading them again i.e. how long it takes, at most, for the
+/// api to pick up a new rotation. It must be well below `ROTATION_GRACE_PERIOD`.
+pub const ROTATIONS_TTL: Duration = Duration::from_secs(5);
+/// Once a rotation starts, the balance of the old sponsor is not transferred before this much time has passed. It
+/// covers gas requests that were already being served by the api when the rotation started.
+pub const ROTATION_GRACE_PERIOD: Duration = Duration::from_secs(60);
+
+/// The replacement of a sponsor key by a new one. While it's in progress the api no longer hands out the coins of
+/// the old sponsor, but it still signs the transactions that already use them. The coin manager of the old sponsor
+/// drains its pool and, once no coin is in use, transfers the remaining balance to the new sponsor.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Rotation {
+  pub to: SuiAddress,
+  // Unix timestamp in milliseconds
+  pub started_at: u64,
+}
+
+impl Rotation {
+  /// Whether the grace period is over at the given unix timestamp, in milliseconds, so that the balance of the old
+  /// sponsor can be transferred
+  pub fn is_past_grace_period(&self, now: u64) -> bool {
+    now.saturating_sub(self.started_at) >= ROTATION_GRACE_PERIOD.as_millis() as u64
+  }
+}
+
+/// Returns all rotations that have been started, keyed by the sponsor being rotated out
+pub async fn load_rotations(redis_pool: &ConnectionPool) -> Result<HashMap<SuiAddress, Rotation>> {
+  let mut conn = redis_pool.connection().await?;
+
+  conn.hgetall(ROTATIONS_KEY).await?
+  .into_iter()
+  .map(|(from, rotation)| {
+    let from = SuiAddress::from_str(&from).map_err(|e| eyre!(e.to_string()))?;
+    Ok((from, serde_json::from_str(&rotation)?))
+  })
+  .collect()
+}
+
+/// Starts rotating the `from` sponsor out in favour of `to`
+pub async fn start_rotation(redis_pool: &ConnectionPool, from: SuiAddress, to: SuiAddress) -> Result<Rotation> {
+  let rotation = Rotation {
+    to,
+    started_at: now_millis(),
+  };
+
+  let mut conn = redis_pool.connection().await?;
+  conn.hset(ROTATIONS_KEY, from.to_string().as_str(), serde_json::to_string(&rotation)?.as_str()).await?;
+
+  Ok(rotation)
+}
+
+struct CachedRotations {
+  rotations: Arc<HashMap<SuiAddress, Rotation>>,
+  loaded_at: Instant,
+}
+
+/// Keeps the rotations in memory for `ROTATIONS_TTL`, so that they're not read from Redis on every request
+pub struct RotationCache {
+  redis_pool: Arc<ConnectionPool>,
+  ttl: Duration,
+  cached: RwLock<Option<CachedRotations>>,
+}
+
+impl RotationCache {
+  pub fn new(redis_pool: Arc<ConnectionPool>) -> Self {
+    Self::with_ttl(redis_pool, ROTATIONS_TTL)
+  }
+
+  pub fn with_ttl(redis_pool: Arc<ConnectionPool>, ttl: Duration) -> Self {
+    Self {
+      redis_pool,
+      ttl,
+      cached: RwLock::new(None),
+    }
+  }
+
+  /// Returns all rotations that have been started, as of at most `ROTATIONS_TTL` ago
+  pub async fn load(&self) -> Result<Arc<HashMap<SuiAddress, Rotation>>> {
+    self.load_with(|| load_rotations(&self.redis_pool)).await
+  }
+
+  /// Same as `load` but the rotations are read with the given loader when the cached ones have expired
+  pub async fn load_with<F, Fut>(&self, loader: F) -> Result<Arc<HashMap<SuiAddress, Rotation>>>
+  where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<HashMap<SuiAddress, Rotation>>>,
+  {
+    if let Some(cached) = &*self.cached.read().unwrap() {
+      if cached.loaded_at.elapsed() < self.ttl {
+        return Ok(Arc::clone(&cached.rotations))
+      }
+    }
+
+    let rotations = Arc::new(loader().await?);
+    *self.cached.write().unwrap() = Some(CachedRotations {
+      rotations: Arc::clone(&rotations),
+      loaded_at: Instant::now(),
+    });
+
+    Ok(rotations)
+  }
+}
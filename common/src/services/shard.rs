@@ -0,0 +1,78 @@
+use std::{str::FromStr, sync::{Arc, atomic::{AtomicUsize, Ordering}}};
+use eyre::Report;
+use sui_types::base_types::SuiAddress;
+use crate::gas_pool::{GasPool, PoolKeys, coin_object_producer::CoinObjectProducer};
+use super::wallet::Wallet;
+
+/// A sponsor account together with its own Gas Pool. Shards are independent from each other; each one has its
+/// own coins, Redis keys, queue and coin manager.
+pub struct Shard {
+  pub wallet: Arc<Wallet>,
+  pub keys: PoolKeys,
+  pub gas_pool: Arc<&'static GasPool>,
+  pub coin_object_producer: Arc<CoinObjectProducer>,
+}
+
+impl Shard {
+  pub fn sponsor(&self) -> SuiAddress {
+    self.wallet.address()
+  }
+}
+
+/// How the shard that serves a gas request is selected
+#[derive(Clone, Copy)]
+pub enum ShardStrategy {
+  RoundRobin,
+  // The shard with the fewest coins handed out by this api instance
+  LeastLoaded,
+}
+
+impl FromStr for ShardStrategy {
+  type Err = Report;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "round_robin" => Ok(Self::RoundRobin),
+      "least_loaded" => Ok(Self::LeastLoaded),
+      _ => Err(Report::msg(format!("unknown shard strategy {s}"))),
+    }
+  }
+}
+
+/// Orders the shards according to the configured strategy
+pub struct ShardSelector {
+  strategy: ShardStrategy,
+  next: AtomicUsize,
+}
+
+impl ShardSelector {
+  pub fn new(strategy: ShardStrategy) -> Self {
+    Self {
+      strategy,
+      next: AtomicUsize::new(0),
+    }
+  }
+
+  /// Returns the shards in the order they should be tried. Later shards are used if the preferred one has no
+  /// coins available.
+  pub fn order<'a>(&self, shards: &'a [Shard]) -> Vec<&'a Shard> {
+    self.order_by_load(shards, |shard| shard.gas_pool.pending_count())
+  }
+
+  /// Orders the given items according to the strategy, using `load` to compare them when the least loaded one
+  /// is preferred
+  pub fn order_by_load<'a, T>(&self, items: &'a [T], load: impl Fn(&T) -> usize) -> Vec<&'a T> {
+    let mut ordered = items.iter().collect::<Vec<_>>();
+    if ordered.is_empty() {return ordered}
+
+    match self.strategy {
+      ShardStrategy::RoundRobin => {
+        let start = self.next.fetch_add(1, Ordering::Relaxed) % ordered.len();
+        ordered.rotate_left(start);
+      },
+      ShardStrategy::LeastLoaded => ordered.sort_by_key(|item| load(item)),
+    }
+
+    ordered
+  }
+}
@@ -0,0 +1,28 @@
+use async_trait::async_trait;
+use eyre::Result;
+use shared_crypto::intent::IntentMessage;
+use sui_types::{base_types::SuiAddress, crypto::Signature, transaction::TransactionData};
+use crate::utils::config::KeyPair;
+use super::Signer;
+
+/// Signs with a key that is held in the process memory
+pub struct KeyPairSigner {
+  keypair: KeyPair,
+}
+
+impl KeyPairSigner {
+  pub fn new(keypair: KeyPair) -> Self {
+    Self {keypair}
+  }
+}
+
+#[async_trait]
+impl Signer for KeyPairSigner {
+  fn address(&self) -> SuiAddress {
+    (&self.keypair.public()).into()
+  }
+
+  async fn sign(&self, msg: &IntentMessage<TransactionData>) -> Result<Signature> {
+    Ok(Signature::new_secure(msg, &*self.keypair))
+  }
+}
@@ -0,0 +1,43 @@
+use std::sync::Arc;
+use async_trait::async_trait;
+use eyre::{Result, ContextCompat};
+use shared_crypto::intent::IntentMessage;
+use sui_types::{base_types::SuiAddress, crypto::Signature, transaction::TransactionData};
+use crate::utils::{config::{SuiConfig, SignerKind}, keystore::sponsor_keypairs};
+use self::{keypair::KeyPairSigner, remote::RemoteSigner};
+
+pub mod keypair;
+pub mod remote;
+
+/// Produces the sponsor signatures. Implementations decide where the private key lives
+#[async_trait]
+pub trait Signer: Send + Sync {
+  /// The address of the account this signer signs for
+  fn address(&self) -> SuiAddress;
+
+  async fn sign(&self, msg: &IntentMessage<TransactionData>) -> Result<Signature>;
+}
+
+/// Creates the sponsor signers selected by the `SIGNER` config; one for each sponsor account
+pub fn sponsor_signers(config: &SuiConfig) -> Result<Vec<Arc<dyn Signer>>> {
+  let signers = match config.signer.as_ref().unwrap_or(&SignerKind::Local) {
+    SignerKind::Local => sponsor_keypairs(config)?
+    .into_iter()
+    .map(|keypair| Arc::new(KeyPairSigner::new(keypair)) as Arc<dyn Signer>)
+    .collect(),
+    SignerKind::Remote => {
+      let url = config.remote_signer_url.as_ref().context("REMOTE_SIGNER_URL is required by the remote signer")?;
+      let addresses = config.sponsor_addresses.as_ref().context("SPONSOR_ADDRESS is required by the remote signer")?;
+
+      addresses.0.iter()
+      .map(|address| Arc::new(RemoteSigner::new(
+        url.clone(),
+        config.remote_signer_auth_token.clone(),
+        *address,
+      )) as Arc<dyn Signer>)
+      .collect()
+    },
+  };
+
+  Ok(signers)
+}
@@ -0,0 +1,76 @@
+use async_trait::async_trait;
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+use shared_crypto::intent::IntentMessage;
+use sui_types::{
+  base_types::SuiAddress, crypto::{Signature, ToFromBytes}, transaction::TransactionData,
+};
+use crate::map_err;
+use super::Signer;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SignRequest {
+  address: SuiAddress,
+  // Base64 encoded BCS bytes of the intent message
+  intent_message: String,
+}
+
+#[derive(Deserialize)]
+struct SignResponse {
+  // Base64 encoded `flag || signature || public key` bytes
+  signature: String,
+}
+
+/// Delegates signing to an external service (e.g. one backed by a KMS or an HSM) so that the private key never
+/// lives in this process. The service must expose `POST /sign`; see the README for the protocol.
+pub struct RemoteSigner {
+  client: reqwest::Client,
+  url: String,
+  auth_token: Option<String>,
+  address: SuiAddress,
+}
+
+impl RemoteSigner {
+  pub fn new(url: String, auth_token: Option<String>, address: SuiAddress) -> Self {
+    Self {
+      client: reqwest::Client::new(),
+      url,
+      auth_token,
+      address,
+    }
+  }
+}
+
+#[async_trait]
+impl Signer for RemoteSigner {
+  fn address(&self) -> SuiAddress {
+    self.address
+  }
+
+  async fn sign(&self, msg: &IntentMessage<TransactionData>) -> Result<Signature> {
+    let body = SignRequest {
+      address: self.address,
+      intent_message: base64::encode(bcs::to_bytes(msg)?),
+    };
+
+    let mut request = self.client.post(format!("{}/sign", self.url)).json(&body);
+    if let Some(auth_token) = &self.auth_token {
+      request = request.bearer_auth(auth_token);
+    }
+
+    let response = request
+    .send()
+    .await?
+    .error_for_status()?
+    .json::<SignResponse>()
+    .await?;
+
+    let sig_data = map_err!(base64::decode(&response.signature))?;
+    // Note that the signature is not verified here. A signature from a different key is rejected when the
+    // transaction is verified before it's executed
+    let signature = map_err!(Signature::from_bytes(&sig_data))?;
+
+    Ok(signature)
+  }
+}
@@ -1,21 +1,72 @@
-use std::sync::Arc;
-use eyre::{eyre, Result, ensure};
+use std::{collections::HashMap, sync::Arc};
+use eyre::{eyre, Result, ensure, ContextCompat};
+use serde::Serialize;
 use shared_crypto::intent::Intent;
 use sui_sdk::SuiClient;
 use sui_types::{
   transaction::{GasData, TransactionData, TransactionKind, Command, ProgrammableMoveCall},
-  base_types::{ObjectID, SuiAddress}, gas_coin::GasCoin, crypto::Signature,
+  base_types::{ObjectID, SuiAddress}, gas_coin::GasCoin, signature::GenericSignature, gas::GasCostSummary,
 };
-use crate::{gas_pool::GasPool, helpers::object::get_object, map_err};
+use log::warn;
+use crate::{helpers::object::{get_object, get_object_types}, map_err, storage::redis::ConnectionPool};
 use super::{
-  gas_meter::GasMeter, wallet::Wallet,
+  gas_meter::{GasMeter, GasPricePolicy}, shard::{Shard, ShardSelector}, rotation::RotationCache,
+  policy::{Policy, TenantPolicy},
+  ptb::{is_ptb_allowed_with_object_types, untyped_vector_objects},
 };
 
+/// What a transaction would cost the sponsor, according to a dry run, and whether it would be sponsored
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Estimate {
+  pub gas_price: u64,
+  pub computation_cost: u64,
+  pub storage_cost: u64,
+  pub storage_rebate: u64,
+  // Upper bound of the gas used i.e. the budget the transaction needs
+  pub gas_budget: u64,
+  // What the transaction costs the sponsor once the storage rebate is paid out. It's negative if the rebate is
+  // larger than the cost e.g. for transactions that delete objects
+  pub net_gas_cost: i64,
+  pub sponsored: bool,
+  // The execution error reported by the dry run, if any
+  pub error: Option<String>,
+}
+
+impl Estimate {
+  /// Builds the estimate from the gas used by a dry run. The transaction is sponsored if it's supported, the dry run
+  /// succeeded and the budget it needs is within the budget the sponsor grants it.
+  pub fn new(
+    gas_price: u64,
+    gas_used: &GasCostSummary,
+    error: Option<String>,
+    is_supported: bool,
+    granted_budget: u64,
+  ) -> Self {
+    let gas_budget = gas_used.computation_cost.saturating_add(gas_used.storage_cost);
+    let net_gas_cost = gas_budget as i128 - gas_used.storage_rebate as i128;
+
+    Self {
+      gas_price,
+      computation_cost: gas_used.computation_cost,
+      storage_cost: gas_used.storage_cost,
+      storage_rebate: gas_used.storage_rebate,
+      gas_budget,
+      net_gas_cost: net_gas_cost.clamp(i64::MIN as i128, i64::MAX as i128) as i64,
+      sponsored: is_supported && error.is_none() && gas_budget <= granted_budget,
+      error,
+    }
+  }
+}
+
 pub struct Sponsor {
   api: Arc<SuiClient>,
-  wallet: Arc<Wallet>,
+  // One shard per sponsor account
+  shards: Arc<Vec<Shard>>,
+  shard_selector: ShardSelector,
   gas_meter: Arc<GasMeter>,
-  gas_pool: Arc<&'static GasPool>,
+  rotations: RotationCache,
+  policy: Arc<Policy>,
   min_coin_balance: u64,
   max_gas_budget: u64,
 }
@@ -23,22 +74,33 @@ pub struct Sponsor {
 impl Sponsor {
   pub fn new(
     api: Arc<SuiClient>,
-    wallet: Arc<Wallet>,
+    shards: Arc<Vec<Shard>>,
+    shard_selector: ShardSelector,
     gas_meter: Arc<GasMeter>,
-    gas_pool: Arc<&'static GasPool>,
+    redis_pool: Arc<ConnectionPool>,
+    policy: Arc<Policy>,
     min_coin_balance: u64,
     max_gas_budget: u64,
   ) -> Self {
     Self {
       api,
-      wallet,
-      gas_pool,
+      shards,
+      shard_selector,
       gas_meter,
+      rotations: RotationCache::new(redis_pool),
+      policy,
       min_coin_balance,
       max_gas_budget,
     }
   }
 
+  /// Returns the shard of the given sponsor account
+  fn shard(&self, sponsor: SuiAddress) -> Result<&Shard> {
+    self.shards.iter()
+    .find(|shard| shard.sponsor() == sponsor)
+    .ok_or_else(|| eyre!("unknown sponsor {}", sponsor))
+  }
+
   /// TODO: add logic that will check if the given sender address is blacklisted i.e. it caused equivocation
   /// in the past and thus it's not elligible to use the sponsor service anymore
   fn is_blacklisted(_sender: &SuiAddress) -> bool {
@@ -51,18 +113,42 @@ impl Sponsor {
     true
   }
 
-  /// TODO: makes sure that client does no abuse by trying to execute expensive transactions blocks
-  fn is_gas_budget_within_limits(_gas_data: &GasData) -> bool {
-    true
+  /// Makes sure that the client does not abuse the sponsor by executing transactions more expensive than the
+  /// budget they were granted
+  fn is_gas_budget_within_limits(gas_data: &GasData, granted_budget: u64) -> bool {
+    gas_data.budget <= granted_budget
+  }
+
+  /// The gas budget the policy grants to the given transaction, if any. See `TenantPolicy::granted_gas_budget`
+  fn granted_gas_budget(&self, tx_data: &TransactionKind, policy: &TenantPolicy) -> Option<u64> {
+    let TransactionKind::ProgrammableTransaction(ptx) = tx_data else {return Some(self.max_gas_budget)};
+
+    policy.granted_gas_budget(ptx, self.max_gas_budget)
   }
 
-  /// Examined the given transaction data and determines if sponsor supports it.
-  fn is_tx_supported(tx_data: &TransactionKind, sender: SuiAddress) -> bool {
-    if Self::is_blacklisted(&sender) {return false};
-    let TransactionKind::ProgrammableTransaction(ptx) = &tx_data else {return false};
+  /// Examined the given transaction data and determines if sponsor supports it under the given policy. The objects
+  /// that MakeMoveVec commands put in vectors without an explicit type are read, so that their type can be checked,
+  /// as long as the policy allows some vector type at all.
+  async fn is_tx_supported(
+    &self,
+    tx_data: &TransactionKind,
+    sender: SuiAddress,
+    policy: &TenantPolicy,
+  ) -> Result<bool> {
+    if Self::is_blacklisted(&sender) {return Ok(false)};
+    let TransactionKind::ProgrammableTransaction(ptx) = &tx_data else {return Ok(false)};
+
+    let vector_objects = untyped_vector_objects(ptx);
+    let object_types = if vector_objects.is_empty() || policy.ptb.make_move_vec_types.is_empty() {
+      HashMap::new()
+    } else {
+      get_object_types(Arc::clone(&self.api), vector_objects).await?
+    };
+
+    if !is_ptb_allowed_with_object_types(ptx, sender, &policy.ptb, &object_types) {return Ok(false)};
 
     // Make sure all commands are supported
-    ptx.commands.iter().all(|cmd| match cmd {
+    let is_supported = ptx.commands.iter().all(|cmd| match cmd {
       Command::MoveCall(move_call) => {
         let ProgrammableMoveCall {package, module, function, ..} = &**move_call;
 
@@ -72,60 +158,156 @@ impl Sponsor {
 
         true
       },
-      Command::SplitCoins(_, _)
-      | Command::TransferObjects(_, _)
-      | Command::MergeCoins(_, _) => return true,
-      Command::Publish(_, _)
-      | Command::MakeMoveVec(_, _)
-      | Command::Upgrade(_, _, _, _) => return false,
-    })
+      // The rest are checked against the policy by is_ptb_allowed
+      _ => true,
+    });
+
+    Ok(is_supported)
   }
 
-  async fn create_gas_data(&self) -> Result<GasData> {
-    let pubkey = &self.wallet.public();
+  /// Takes a gas coin, for a transaction of the given sender, from the shard selected by the shard strategy. If that
+  /// shard has no coins available, the next one is tried. The shards of sponsors that are being rotated out are
+  /// skipped.
+  async fn create_gas_data(
+    &self,
+    gas_price_policy: &GasPricePolicy,
+    budget: u64,
+    sender: SuiAddress,
+  ) -> Result<GasData> {
+    let price = self.gas_meter.sponsored_gas_price(gas_price_policy).await?;
+    let rotations = self.rotations.load().await?;
 
-    let gas_data = GasData {
-      payment: vec![self.gas_pool.gas_object().await?],
-      owner: pubkey.into(),
-      price: self.gas_meter.gas_price().await?,
-      budget: self.max_gas_budget,
-    };
+    for shard in self.shard_selector.order(&self.shards) {
+      if rotations.contains_key(&shard.sponsor()) {continue}
 
-    Ok(gas_data)
+      match shard.gas_pool.gas_object(sender).await {
+        Ok(gas_object) => return Ok(GasData {
+          payment: vec![gas_object],
+          owner: shard.sponsor(),
+          price,
+          budget,
+        }),
+        Err(error) => warn!("Shard {} could not provide a gas coin: {:?}", shard.sponsor(), error),
+      }
+    }
+
+    Err(eyre!("Gas pool empty"))
   }
 
-  pub async fn gas_object_processed(&self, coin_object_id: ObjectID) -> Result<()> {
+  /// Returns the given gas coin, owned by the given sponsor, back to its pool or removes it from the pool
+  pub async fn gas_object_processed(&self, sponsor: SuiAddress, coin_object_id: ObjectID) -> Result<()> {
+    let gas_pool = &self.shard(sponsor)?.gas_pool;
     let coin = &get_object(Arc::clone(&self.api), coin_object_id).await?;
     let coin_balance = map_err!(TryInto::<GasCoin>::try_into(coin))?;
+    let is_rotating = self.rotations.load().await?.contains_key(&sponsor);
 
     // check if the coin_object_id has enough balance. If not then remove it from the queue i.e. ack
-    // as well as, from Redis.
-    if coin_balance.value() <= self.min_coin_balance {
-      self.gas_pool.remove_gas_object(coin_object_id).await?;
+    // as well as, from Redis. The same goes for coins the coin manager wants to shrink the pool by and for
+    // the coins of a sponsor that is being rotated out.
+    if coin_balance.value() <= self.min_coin_balance || is_rotating || gas_pool.is_retiring(coin_object_id).await? {
+      gas_pool.remove_gas_object(coin_object_id).await?;
     } else {
-      self.gas_pool.return_gas_object(coin_object_id).await?;
+      gas_pool.return_gas_object(coin_object_id).await?;
     }
 
     Ok(())
   }
 
-  /// Returns a gas objects for the given transaction data
-  pub async fn request_gas(&self, tx_data: TransactionKind, sender: SuiAddress) -> Result<GasData> {
-    ensure!(Self::is_tx_supported(&tx_data, sender), "transaction is not supported");
-    let gas_data = self.create_gas_data().await?;
-    ensure!(Self::is_gas_budget_within_limits(&gas_data), "exceeded gas budget");
+  /// Returns a gas objects for the given transaction data. The gas price and budget are set by the policy of the
+  /// given tenant, or the default policy if there is none.
+  pub async fn request_gas(
+    &self,
+    tx_data: TransactionKind,
+    sender: SuiAddress,
+    tenant: Option<&str>,
+  ) -> Result<GasData> {
+    let tenant_policy = self.policy.tenant(tenant)?;
+    ensure!(self.is_tx_supported(&tx_data, sender, tenant_policy).await?, "transaction is not supported");
+    let granted_budget = self.granted_gas_budget(&tx_data, tenant_policy)
+    .context("the transaction calls a function without a gas budget")?;
+    let gas_data = self.create_gas_data(&tenant_policy.gas_price, granted_budget, sender).await?;
+    ensure!(Self::is_gas_budget_within_limits(&gas_data, granted_budget), "exceeded gas budget");
 
     Ok(gas_data)
   }
 
+  /// Estimates the cost of the given transaction to the sponsor by dry running it with the gas budget it would be
+  /// granted. Dry runs don't lock objects, so the largest coin of one of the sponsors stands in for the gas coin.
+  /// Nothing is taken from the Gas Pool. The transaction goes through the same checks as in `request_gas`.
+  pub async fn estimate(
+    &self,
+    tx_data: TransactionKind,
+    sender: SuiAddress,
+    tenant: Option<&str>,
+  ) -> Result<Estimate> {
+    let tenant_policy = self.policy.tenant(tenant)?;
+    let rotations = self.rotations.load().await?;
+    let sponsor = self.shards.iter()
+    .map(Shard::sponsor)
+    .find(|sponsor| !rotations.contains_key(sponsor))
+    .context("no sponsor available")?;
+
+    let gas_coin = self.api.coin_read_api()
+    .get_coins(sponsor, None, None, None)
+    .await?
+    .data
+    .into_iter()
+    .max_by_key(|coin| coin.balance)
+    .context("the sponsor has no coins")?;
+
+    let gas_price = self.gas_meter.sponsored_gas_price(&tenant_policy.gas_price).await?;
+    // A transaction that calls a function without a budget is not sponsored, but it's still dry run so that the
+    // client learns what it would cost
+    let granted_budget = self.granted_gas_budget(&tx_data, tenant_policy);
+    let budget = granted_budget.unwrap_or(self.max_gas_budget);
+    let gas_data = GasData {
+      payment: vec![gas_coin.object_ref()],
+      owner: sponsor,
+      price: gas_price,
+      budget,
+    };
+    let is_supported = self.is_tx_supported(&tx_data, sender, tenant_policy).await?
+    && granted_budget.map_or(false, |granted| Self::is_gas_budget_within_limits(&gas_data, granted));
+
+    let (gas_used, error) = self.gas_meter
+    .dry_run(TransactionData::new_with_gas_data(tx_data, sender, gas_data))
+    .await?;
+
+    Ok(Estimate::new(gas_price, &gas_used, error, is_supported, budget))
+  }
+
+  /// Dry runs the given transaction, if the policy of the given tenant asks for it, and returns the execution
+  /// error if it would fail. A failing transaction still burns the sponsor gas, so it should not be submitted.
+  /// Only transactions that have passed `sign_tx`, and whose signatures have been verified, should be dry run.
+  pub async fn dry_run_failure(&self, tx_data: &TransactionData, tenant: Option<&str>) -> Result<Option<String>> {
+    if !self.policy.tenant(tenant)?.dry_run_before_submit {return Ok(None)}
+
+    let (_, error) = self.gas_meter.dry_run(tx_data.clone()).await?;
+    Ok(error)
+  }
+
   /// Returns a signature on the entire transaction. This is after the client has requested a gas object
   /// and has signed the given tx_data. After this call, sponsor can transmit the transaction.
-  /// Performs the same transaction data checks as in `request_gas`.
-  pub async fn sign_tx(&self, tx_data: &TransactionData) -> Result<Signature> {
+  /// Performs the same transaction data checks as in `request_gas`, under the policy of the given tenant. The
+  /// transaction must be paid with a single gas coin that this instance has handed out to its sender and not yet
+  /// taken back. The transaction is signed with the key of the sponsor that owns the gas coin.
+  pub async fn sign_tx(&self, tx_data: &TransactionData, tenant: Option<&str>) -> Result<GenericSignature> {
+    let tenant_policy = self.policy.tenant(tenant)?;
     let TransactionData::V1(tx) = &tx_data;
-    ensure!(Self::is_tx_supported(&tx.kind, tx.sender), "transaction is not supported");
-    ensure!(Self::is_gas_budget_within_limits(&tx.gas_data), "exceeded gas budget");
+    ensure!(self.is_tx_supported(&tx.kind, tx.sender, tenant_policy).await?, "transaction is not supported");
+    let granted_budget = self.granted_gas_budget(&tx.kind, tenant_policy)
+    .context("the transaction calls a function without a gas budget")?;
+    ensure!(Self::is_gas_budget_within_limits(&tx.gas_data, granted_budget), "exceeded gas budget");
+
+    let shard = self.shard(tx.gas_data.owner)?;
+    let [(gas_coin, _, _)] = tx.gas_data.payment.as_slice() else {
+      return Err(eyre!("the transaction must be paid with a single gas coin"))
+    };
+    ensure!(
+      shard.gas_pool.is_checked_out_for(*gas_coin, tx.sender),
+      "gas coin {} was not handed out to {} by this instance", gas_coin, tx.sender,
+    );
 
-    self.wallet.sign(&tx_data, Intent::sui_transaction())
+    shard.wallet.sign(&tx_data, Intent::sui_transaction()).await
   }
 }
@@ -1,35 +1,126 @@
-use serde::Serialize;
-use eyre::Result;
+use std::sync::Arc;
+use eyre::{Result, ensure, ContextCompat};
+use log::warn;
 use shared_crypto::intent::{IntentMessage, Intent};
-use sui_types::{crypto::{PublicKey, Signature}, base_types::SuiAddress};
+use sui_types::{
+  base_types::SuiAddress, transaction::TransactionData, signature::GenericSignature,
+  multisig::{MultiSig, MultiSigPublicKey, WeightUnit},
+};
+use crate::{map_err, utils::config::SuiConfig};
+use super::signer::{Signer, sponsor_signers};
 
-use crate::utils::config::KeyPair;
+enum Keys {
+  Single(Arc<dyn Signer>),
+  // The signers of the member keys that are available to us, in the order of the multisig public key, together
+  // with their weight
+  Multisig {
+    public_key: MultiSigPublicKey,
+    members: Vec<(Arc<dyn Signer>, WeightUnit)>,
+  },
+}
 
+/// Signs on behalf of an account that is either controlled by a single key or is a multisig
 pub struct Wallet {
-  keypair: KeyPair,
+  address: SuiAddress,
+  keys: Keys,
 }
 
 impl Wallet {
-  pub fn new(keypair: KeyPair) -> Self {
+  pub fn new(signer: Arc<dyn Signer>) -> Self {
     Self {
-      keypair,
+      address: signer.address(),
+      keys: Keys::Single(signer),
     }
   }
 
-  pub fn public(&self) -> PublicKey {
-    self.keypair.public()
+  /// A wallet for the multisig account of the given public key. Each signer must hold one of the member keys
+  /// and together they must reach the threshold.
+  pub fn multisig(public_key: MultiSigPublicKey, signers: Vec<Arc<dyn Signer>>) -> Result<Self> {
+    let member_weights = public_key.pubkeys().iter()
+    .map(|(pk, weight)| (SuiAddress::from(pk), *weight))
+    .collect::<Vec<_>>();
+
+    for signer in &signers {
+      ensure!(
+        member_weights.iter().any(|(address, _)| *address == signer.address()),
+        "{} is not a member of the multisig", signer.address(),
+      );
+    }
+
+    let members = member_weights.into_iter()
+    .filter_map(|(address, weight)| {
+      signers.iter()
+      .find(|signer| signer.address() == address)
+      .map(|signer| (Arc::clone(signer), weight))
+    })
+    .collect::<Vec<_>>();
+
+    let total_weight = members.iter().map(|(_, weight)| *weight as u16).sum::<u16>();
+    ensure!(
+      total_weight >= *public_key.threshold(),
+      "the available member keys weigh {} but the multisig threshold is {}", total_weight, public_key.threshold(),
+    );
+
+    Ok(Self {
+      address: SuiAddress::from(&public_key),
+      keys: Keys::Multisig {public_key, members},
+    })
   }
 
   pub fn address(&self) -> SuiAddress {
-    (&self.keypair.public()).into()
+    self.address
   }
 
-  pub fn sign<T: Serialize>(&self, msg: &T, intent: Intent) -> Result<Signature> {
-    let sig = Signature::new_secure(
-      &IntentMessage::new(intent, msg),
-      &*self.keypair
-    );
+  /// Signs the given transaction. A multisig is signed by its members, in order, until their weight reaches the
+  /// threshold; a member that fails to sign is skipped.
+  pub async fn sign(&self, tx_data: &TransactionData, intent: Intent) -> Result<GenericSignature> {
+    let msg = IntentMessage::new(intent, tx_data.clone());
+
+    let (public_key, members) = match &self.keys {
+      Keys::Single(signer) => return Ok(GenericSignature::Signature(signer.sign(&msg).await?)),
+      Keys::Multisig {public_key, members} => (public_key, members),
+    };
+
+    let threshold = *public_key.threshold();
+    let mut signatures = vec![];
+    let mut weight = 0;
 
-    Ok(sig)
+    for (signer, member_weight) in members {
+      if weight >= threshold {break}
+
+      match signer.sign(&msg).await {
+        Ok(signature) => {
+          signatures.push(signature);
+          weight += *member_weight as u16;
+        },
+        Err(error) => warn!("Multisig member {} failed to sign: {:?}", signer.address(), error),
+      }
+    }
+
+    ensure!(weight >= threshold, "multisig members reached weight {} of {}", weight, threshold);
+    let multisig = map_err!(MultiSig::combine(signatures, public_key.clone()))?;
+
+    Ok(GenericSignature::MultiSig(multisig))
   }
 }
+
+/// Creates one wallet per sponsor account. If `MULTISIG_PUBLIC_KEYS` is set there is a single sponsor, the
+/// multisig, and the sponsor signers hold its member keys.
+pub fn sponsor_wallets(config: &SuiConfig) -> Result<Vec<Wallet>> {
+  let signers = sponsor_signers(config)?;
+
+  let Some(public_keys) = &config.multisig_public_keys else {
+    return Ok(signers.into_iter().map(Wallet::new).collect())
+  };
+
+  let public_keys = public_keys.0.iter().map(|pk| pk.0.clone()).collect::<Vec<_>>();
+  let weights = config.multisig_weights.as_ref().map_or(vec![1; public_keys.len()], |w| w.0.clone());
+  ensure!(
+    weights.len() == public_keys.len(),
+    "MULTISIG_WEIGHTS has {} weights but MULTISIG_PUBLIC_KEYS has {} keys", weights.len(), public_keys.len(),
+  );
+  let threshold = config.multisig_threshold.context("MULTISIG_THRESHOLD must be set when MULTISIG_PUBLIC_KEYS is")?;
+  let public_key = map_err!(MultiSigPublicKey::new(public_keys, weights, threshold))?;
+
+  Ok(vec![Wallet::multisig(public_key, signers)?])
+}
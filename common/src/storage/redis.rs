@@ -1,6 +1,6 @@
-use std::iter::zip;
+use std::{iter::zip, collections::{HashSet, HashMap}};
 use deadpool_redis::{Pool, Config, Connection, Runtime};
-use redis::cmd;
+use redis::{cmd, pipe};
 use eyre::Result;
 
 pub struct ConnectionPool(Pool);
@@ -52,6 +52,24 @@ impl Redis {
     .map_err(Into::<_>::into)
   }
 
+  /// Sets the key only if it does not exist. Returns whether the key was set
+  pub async fn set_nx<T: AsRef<str>>(&mut self, key: T, value: T) -> Result<bool> {
+    let result: Option<String> = cmd("SET")
+    .arg(&[key.as_ref(), value.as_ref(), "NX"])
+    .query_async(&mut self.0).await?;
+
+    Ok(result.is_some())
+  }
+
+  /// Sets the key with the given expiry only if it does not exist. Returns whether the key was set
+  pub async fn set_nx_ex<T: AsRef<str>>(&mut self, key: T, value: T, secs: usize) -> Result<bool> {
+    let result: Option<String> = cmd("SET")
+    .arg(&[key.as_ref(), value.as_ref(), "NX", "EX", &secs.to_string()])
+    .query_async(&mut self.0).await?;
+
+    Ok(result.is_some())
+  }
+
   pub async fn get<T: AsRef<str>>(&mut self, key: T) -> Result<String> {
     cmd("GET")
     .arg(&[key.as_ref()])
@@ -59,6 +77,14 @@ impl Redis {
     .map_err(Into::<_>::into)
   }
 
+  /// Same as `get` but returns None if the key does not exist
+  pub async fn get_opt<T: AsRef<str>>(&mut self, key: T) -> Result<Option<String>> {
+    cmd("GET")
+    .arg(&[key.as_ref()])
+    .query_async(&mut self.0).await
+    .map_err(Into::<_>::into)
+  }
+
   pub async fn mget<T: AsRef<str>>(&mut self, keys: &[T]) -> Result<Vec<String>> {
     let keys = keys.iter().map(AsRef::as_ref).collect::<Vec<_>>();
 
@@ -81,4 +107,167 @@ impl Redis {
     .query_async(&mut self.0).await
     .map_err(Into::<_>::into)
   }
+
+  /// Incrementally iterates over the keys matching the given pattern. Unlike `KEYS` this will not block the
+  /// server while traversing the keyspace. Note that SCAN can return the same key more than once, hence the set.
+  pub async fn scan<T: AsRef<str>>(&mut self, key_pattern: T, count: usize) -> Result<HashSet<String>> {
+    let mut keys = HashSet::new();
+    let mut cursor = 0_u64;
+
+    loop {
+      let (next_cursor, batch): (u64, Vec<String>) = cmd("SCAN")
+      .arg(cursor)
+      .arg("MATCH")
+      .arg(key_pattern.as_ref())
+      .arg("COUNT")
+      .arg(count)
+      .query_async(&mut self.0).await?;
+
+      keys.extend(batch);
+
+      if next_cursor == 0 {break}
+      cursor = next_cursor;
+    }
+
+    Ok(keys)
+  }
+
+  pub async fn sadd<T: AsRef<str>>(&mut self, key: T, members: &[T]) -> Result<()> {
+    let members = members.iter().map(AsRef::as_ref).collect::<Vec<_>>();
+
+    cmd("SADD")
+    .arg(key.as_ref())
+    .arg(members)
+    .query_async(&mut self.0).await
+    .map_err(Into::<_>::into)
+  }
+
+  /// Adds the given members to each of the given sets in a single MULTI/EXEC transaction
+  pub async fn sadd_all<K: AsRef<str>, V: AsRef<str>>(&mut self, keys: &[K], members: &[V]) -> Result<()> {
+    let members = members.iter().map(AsRef::as_ref).collect::<Vec<_>>();
+    let mut pipe = pipe();
+    pipe.atomic();
+
+    for key in keys {
+      pipe.cmd("SADD").arg(key.as_ref()).arg(&members).ignore();
+    }
+
+    pipe
+    .query_async(&mut self.0).await
+    .map_err(Into::<_>::into)
+  }
+
+  pub async fn srem<T: AsRef<str>>(&mut self, key: T, member: T) -> Result<()> {
+    cmd("SREM")
+    .arg(&[key.as_ref(), member.as_ref()])
+    .query_async(&mut self.0).await
+    .map_err(Into::<_>::into)
+  }
+
+  /// Removes the given members from each of the given sets in a single MULTI/EXEC transaction
+  pub async fn srem_all<K: AsRef<str>, V: AsRef<str>>(&mut self, keys: &[K], members: &[V]) -> Result<()> {
+    let members = members.iter().map(AsRef::as_ref).collect::<Vec<_>>();
+    let mut pipe = pipe();
+    pipe.atomic();
+
+    for key in keys {
+      pipe.cmd("SREM").arg(key.as_ref()).arg(&members).ignore();
+    }
+
+    pipe
+    .query_async(&mut self.0).await
+    .map_err(Into::<_>::into)
+  }
+
+  pub async fn scard<T: AsRef<str>>(&mut self, key: T) -> Result<usize> {
+    cmd("SCARD")
+    .arg(key.as_ref())
+    .query_async(&mut self.0).await
+    .map_err(Into::<_>::into)
+  }
+
+  pub async fn sismember<T: AsRef<str>>(&mut self, key: T, member: T) -> Result<bool> {
+    cmd("SISMEMBER")
+    .arg(&[key.as_ref(), member.as_ref()])
+    .query_async(&mut self.0).await
+    .map_err(Into::<_>::into)
+  }
+
+  /// Incrementally iterates over all members of the given set. SSCAN can return the same member more
+  /// than once, hence the set.
+  pub async fn sscan<T: AsRef<str>>(&mut self, key: T, count: usize) -> Result<HashSet<String>> {
+    let mut members = HashSet::new();
+    let mut cursor = 0_u64;
+
+    loop {
+      let (next_cursor, batch): (u64, Vec<String>) = cmd("SSCAN")
+      .arg(key.as_ref())
+      .arg(cursor)
+      .arg("COUNT")
+      .arg(count)
+      .query_async(&mut self.0).await?;
+
+      members.extend(batch);
+
+      if next_cursor == 0 {break}
+      cursor = next_cursor;
+    }
+
+    Ok(members)
+  }
+
+  pub async fn hset<T: AsRef<str>>(&mut self, key: T, field: T, value: T) -> Result<()> {
+    cmd("HSET")
+    .arg(&[key.as_ref(), field.as_ref(), value.as_ref()])
+    .query_async(&mut self.0).await
+    .map_err(Into::<_>::into)
+  }
+
+  /// Records the given field in the hash, unless it's already there, and checks whether the given member is in the
+  /// set, in a single MULTI/EXEC transaction. Returns whether the field was set and whether the member was found
+  pub async fn hsetnx_sismember<T: AsRef<str>>(
+    &mut self,
+    key: T,
+    field: T,
+    value: T,
+    set_key: T,
+    member: T,
+  ) -> Result<(bool, bool)> {
+    pipe()
+    .atomic()
+    .cmd("HSETNX").arg(&[key.as_ref(), field.as_ref(), value.as_ref()])
+    .cmd("SISMEMBER").arg(&[set_key.as_ref(), member.as_ref()])
+    .query_async(&mut self.0).await
+    .map_err(Into::<_>::into)
+  }
+
+  pub async fn hdel<T: AsRef<str>>(&mut self, key: T, field: T) -> Result<()> {
+    cmd("HDEL")
+    .arg(&[key.as_ref(), field.as_ref()])
+    .query_async(&mut self.0).await
+    .map_err(Into::<_>::into)
+  }
+
+  pub async fn hgetall<T: AsRef<str>>(&mut self, key: T) -> Result<HashMap<String, String>> {
+    cmd("HGETALL")
+    .arg(key.as_ref())
+    .query_async(&mut self.0).await
+    .map_err(Into::<_>::into)
+  }
+
+  /// Increments the given fields of a hash and sets its expiry in a single MULTI/EXEC transaction
+  pub async fn hincrby_ex<T: AsRef<str>>(&mut self, key: T, increments: &[(T, u64)], secs: usize) -> Result<()> {
+    let mut pipe = pipe();
+    pipe.atomic();
+
+    for (field, increment) in increments {
+      pipe.cmd("HINCRBY").arg(key.as_ref()).arg(field.as_ref()).arg(*increment).ignore();
+    }
+
+    pipe.cmd("EXPIRE").arg(key.as_ref()).arg(secs).ignore();
+
+    pipe
+    .query_async(&mut self.0).await
+    .map_err(Into::<_>::into)
+  }
 }
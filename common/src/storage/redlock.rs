@@ -1,14 +1,16 @@
 use eyre::{Result, Report};
-use rslock::{LockManager, Lock};
+use rslock::LockManager;
+
+pub use rslock::Lock;
 
 pub struct RedLock {
   inner: LockManager,
 }
 
 impl RedLock {
-  pub fn new(redis_hosts: Vec<&str>, password: &str) -> Self {
+  pub fn new(redis_hosts: Vec<&str>, password: &str, port: u16) -> Self {
     let inner = LockManager::new(
-    redis_hosts.iter().map(|redis_host| format!("redis://:{}@{}:6379", password, redis_host)).collect(),
+    redis_hosts.iter().map(|redis_host| format!("redis://:{}@{}:{}", password, redis_host, port)).collect(),
   );
 
     Self {inner}
@@ -20,6 +22,14 @@ impl RedLock {
     .map_err(|error| Report::msg(format!("{:?}", error)))
   }
 
+  /// Extends the validity of a lock we already hold by the given ttl. Fails if the lock has expired and has
+  /// been acquired by someone else in the meantime.
+  pub async fn extend<'a>(&'a self, lock: &Lock<'a>, ttl: usize) -> Result<Lock<'a>> {
+    self.inner.extend(lock, ttl)
+    .await
+    .map_err(|error| Report::msg(format!("{:?}", error)))
+  }
+
   pub async fn unlock(&self, lock: Lock<'_>) {
     self.inner.unlock(&lock).await
   }
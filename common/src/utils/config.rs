@@ -1,7 +1,8 @@
 use std::{str::FromStr, sync::Arc, ops::Deref};
 use envconfig::Envconfig;
-use sui_types::{crypto::SuiKeyPair};
+use sui_types::{crypto::{self, SuiKeyPair, EncodeDecodeBase64}, base_types::SuiAddress};
 use eyre::Report;
+use crate::{services::shard::ShardStrategy, ledger::LedgerBackend};
 
 #[derive(Envconfig)]
 pub struct Config {
@@ -19,14 +20,26 @@ pub struct Config {
   pub gas_pool: GasPoolConfig,
   #[envconfig(nested = true)]
   pub sponsor: SponsorConfig,
+  #[envconfig(nested = true)]
+  pub coin_manager: CoinManagerConfig,
+  #[envconfig(nested = true)]
+  pub treasury: TreasuryConfig,
+  #[envconfig(nested = true)]
+  pub ledger: LedgerConfig,
   #[envconfig(from = "FIREBASE_API_KEY")]
   pub firebase_api_key: Option<String>,
+  // Bearer token of the operator endpoints i.e. /ledger and /admin. They are disabled if not set
+  #[envconfig(from = "ADMIN_AUTH_TOKEN")]
+  pub admin_auth_token: Option<String>,
 }
 
 #[derive(Envconfig)]
 pub struct SponsorConfig {
   #[envconfig(from = "MAX_GAS_BUDGET")]
   pub max_gas_budget: Option<u64>,
+  // JSON file with the gas price, and other, settings of each tenant. The defaults apply if not set
+  #[envconfig(from = "SPONSOR_POLICY_FILE")]
+  pub policy_file: Option<String>,
 }
 
 #[derive(Envconfig)]
@@ -39,6 +52,79 @@ pub struct GasPoolConfig {
   pub coin_balance_deposit: Option<u64>,
   #[envconfig(from = "MIN_COIN_BALANCE")]
   pub min_coin_balance: Option<u64>,
+  #[envconfig(from = "TARGET_POOL_BALANCE")]
+  pub target_pool_balance: Option<u64>,
+  #[envconfig(from = "MIN_POOL_BALANCE")]
+  pub min_pool_balance: Option<u64>,
+  #[envconfig(from = "TOP_UP_THRESHOLD")]
+  pub top_up_threshold: Option<u64>,
+  #[envconfig(from = "ADAPTIVE_POOL_SIZING")]
+  pub adaptive_sizing: Option<bool>,
+  #[envconfig(from = "POOL_SIZING_WINDOW")]
+  pub sizing_window: Option<u64>,
+  #[envconfig(from = "POOL_HEADROOM")]
+  pub headroom: Option<f64>,
+}
+
+#[derive(Envconfig)]
+pub struct CoinManagerConfig {
+  // Validity, in milliseconds, of the leader lease a coin manager instance must hold in order to rebalance coins
+  #[envconfig(from = "LEADER_LEASE_TTL")]
+  pub leader_lease_ttl: Option<usize>,
+  // Interval, in seconds, between two reconciliations of the pool with the chain state
+  #[envconfig(from = "RECONCILE_INTERVAL")]
+  pub reconcile_interval: Option<u64>,
+}
+
+#[derive(Envconfig)]
+pub struct TreasuryConfig {
+  // A warning alert is raised when the total sponsor balance drops below this
+  #[envconfig(from = "BALANCE_WARNING_THRESHOLD")]
+  pub balance_warning_threshold: Option<u64>,
+  // A critical alert is raised when the total sponsor balance drops below this
+  #[envconfig(from = "BALANCE_CRITICAL_THRESHOLD")]
+  pub balance_critical_threshold: Option<u64>,
+  // A warning alert is raised when the projected runway, in seconds, drops below this
+  #[envconfig(from = "RUNWAY_WARNING_THRESHOLD")]
+  pub runway_warning_threshold: Option<u64>,
+  // A critical alert is raised when the projected runway, in seconds, drops below this
+  #[envconfig(from = "RUNWAY_CRITICAL_THRESHOLD")]
+  pub runway_critical_threshold: Option<u64>,
+  // Seconds of balance history the spend rate is computed over
+  #[envconfig(from = "SPEND_RATE_WINDOW")]
+  pub spend_rate_window: Option<u64>,
+  #[envconfig(from = "ALERT_WEBHOOK_URL")]
+  pub alert_webhook_url: Option<String>,
+  // The service the sponsor is funded through. Auto-funding is disabled if not set
+  #[envconfig(from = "TREASURY_FUNDING_URL")]
+  pub funding_url: Option<String>,
+  #[envconfig(from = "TREASURY_FUNDING_AUTH_TOKEN")]
+  pub funding_auth_token: Option<String>,
+  #[envconfig(from = "AUTO_FUND_THRESHOLD")]
+  pub auto_fund_threshold: Option<u64>,
+  #[envconfig(from = "AUTO_FUND_AMOUNT")]
+  pub auto_fund_amount: Option<u64>,
+  // Min number of seconds between two fundings
+  #[envconfig(from = "AUTO_FUND_COOLDOWN")]
+  pub auto_fund_cooldown: Option<u64>,
+  // Funds above WORKING_BALANCE are swept to this address. Sweeping is disabled if not set
+  #[envconfig(from = "COLD_TREASURY_ADDRESS")]
+  pub cold_address: Option<SuiAddress>,
+  #[envconfig(from = "WORKING_BALANCE")]
+  pub working_balance: Option<u64>,
+  // Seconds between two sweeps
+  #[envconfig(from = "SWEEP_INTERVAL")]
+  pub sweep_interval: Option<u64>,
+}
+
+#[derive(Envconfig)]
+pub struct LedgerConfig {
+  // Where the executed sponsored transactions are recorded: jsonl or sqlite. Nothing is recorded if not set
+  #[envconfig(from = "LEDGER_BACKEND")]
+  pub backend: Option<LedgerBackend>,
+  // The JSONL file or the SQLite database
+  #[envconfig(from = "LEDGER_PATH")]
+  pub path: Option<String>,
 }
 
 #[derive(Envconfig)]
@@ -47,14 +133,46 @@ pub struct RabbitMQConfig {
   pub uri: String,
   #[envconfig(from = "RETRY_TTL")]
   pub retry_ttl: u32,
+  // Url of the RabbitMQ management API e.g. http://localhost:15672. The coin manager uses it to list the queued coins
+  #[envconfig(from = "RABBITMQ_MANAGEMENT_URI")]
+  pub management_uri: Option<String>,
 }
 
 #[derive(Envconfig)]
 pub struct SuiConfig {
   #[envconfig(from = "SUI_RPC")]
   pub rpc: String,
+  #[envconfig(from = "SIGNER")]
+  pub signer: Option<SignerKind>,
+  // The local signer loads the keys from the keystore files if set; otherwise from SPONSOR_PRIV_KEY
+  #[envconfig(from = "SPONSOR_KEYSTORE")]
+  pub sponsor_keystores: Option<List<String>>,
+  // File holding the passphrase of the encrypted keystores. If not set the passphrase is prompted for
+  #[envconfig(from = "SPONSOR_KEYSTORE_PASSPHRASE_FILE")]
+  pub sponsor_keystore_passphrase_file: Option<String>,
   #[envconfig(from = "SPONSOR_PRIV_KEY")]
-  pub sponsor_keypair: KeyPair,
+  pub sponsor_keypairs: Option<List<KeyPair>>,
+  // Required by the remote signer
+  #[envconfig(from = "REMOTE_SIGNER_URL")]
+  pub remote_signer_url: Option<String>,
+  #[envconfig(from = "REMOTE_SIGNER_AUTH_TOKEN")]
+  pub remote_signer_auth_token: Option<String>,
+  // The sponsor addresses i.e. the addresses of the keys held by the remote signer. With keystores that hold
+  // more than one key, it selects the ones to use
+  #[envconfig(from = "SPONSOR_ADDRESS")]
+  pub sponsor_addresses: Option<List<SuiAddress>>,
+  // How the sponsor account, and thus gas pool shard, is picked for each gas request when there are many
+  #[envconfig(from = "SHARD_STRATEGY")]
+  pub shard_strategy: Option<ShardStrategy>,
+  // Member keys of a multisig sponsor. If set, the sponsor is the multisig address and the keys above are its
+  // members
+  #[envconfig(from = "MULTISIG_PUBLIC_KEYS")]
+  pub multisig_public_keys: Option<List<PublicKey>>,
+  // One weight per member key. Each member weighs 1 if not set
+  #[envconfig(from = "MULTISIG_WEIGHTS")]
+  pub multisig_weights: Option<List<u8>>,
+  #[envconfig(from = "MULTISIG_THRESHOLD")]
+  pub multisig_threshold: Option<u16>,
 }
 
 #[derive(Envconfig)]
@@ -67,6 +185,8 @@ pub struct RedisConfig {
   pub password: String,
 }
 
+/// A keypair held in memory. Its clones share the same keypair, whose private key zeroizes itself once the last
+/// of them is dropped.
 pub struct KeyPair(Arc<SuiKeyPair>);
 
 impl FromStr for KeyPair {
@@ -76,7 +196,13 @@ impl FromStr for KeyPair {
     let keypair = SuiKeyPair::from_str(s)
     .map_err(|e| Report::msg(e.to_string()))?;
 
-    Ok(Self(Arc::new(keypair)))
+    Ok(keypair.into())
+  }
+}
+
+impl From<SuiKeyPair> for KeyPair {
+  fn from(keypair: SuiKeyPair) -> Self {
+    Self(Arc::new(keypair))
   }
 }
 
@@ -94,6 +220,59 @@ impl Clone for KeyPair {
   }
 }
 
+/// A public key in the Sui CLI format i.e. base64 encoded `flag || public key` bytes
+pub struct PublicKey(pub crypto::PublicKey);
+
+impl FromStr for PublicKey {
+  type Err = Report;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let public_key = crypto::PublicKey::decode_base64(s)
+    .map_err(|e| Report::msg(e.to_string()))?;
+
+    Ok(Self(public_key))
+  }
+}
+
+/// Where the sponsor key lives
+pub enum SignerKind {
+  // In the process memory, loaded from `SPONSOR_KEYSTORE` or `SPONSOR_PRIV_KEY`
+  Local,
+  // In an external signing service
+  Remote,
+}
+
+impl FromStr for SignerKind {
+  type Err = Report;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "local" => Ok(Self::Local),
+      "remote" => Ok(Self::Remote),
+      _ => Err(Report::msg(format!("unknown signer {s}"))),
+    }
+  }
+}
+
+/// A comma separated list of values
+pub struct List<T>(pub Vec<T>);
+
+impl<T> FromStr for List<T>
+where
+  T: FromStr,
+  T::Err: std::fmt::Display,
+{
+  type Err = Report;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let values = s.split(",")
+    .map(|val| val.trim().parse::<T>().map_err(|e| Report::msg(e.to_string())))
+    .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Self(values))
+  }
+}
+
 pub struct CorsConfig {
   pub origin: Vec<String>,
 }
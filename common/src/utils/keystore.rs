@@ -0,0 +1,192 @@
+use std::{fs, path::Path};
+use eyre::{Result, eyre, ensure};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+use scrypt::{scrypt, Params};
+use chacha20poly1305::{
+  ChaCha20Poly1305, Key, Nonce,
+  aead::{Aead, AeadCore, KeyInit, OsRng, rand_core::RngCore},
+};
+use sui_types::{crypto::{SuiKeyPair, EncodeDecodeBase64}, base_types::SuiAddress};
+use crate::map_err;
+use super::config::{KeyPair, SuiConfig};
+
+const VERSION: u8 = 1;
+// scrypt cost parameters used for new keystores. Decryption uses the ones stored in the file
+const SCRYPT_LOG_N: u8 = 15;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_P: u32 = 1;
+const SALT_LEN: usize = 32;
+const KEY_LEN: usize = 32;
+
+#[derive(Serialize, Deserialize)]
+struct KdfParams {
+  log_n: u8,
+  r: u32,
+  p: u32,
+  // base64
+  salt: String,
+}
+
+/// A single private key, encrypted with a key derived from a passphrase using scrypt. The plaintext is the key
+/// in the Sui CLI keystore format i.e. base64 encoded `flag || private key` bytes.
+#[derive(Serialize, Deserialize)]
+pub struct EncryptedKeystore {
+  version: u8,
+  kdf: KdfParams,
+  // base64 ChaCha20-Poly1305 nonce
+  nonce: String,
+  // base64
+  ciphertext: String,
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], log_n: u8, r: u32, p: u32) -> Result<Zeroizing<[u8; KEY_LEN]>> {
+  let params = map_err!(Params::new(log_n, r, p, KEY_LEN))?;
+  let mut key = Zeroizing::new([0u8; KEY_LEN]);
+  map_err!(scrypt(passphrase.as_bytes(), salt, &params, &mut *key))?;
+
+  Ok(key)
+}
+
+fn decode_keypair(encoded: &str) -> Result<SuiKeyPair> {
+  SuiKeyPair::decode_base64(encoded).map_err(|e| eyre!(e.to_string()))
+}
+
+impl EncryptedKeystore {
+  pub fn encrypt(keypair: &SuiKeyPair, passphrase: &str) -> Result<Self> {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+
+    let key = derive_key(passphrase, &salt, SCRYPT_LOG_N, SCRYPT_R, SCRYPT_P)?;
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(&*key));
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let plaintext = Zeroizing::new(keypair.encode_base64());
+    let ciphertext = cipher
+    .encrypt(&nonce, plaintext.as_bytes())
+    .map_err(|_| eyre!("failed to encrypt the keystore"))?;
+
+    Ok(Self {
+      version: VERSION,
+      kdf: KdfParams {
+        log_n: SCRYPT_LOG_N,
+        r: SCRYPT_R,
+        p: SCRYPT_P,
+        salt: base64::encode(salt),
+      },
+      nonce: base64::encode(nonce),
+      ciphertext: base64::encode(ciphertext),
+    })
+  }
+
+  pub fn decrypt(&self, passphrase: &str) -> Result<SuiKeyPair> {
+    ensure!(self.version == VERSION, "unsupported keystore version {}", self.version);
+
+    let salt = map_err!(base64::decode(&self.kdf.salt))?;
+    let nonce = map_err!(base64::decode(&self.nonce))?;
+    let ciphertext = map_err!(base64::decode(&self.ciphertext))?;
+    ensure!(nonce.len() == 12, "invalid keystore nonce");
+
+    let key = derive_key(passphrase, &salt, self.kdf.log_n, self.kdf.r, self.kdf.p)?;
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(&*key));
+    let plaintext = Zeroizing::new(
+      cipher
+      .decrypt(Nonce::from_slice(&nonce), ciphertext.as_ref())
+      .map_err(|_| eyre!("wrong passphrase or corrupted keystore"))?
+    );
+    let encoded = Zeroizing::new(map_err!(String::from_utf8(plaintext.to_vec()))?);
+
+    decode_keypair(&encoded)
+  }
+}
+
+/// Loads a key from a Sui CLI keystore file i.e. a JSON array of base64 encoded keys. If the file holds more than one
+/// key, the address of the one to load must be given.
+pub fn load_cli_keystore(keys: &[String], address: Option<SuiAddress>) -> Result<SuiKeyPair> {
+  let mut keypairs = keys.iter()
+  .map(|key| decode_keypair(key))
+  .collect::<Result<Vec<_>>>()?;
+
+  match address {
+    Some(address) => {
+      let pos = keypairs.iter()
+      .position(|kp| SuiAddress::from(&kp.public()) == address)
+      .ok_or_else(|| eyre!("address {} not found in the keystore", address))?;
+
+      Ok(keypairs.swap_remove(pos))
+    },
+    None => {
+      ensure!(keypairs.len() == 1, "the keystore holds {} keys; SPONSOR_ADDRESS must be set", keypairs.len());
+      Ok(keypairs.remove(0))
+    },
+  }
+}
+
+/// Reads the passphrase from the given file or, if there is none, prompts for it
+fn read_passphrase(passphrase_file: Option<&str>) -> Result<Zeroizing<String>> {
+  let passphrase = match passphrase_file {
+    Some(path) => Zeroizing::new(fs::read_to_string(path)?),
+    None => Zeroizing::new(rpassword::prompt_password("Keystore passphrase: ")?),
+  };
+
+  Ok(Zeroizing::new(passphrase.trim_end_matches(|c| c == '\r' || c == '\n').to_string()))
+}
+
+/// Loads the keys from either a Sui CLI keystore or an encrypted keystore file. If addresses are given, only
+/// the keys of those addresses are loaded.
+pub fn load_keystore(
+  path: impl AsRef<Path>,
+  addresses: &[SuiAddress],
+  passphrase_file: Option<&str>,
+) -> Result<Vec<KeyPair>> {
+  let content = Zeroizing::new(fs::read_to_string(path)?);
+  let is_selected = |kp: &SuiKeyPair| addresses.is_empty() || addresses.contains(&SuiAddress::from(&kp.public()));
+
+  let keypairs = if let Ok(keys) = serde_json::from_str::<Vec<String>>(&content) {
+    let keys = Zeroizing::new(keys);
+
+    if addresses.is_empty() {
+      vec![load_cli_keystore(&keys, None)?]
+    } else {
+      keys.iter()
+      .map(|key| decode_keypair(key))
+      .collect::<Result<Vec<_>>>()?
+      .into_iter()
+      .filter(is_selected)
+      .collect()
+    }
+  } else {
+    let keystore: EncryptedKeystore = serde_json::from_str(&content)?;
+    let passphrase = read_passphrase(passphrase_file)?;
+    let keypair = keystore.decrypt(&passphrase)?;
+    ensure!(is_selected(&keypair), "keystore key {} is not in SPONSOR_ADDRESS", SuiAddress::from(&keypair.public()));
+
+    vec![keypair]
+  };
+
+  Ok(keypairs.into_iter().map(Into::into).collect())
+}
+
+/// Returns the sponsor keys from `SPONSOR_KEYSTORE` if set; otherwise from `SPONSOR_PRIV_KEY`
+pub fn sponsor_keypairs(config: &SuiConfig) -> Result<Vec<KeyPair>> {
+  let Some(paths) = &config.sponsor_keystores else {
+    return config.sponsor_keypairs.as_ref()
+    .map(|keypairs| keypairs.0.clone())
+    .ok_or_else(|| eyre!("either SPONSOR_KEYSTORE or SPONSOR_PRIV_KEY must be set"))
+  };
+
+  let addresses = config.sponsor_addresses.as_ref().map_or(vec![], |a| a.0.clone());
+  let mut keypairs = vec![];
+
+  for path in &paths.0 {
+    keypairs.extend(load_keystore(path, &addresses, config.sponsor_keystore_passphrase_file.as_deref())?);
+  }
+
+  for address in &addresses {
+    ensure!(
+      keypairs.iter().any(|kp| SuiAddress::from(&kp.public()) == *address),
+      "the key of {} was not found in SPONSOR_KEYSTORE", address,
+    );
+  }
+
+  Ok(keypairs)
+}
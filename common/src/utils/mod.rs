@@ -1,3 +1,4 @@
 pub mod config;
 pub mod store;
 pub mod error;
+pub mod keystore;
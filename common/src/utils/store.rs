@@ -2,21 +2,27 @@ use std::sync::Arc;
 use envconfig::Envconfig;
 use sui_sdk::{SuiClientBuilder, SuiClient};
 use crate::{
-  services::{sponsor::Sponsor, gas_meter::GasMeter, wallet::Wallet},
-  gas_pool::{GasPool, coin_object_producer::CoinObjectProducer},
-  storage::{redis::ConnectionPool, redlock::RedLock}, helpers::tx::TxManager
+  services::{
+    sponsor::Sponsor, gas_meter::GasMeter, wallet::sponsor_wallets,
+    shard::{Shard, ShardSelector, ShardStrategy}, policy::Policy,
+  },
+  gas_pool::{GasPool, PoolKeys, coin_object_producer::CoinObjectProducer},
+  storage::{redis::ConnectionPool, redlock::RedLock}, helpers::tx::TxManager,
+  ledger::{self, Ledger},
 };
 use super::config::{Config};
 pub struct Store {
   pub config: Config,
   pub rpc_client: Arc<SuiClient>,
-  pub wallet: Arc<Wallet>,
   pub gas_meter: Arc<GasMeter>,
   pub tx_manager: Arc<TxManager>,
   pub sponsor: Sponsor,
+  pub policy: Arc<Policy>,
   pub redis_pool: Arc<ConnectionPool>,
   pub redlock: Arc<RedLock>,
-  pub coin_object_producer: Arc<CoinObjectProducer>,
+  // One shard per sponsor account
+  pub shards: Arc<Vec<Shard>>,
+  pub ledger: Option<Arc<dyn Ledger>>,
 }
 
 impl Store {
@@ -30,31 +36,57 @@ impl Store {
     );
 
     let redis_pool = Arc::new(ConnectionPool::new(&config.redis.host, &config.redis.password, config.redis.port));
-    let redlock = Arc::new(RedLock::new(vec![&config.redis.host], &config.redis.password));
+    let redlock = Arc::new(RedLock::new(vec![&config.redis.host], &config.redis.password, config.redis.port));
 
-    let coin_object_producer = Arc::new(
-      CoinObjectProducer::try_new(
-        config.rabbitmq.uri.clone(),
-        config.rabbitmq.retry_ttl
-      ).await.expect("create coin object producer")
-    );
+    let wallets = sponsor_wallets(&config.sui).unwrap();
+    assert!(!wallets.is_empty(), "at least one sponsor key is required");
+    let unsharded_sponsor = PoolKeys::claim_unsharded_sponsor(&redis_pool, wallets[0].address()).await.unwrap();
+    let mut shards = vec![];
 
-    let wallet = Arc::new(Wallet::new(config.sui.sponsor_keypair.clone()));
-    let gas_pool: Arc<&'static GasPool> = Arc::new(Box::leak(Box::new(GasPool::try_new(
-      Arc::clone(&rpc_client),
-      Arc::clone(&redis_pool),
-      &config.rabbitmq.uri,
-    ).await)));
-    
-    GasPool::spawn_clean_queue(Arc::clone(&gas_pool));
+    for wallet in wallets {
+      let wallet = Arc::new(wallet);
+      let keys = PoolKeys::new(wallet.address(), unsharded_sponsor);
+
+      let coin_object_producer = Arc::new(
+        CoinObjectProducer::try_new(
+          config.rabbitmq.uri.clone(),
+          keys.queue.clone(),
+          config.rabbitmq.retry_ttl
+        ).await.expect("create coin object producer")
+      );
 
-    let gas_meter = Arc::new(GasMeter::new(Arc::clone(&rpc_client)));
+      let gas_pool: Arc<&'static GasPool> = Arc::new(Box::leak(Box::new(GasPool::try_new(
+        Arc::clone(&rpc_client),
+        Arc::clone(&redis_pool),
+        keys.clone(),
+        &config.rabbitmq.uri,
+      ).await)));
+
+      GasPool::spawn_clean_queue(Arc::clone(&gas_pool));
+
+      shards.push(Shard {
+        wallet,
+        keys,
+        gas_pool,
+        coin_object_producer,
+      });
+    }
+
+    let shards = Arc::new(shards);
+    let policy = Arc::new(
+      config.sponsor.policy_file.as_ref()
+      .map(|path| Policy::load(path).expect("load the sponsor policy"))
+      .unwrap_or_default()
+    );
+    let gas_meter = Arc::new(GasMeter::new(Arc::clone(&rpc_client), Arc::clone(&redis_pool), policy.congestion));
 
     let sponsor = Sponsor::new(
       Arc::clone(&rpc_client),
-      Arc::clone(&wallet),
+      Arc::clone(&shards),
+      ShardSelector::new(config.sui.shard_strategy.unwrap_or(ShardStrategy::RoundRobin)),
       Arc::clone(&gas_meter),
-      gas_pool,
+      Arc::clone(&redis_pool),
+      Arc::clone(&policy),
       config.gas_pool.min_coin_balance.unwrap(),
       config.sponsor.max_gas_budget.unwrap(),
     );
@@ -64,13 +96,25 @@ impl Store {
     Self {
       config,
       rpc_client: rpc_client,
-      wallet,
       gas_meter,
       tx_manager,
       sponsor,
+      policy,
       redis_pool,
       redlock,
-      coin_object_producer,
+      shards,
+      ledger: None,
     }
   }
+
+  /// Opens the ledger if `LEDGER_BACKEND` is set. Only the api records transactions, so it's the only one that opens
+  /// it. The ledger backends are local files, which must have a single writer i.e. a single api instance.
+  pub fn with_ledger(mut self) -> Self {
+    self.ledger = self.config.ledger.backend.map(|backend| {
+      let path = self.config.ledger.path.as_ref().expect("LEDGER_PATH must be set when LEDGER_BACKEND is");
+      ledger::open(backend, path).expect("open the ledger")
+    });
+
+    self
+  }
 }
@@ -0,0 +1,116 @@
+mod support;
+
+use sui_types::base_types::SuiAddress;
+use sui_sponsor_common::ledger::{LedgerEntry, billing::{Period, aggregate, csv_field, mist_to_sui, to_csv}};
+use support::ledger_entry;
+
+// 2023-11-14T22:13:20Z
+const TIMESTAMP: u64 = 1_700_000_000_000;
+const DAY_MILLIS: u64 = 24 * 60 * 60 * 1000;
+
+fn entry(tenant: Option<&str>, timestamp: u64) -> LedgerEntry {
+  ledger_entry(SuiAddress::ZERO, tenant, timestamp)
+}
+
+#[test]
+fn finds_the_utc_day_and_month_of_a_timestamp() {
+  assert_eq!(Period::Day.of(TIMESTAMP), "2023-11-14");
+  assert_eq!(Period::Month.of(TIMESTAMP), "2023-11");
+  // Midnight belongs to the day that starts
+  assert_eq!(Period::Day.of(1_700_006_400_000), "2023-11-15");
+  assert_eq!(Period::Month.of(1_701_388_800_000), "2023-12");
+}
+
+#[test]
+fn converts_mist_to_an_exact_number_of_sui() {
+  assert_eq!(mist_to_sui(1_500_000_000), "1.500000000");
+  assert_eq!(mist_to_sui(0), "0.000000000");
+  assert_eq!(mist_to_sui(i64::MAX), "9223372036.854775807");
+}
+
+#[test]
+fn converts_less_than_a_sui() {
+  assert_eq!(mist_to_sui(1), "0.000000001");
+  assert_eq!(mist_to_sui(999_999_999), "0.999999999");
+}
+
+#[test]
+fn converts_negative_mist() {
+  assert_eq!(mist_to_sui(-1_500_000_000), "-1.500000000");
+  assert_eq!(mist_to_sui(-1), "-0.000000001");
+  assert_eq!(mist_to_sui(i64::MIN), "-9223372036.854775808");
+}
+
+#[test]
+fn leaves_plain_csv_fields_as_they_are() {
+  assert_eq!(csv_field("acme"), "acme");
+  assert_eq!(csv_field(""), "");
+}
+
+#[test]
+fn quotes_csv_fields_with_separators_or_line_breaks() {
+  assert_eq!(csv_field("acme, inc"), "\"acme, inc\"");
+  assert_eq!(csv_field("acme\ninc"), "\"acme\ninc\"");
+  assert_eq!(csv_field("acme\r\ninc"), "\"acme\r\ninc\"");
+}
+
+#[test]
+fn escapes_quotes_in_csv_fields() {
+  assert_eq!(csv_field("the \"acme\" tenant"), "\"the \"\"acme\"\" tenant\"");
+}
+
+#[test]
+fn aggregates_each_tenant_per_period_in_tenant_then_period_order() {
+  let entries = vec![
+    entry(Some("globex"), TIMESTAMP),
+    entry(Some("acme"), TIMESTAMP + DAY_MILLIS),
+    entry(Some("acme"), TIMESTAMP),
+    entry(None, TIMESTAMP),
+    entry(Some("acme"), TIMESTAMP),
+  ];
+
+  let lines = aggregate(entries, Period::Day);
+  let keys = lines.iter().map(|l| (l.tenant.as_deref(), l.period.as_str())).collect::<Vec<_>>();
+
+  assert_eq!(keys, vec![
+    (None, "2023-11-14"),
+    (Some("acme"), "2023-11-14"),
+    (Some("acme"), "2023-11-15"),
+    (Some("globex"), "2023-11-14"),
+  ]);
+  assert_eq!(lines[1].tx_count, 2);
+  assert_eq!(lines[1].net_gas_cost_mist, 5_000_000);
+  assert_eq!(lines[1].net_gas_cost_sui, "0.005000000");
+}
+
+#[test]
+fn aggregates_a_month_of_entries_into_one_line() {
+  let entries = vec![entry(Some("acme"), TIMESTAMP), entry(Some("acme"), TIMESTAMP + DAY_MILLIS)];
+
+  let lines = aggregate(entries, Period::Month);
+
+  assert_eq!(lines.len(), 1);
+  assert_eq!(lines[0].period, "2023-11");
+  assert_eq!(lines[0].tx_count, 2);
+}
+
+#[test]
+fn bills_a_negative_net_gas_cost() {
+  let entry = LedgerEntry {storage_rebate: 4_000_000, ..entry(Some("acme"), TIMESTAMP)};
+
+  let lines = aggregate(vec![entry], Period::Day);
+
+  assert_eq!(lines[0].net_gas_cost_mist, -1_000_000);
+  assert_eq!(lines[0].net_gas_cost_sui, "-0.001000000");
+}
+
+#[test]
+fn renders_the_report_as_csv() {
+  let lines = aggregate(vec![entry(Some("acme, inc"), TIMESTAMP), entry(None, TIMESTAMP)], Period::Day);
+
+  assert_eq!(to_csv(&lines), "\
+tenant,period,tx_count,computation_cost,storage_cost,storage_rebate,net_gas_cost_mist,net_gas_cost_sui
+,2023-11-14,1,1000000,2000000,500000,2500000,0.002500000
+\"acme, inc\",2023-11-14,1,1000000,2000000,500000,2500000,0.002500000
+");
+}
@@ -0,0 +1,91 @@
+use sui_types::gas::GasCostSummary;
+use sui_sponsor_common::services::sponsor::Estimate;
+
+const GRANTED_BUDGET: u64 = 10_000_000;
+
+fn gas_used(computation_cost: u64, storage_cost: u64, storage_rebate: u64) -> GasCostSummary {
+  GasCostSummary::new(computation_cost, storage_cost, storage_rebate, 0)
+}
+
+#[test]
+fn needs_a_budget_for_the_computation_and_storage_cost() {
+  let estimate = Estimate::new(1_000, &gas_used(1_000_000, 2_964_000, 978_120), None, true, GRANTED_BUDGET);
+
+  assert_eq!(estimate.gas_price, 1_000);
+  assert_eq!(estimate.gas_budget, 3_964_000);
+  assert_eq!(estimate.net_gas_cost, 2_985_880);
+  assert!(estimate.sponsored);
+}
+
+#[test]
+fn costs_the_sponsor_less_than_nothing_when_the_rebate_exceeds_the_cost() {
+  // e.g. a transaction that deletes objects
+  let estimate = Estimate::new(1_000, &gas_used(1_000_000, 988_000, 5_000_000), None, true, GRANTED_BUDGET);
+
+  assert_eq!(estimate.gas_budget, 1_988_000);
+  assert_eq!(estimate.net_gas_cost, -3_012_000);
+  assert!(estimate.sponsored);
+}
+
+#[test]
+fn clamps_the_net_gas_cost_to_the_i64_range() {
+  let estimate = Estimate::new(1_000, &gas_used(u64::MAX, u64::MAX, 0), None, true, GRANTED_BUDGET);
+  assert_eq!(estimate.gas_budget, u64::MAX);
+  assert_eq!(estimate.net_gas_cost, i64::MAX);
+
+  let estimate = Estimate::new(1_000, &gas_used(0, 0, u64::MAX), None, true, GRANTED_BUDGET);
+  assert_eq!(estimate.net_gas_cost, i64::MIN);
+}
+
+#[test]
+fn sponsors_a_budget_equal_to_the_granted_one() {
+  let estimate = Estimate::new(1_000, &gas_used(GRANTED_BUDGET / 2, GRANTED_BUDGET / 2, 0), None, true, GRANTED_BUDGET);
+
+  assert!(estimate.sponsored);
+}
+
+#[test]
+fn does_not_sponsor_a_budget_above_the_granted_one() {
+  // The storage rebate doesn't count; the sponsor must be able to pay the cost before it gets the rebate
+  let estimate = Estimate::new(
+    1_000,
+    &gas_used(GRANTED_BUDGET / 2, GRANTED_BUDGET / 2 + 1, GRANTED_BUDGET),
+    None,
+    true,
+    GRANTED_BUDGET,
+  );
+
+  assert!(!estimate.sponsored);
+}
+
+#[test]
+fn does_not_sponsor_unsupported_transactions() {
+  let estimate = Estimate::new(1_000, &gas_used(1_000_000, 2_964_000, 978_120), None, false, GRANTED_BUDGET);
+
+  assert!(!estimate.sponsored);
+}
+
+#[test]
+fn does_not_sponsor_transactions_whose_dry_run_failed() {
+  let error = Some("InsufficientCoinBalance in command 0".to_string());
+  let estimate = Estimate::new(1_000, &gas_used(1_000_000, 988_000, 978_120), error.clone(), true, GRANTED_BUDGET);
+
+  assert!(!estimate.sponsored);
+  assert_eq!(estimate.error, error);
+}
+
+#[test]
+fn serializes_in_camel_case() {
+  let estimate = Estimate::new(1_000, &gas_used(1_000_000, 2_964_000, 978_120), None, true, GRANTED_BUDGET);
+
+  assert_eq!(serde_json::to_value(&estimate).unwrap(), serde_json::json!({
+    "gasPrice": 1_000,
+    "computationCost": 1_000_000,
+    "storageCost": 2_964_000,
+    "storageRebate": 978_120,
+    "gasBudget": 3_964_000,
+    "netGasCost": 2_985_880,
+    "sponsored": true,
+    "error": null,
+  }));
+}
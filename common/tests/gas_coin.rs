@@ -0,0 +1,164 @@
+mod support;
+
+use sui_sponsor_common::services::ptb::{PtbRules, is_ptb_allowed, uses_gas_coin};
+use sui_types::{
+  base_types::SuiAddress,
+  programmable_transaction_builder::ProgrammableTransactionBuilder,
+  transaction::{Argument, Command, ProgrammableTransaction},
+  Identifier, parse_sui_type_tag,
+};
+use support::{COIN_TYPE, coin, package, rules};
+
+fn sender() -> SuiAddress {
+  SuiAddress::random_for_testing_only()
+}
+
+/// Rules that allow every command of the given sender, so that only the gas coin check can reject a transaction
+fn permissive_rules(sender: SuiAddress) -> PtbRules {
+  rules(serde_json::json!({
+    "makeMoveVecTypes": [COIN_TYPE],
+    "allowPublish": true,
+    "upgradePackages": [package()],
+    "upgradeCapOwners": [sender],
+  }))
+}
+
+fn move_call(arguments: Vec<Argument>) -> Command {
+  Command::move_call(
+    package(),
+    Identifier::new("game").unwrap(),
+    Identifier::new("play").unwrap(),
+    vec![],
+    arguments,
+  )
+}
+
+fn is_allowed(ptx: &ProgrammableTransaction) -> bool {
+  is_ptb_allowed(ptx, sender(), &PtbRules::default())
+}
+
+#[test]
+fn allows_transactions_that_only_use_the_sender_coins() {
+  let sender = sender();
+  let mut builder = ProgrammableTransactionBuilder::new();
+  let coin = coin(&mut builder);
+  let amount = builder.pure(1_000u64).unwrap();
+  let split = builder.command(Command::SplitCoins(coin, vec![amount]));
+  let recipient = builder.pure(sender).unwrap();
+  builder.command(Command::TransferObjects(vec![split], recipient));
+  builder.command(move_call(vec![coin]));
+  let ptx = builder.finish();
+
+  assert!(!uses_gas_coin(&ptx));
+  assert!(is_ptb_allowed(&ptx, sender, &PtbRules::default()));
+}
+
+#[test]
+fn rejects_splitting_the_gas_coin() {
+  let mut builder = ProgrammableTransactionBuilder::new();
+  let amount = builder.pure(1_000_000_000u64).unwrap();
+  let split = builder.command(Command::SplitCoins(Argument::GasCoin, vec![amount]));
+  let recipient = builder.pure(sender()).unwrap();
+  builder.command(Command::TransferObjects(vec![split], recipient));
+
+  assert!(!is_allowed(&builder.finish()));
+}
+
+#[test]
+fn rejects_transferring_the_gas_coin() {
+  let mut builder = ProgrammableTransactionBuilder::new();
+  let recipient = builder.pure(sender()).unwrap();
+  builder.command(Command::TransferObjects(vec![Argument::GasCoin], recipient));
+
+  assert!(!is_allowed(&builder.finish()));
+}
+
+#[test]
+fn rejects_transferring_the_gas_coin_among_other_objects() {
+  let mut builder = ProgrammableTransactionBuilder::new();
+  let coin = coin(&mut builder);
+  let recipient = builder.pure(sender()).unwrap();
+  builder.command(Command::TransferObjects(vec![coin, Argument::GasCoin], recipient));
+
+  assert!(!is_allowed(&builder.finish()));
+}
+
+#[test]
+fn rejects_merging_into_the_gas_coin() {
+  let mut builder = ProgrammableTransactionBuilder::new();
+  let coin = coin(&mut builder);
+  builder.command(Command::MergeCoins(Argument::GasCoin, vec![coin]));
+
+  assert!(!is_allowed(&builder.finish()));
+}
+
+#[test]
+fn rejects_merging_the_gas_coin_into_another_coin() {
+  let mut builder = ProgrammableTransactionBuilder::new();
+  let coin = coin(&mut builder);
+  builder.command(Command::MergeCoins(coin, vec![Argument::GasCoin]));
+  let recipient = builder.pure(sender()).unwrap();
+  builder.command(Command::TransferObjects(vec![coin], recipient));
+
+  assert!(!is_allowed(&builder.finish()));
+}
+
+#[test]
+fn rejects_passing_the_gas_coin_to_a_move_call() {
+  let mut builder = ProgrammableTransactionBuilder::new();
+  let coin = coin(&mut builder);
+  builder.command(move_call(vec![coin, Argument::GasCoin]));
+
+  assert!(!is_allowed(&builder.finish()));
+}
+
+fn coin_vector(with_gas_coin: bool) -> ProgrammableTransaction {
+  let mut builder = ProgrammableTransactionBuilder::new();
+  let mut elements = vec![coin(&mut builder)];
+  if with_gas_coin {
+    elements.push(Argument::GasCoin);
+  }
+  let coins = builder.command(Command::MakeMoveVec(Some(parse_sui_type_tag(COIN_TYPE).unwrap()), elements));
+  builder.command(move_call(vec![coins]));
+
+  builder.finish()
+}
+
+#[test]
+fn rejects_wrapping_the_gas_coin_in_a_vector() {
+  let sender = sender();
+
+  // The vector type is allowed, so the gas coin is the only reason to reject it
+  assert!(is_ptb_allowed(&coin_vector(false), sender, &permissive_rules(sender)));
+  assert!(uses_gas_coin(&coin_vector(true)));
+  assert!(!is_ptb_allowed(&coin_vector(true), sender, &permissive_rules(sender)));
+}
+
+fn upgrade(ticket: impl FnOnce(&mut ProgrammableTransactionBuilder) -> Argument) -> ProgrammableTransaction {
+  let mut builder = ProgrammableTransactionBuilder::new();
+  let ticket = ticket(&mut builder);
+  builder.command(Command::Upgrade(vec![], vec![], package(), ticket));
+
+  builder.finish()
+}
+
+#[test]
+fn rejects_the_gas_coin_as_an_upgrade_ticket() {
+  let sender = sender();
+
+  // Upgrades of the package are allowed, so the gas coin is the only reason to reject it
+  assert!(is_ptb_allowed(&upgrade(|builder| builder.pure(0u8).unwrap()), sender, &permissive_rules(sender)));
+  assert!(!is_ptb_allowed(&upgrade(|_| Argument::GasCoin), sender, &permissive_rules(sender)));
+}
+
+#[test]
+fn rejects_the_gas_coin_even_when_the_rules_allow_everything_else() {
+  let mut builder = ProgrammableTransactionBuilder::new();
+  let amount = builder.pure(1u64).unwrap();
+  builder.command(Command::SplitCoins(Argument::GasCoin, vec![amount]));
+  let ptx = builder.finish();
+
+  let sender = sender();
+
+  assert!(!is_ptb_allowed(&ptx, sender, &permissive_rules(sender)));
+}
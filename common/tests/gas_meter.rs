@@ -0,0 +1,113 @@
+use sui_sdk::rpc_types::SuiExecutionStatus;
+use sui_sponsor_common::services::gas_meter::{CachedGasPrice, GasMeter, GasPricePolicy, EPOCH_CHANGE_POLL_MILLIS};
+
+const EPOCH_END: u64 = 1_700_000_000_000;
+
+#[test]
+fn is_fresh_until_the_epoch_ends() {
+  let gas_price = CachedGasPrice::new(10, 750, EPOCH_END, EPOCH_END - 60_000);
+
+  assert_eq!(gas_price.expires_at, EPOCH_END);
+  assert!(gas_price.is_fresh_at(EPOCH_END - 60_000));
+  assert!(gas_price.is_fresh_at(EPOCH_END - 1));
+}
+
+#[test]
+fn expires_once_the_epoch_ends() {
+  let gas_price = CachedGasPrice::new(10, 750, EPOCH_END, EPOCH_END - 60_000);
+
+  assert!(!gas_price.is_fresh_at(EPOCH_END));
+  assert!(!gas_price.is_fresh_at(EPOCH_END + 1));
+}
+
+#[test]
+fn polls_for_the_next_epoch_once_the_current_one_is_overdue() {
+  let now = EPOCH_END + 5_000;
+  let gas_price = CachedGasPrice::new(10, 750, EPOCH_END, now);
+
+  assert_eq!(gas_price.expires_at, now + EPOCH_CHANGE_POLL_MILLIS);
+  assert!(gas_price.is_fresh_at(now + EPOCH_CHANGE_POLL_MILLIS - 1));
+  assert!(!gas_price.is_fresh_at(now + EPOCH_CHANGE_POLL_MILLIS));
+}
+
+#[test]
+fn polls_for_the_next_epoch_shortly_before_the_current_one_ends() {
+  let now = EPOCH_END - 1_000;
+  let gas_price = CachedGasPrice::new(10, 750, EPOCH_END, now);
+
+  assert_eq!(gas_price.expires_at, now + EPOCH_CHANGE_POLL_MILLIS);
+}
+
+#[test]
+fn shares_the_gas_price_as_json() {
+  let gas_price = CachedGasPrice::new(10, 750, EPOCH_END, EPOCH_END - 60_000);
+  let json = serde_json::to_value(gas_price).unwrap();
+
+  assert_eq!(json, serde_json::json!({"epoch": 10, "referenceGasPrice": 750, "expiresAt": EPOCH_END}));
+
+  let shared: CachedGasPrice = serde_json::from_value(json).unwrap();
+  assert_eq!(shared.epoch, 10);
+  assert_eq!(shared.reference_gas_price, 750);
+  assert_eq!(shared.expires_at, EPOCH_END);
+}
+
+fn gas_price_policy(json: serde_json::Value) -> GasPricePolicy {
+  serde_json::from_value(json).unwrap()
+}
+
+#[test]
+fn uses_the_reference_gas_price_by_default() {
+  let policy = GasPricePolicy::default();
+
+  assert_eq!(policy.apply(750, false), 750);
+  assert_eq!(policy.apply(750, true), 750);
+}
+
+#[test]
+fn multiplies_the_reference_gas_price_and_adds_the_tip() {
+  let policy = gas_price_policy(serde_json::json!({"multiplier": 1.5, "tip": 10}));
+
+  assert_eq!(policy.apply(750, false), 1_135);
+}
+
+#[test]
+fn rounds_the_multiplied_price_up() {
+  let policy = gas_price_policy(serde_json::json!({"multiplier": 1.001}));
+
+  assert_eq!(policy.apply(750, false), 751);
+}
+
+#[test]
+fn applies_the_congestion_multiplier_only_while_congested() {
+  let policy = gas_price_policy(serde_json::json!({"multiplier": 1.5, "tip": 10, "congestionMultiplier": 2.0}));
+
+  assert_eq!(policy.apply(750, false), 1_135);
+  assert_eq!(policy.apply(750, true), 2_260);
+}
+
+#[test]
+fn caps_the_price_at_the_max() {
+  let policy = gas_price_policy(serde_json::json!({"multiplier": 2.0, "congestionMultiplier": 3.0, "max": 1_000}));
+
+  assert_eq!(policy.apply(400, false), 800);
+  assert_eq!(policy.apply(400, true), 1_000);
+}
+
+#[test]
+fn never_goes_below_the_reference_gas_price() {
+  let policy = gas_price_policy(serde_json::json!({"multiplier": 0.5, "max": 500}));
+
+  assert_eq!(policy.apply(750, false), 750);
+}
+
+#[test]
+fn reports_the_error_of_a_failed_execution() {
+  let status = SuiExecutionStatus::Failure {error: "InsufficientGas".to_string()};
+
+  assert_eq!(GasMeter::execution_error(&status), Some("InsufficientGas".to_string()));
+}
+
+#[test]
+fn reports_no_error_for_a_successful_execution() {
+  assert_eq!(GasMeter::execution_error(&SuiExecutionStatus::Success), None);
+}
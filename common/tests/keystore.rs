@@ -0,0 +1,54 @@
+mod support;
+
+use sui_types::{
+  base_types::SuiAddress,
+  crypto::EncodeDecodeBase64,
+};
+use sui_sponsor_common::utils::{config::KeyPair, keystore::EncryptedKeystore};
+
+#[test]
+fn decrypts_what_it_encrypted() {
+  let keypair = support::keypair();
+  let keystore = EncryptedKeystore::encrypt(&keypair, "correct horse").unwrap();
+  let json = serde_json::to_string(&keystore).unwrap();
+
+  let keystore: EncryptedKeystore = serde_json::from_str(&json).unwrap();
+  let decrypted = keystore.decrypt("correct horse").unwrap();
+
+  assert_eq!(decrypted.encode_base64(), keypair.encode_base64());
+  assert!(!json.contains(&keypair.encode_base64()));
+}
+
+#[test]
+fn rejects_a_wrong_passphrase() {
+  let keystore = EncryptedKeystore::encrypt(&support::keypair(), "correct horse").unwrap();
+
+  assert!(keystore.decrypt("battery staple").is_err());
+}
+
+#[test]
+fn rejects_a_tampered_ciphertext() {
+  let keystore = EncryptedKeystore::encrypt(&support::keypair(), "correct horse").unwrap();
+  let mut json: serde_json::Value = serde_json::to_value(&keystore).unwrap();
+  let mut ciphertext = base64::decode(json["ciphertext"].as_str().unwrap()).unwrap();
+  ciphertext[0] ^= 1;
+  json["ciphertext"] = base64::encode(ciphertext).into();
+
+  let keystore: EncryptedKeystore = serde_json::from_value(json).unwrap();
+
+  assert!(keystore.decrypt("correct horse").is_err());
+}
+
+#[test]
+fn keeps_the_keypair_until_the_last_clone_is_dropped() {
+  let keypair = support::keypair();
+  let address = SuiAddress::from(&keypair.public());
+  let encoded = keypair.encode_base64();
+
+  let first = KeyPair::from(keypair);
+  let second = first.clone();
+  drop(first);
+
+  assert_eq!(SuiAddress::from(&second.public()), address);
+  assert_eq!(second.encode_base64(), encoded);
+}
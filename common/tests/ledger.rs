@@ -0,0 +1,177 @@
+mod support;
+
+use std::{env, fs, path::PathBuf};
+use sui_types::base_types::SuiAddress;
+use sui_sponsor_common::ledger::{
+  LedgerBackend, LedgerEntry, GroupBy, Totals, TotalsQuery, aggregate, day, open,
+};
+use support::ledger_entry;
+
+// 2023-11-14T22:13:20Z
+const TIMESTAMP: u64 = 1_700_000_000_000;
+const DAY_MILLIS: u64 = 24 * 60 * 60 * 1000;
+
+fn query(group_by: GroupBy, from: Option<u64>, to: Option<u64>) -> TotalsQuery {
+  TotalsQuery {group_by, from, to}
+}
+
+/// A fresh path in the temp dir for a ledger file
+fn ledger_path(name: &str) -> PathBuf {
+  let path = env::temp_dir().join(format!("sui-sponsor-ledger-{}-{name}", std::process::id()));
+  let _ = fs::remove_file(&path);
+
+  path
+}
+
+fn keys(totals: &[Totals]) -> Vec<Option<&str>> {
+  totals.iter().map(|t| t.key.as_deref()).collect()
+}
+
+#[test]
+fn subtracts_the_storage_rebate_from_the_net_gas_cost() {
+  assert_eq!(ledger_entry(SuiAddress::ZERO, None, TIMESTAMP).net_gas_cost(), 2_500_000);
+}
+
+#[test]
+fn has_a_negative_net_gas_cost_when_the_rebate_exceeds_the_cost() {
+  let entry = LedgerEntry {storage_rebate: 5_000_000, ..ledger_entry(SuiAddress::ZERO, None, TIMESTAMP)};
+
+  assert_eq!(entry.net_gas_cost(), -2_000_000);
+}
+
+#[test]
+fn clamps_the_net_gas_cost_to_the_i64_range() {
+  let entry = ledger_entry(SuiAddress::ZERO, None, TIMESTAMP);
+
+  assert_eq!(LedgerEntry {computation_cost: u64::MAX, storage_rebate: 0, ..entry.clone()}.net_gas_cost(), i64::MAX);
+  assert_eq!(LedgerEntry {storage_rebate: u64::MAX, ..entry}.net_gas_cost(), i64::MIN);
+}
+
+#[test]
+fn formats_the_utc_day_of_a_timestamp() {
+  assert_eq!(day(TIMESTAMP), "2023-11-14");
+  assert_eq!(day(TIMESTAMP + DAY_MILLIS), "2023-11-15");
+}
+
+#[test]
+fn aggregates_the_entries_of_each_tenant_in_key_order() {
+  let entries = vec![
+    ledger_entry(SuiAddress::ZERO, Some("globex"), TIMESTAMP),
+    ledger_entry(SuiAddress::ZERO, Some("acme"), TIMESTAMP),
+    ledger_entry(SuiAddress::ZERO, None, TIMESTAMP),
+    ledger_entry(SuiAddress::ZERO, Some("acme"), TIMESTAMP),
+  ];
+
+  let totals = aggregate(entries, &query(GroupBy::Tenant, None, None));
+
+  assert_eq!(keys(&totals), vec![None, Some("acme"), Some("globex")]);
+  assert_eq!(totals[1].tx_count, 2);
+  assert_eq!(totals[1].computation_cost, 2_000_000);
+  assert_eq!(totals[1].storage_cost, 4_000_000);
+  assert_eq!(totals[1].storage_rebate, 1_000_000);
+  assert_eq!(totals[1].net_gas_cost, 5_000_000);
+}
+
+#[test]
+fn aggregates_the_entries_of_each_day_within_the_range() {
+  let entries = vec![
+    ledger_entry(SuiAddress::ZERO, None, TIMESTAMP - DAY_MILLIS),
+    ledger_entry(SuiAddress::ZERO, None, TIMESTAMP + DAY_MILLIS),
+    ledger_entry(SuiAddress::ZERO, None, TIMESTAMP),
+    ledger_entry(SuiAddress::ZERO, None, TIMESTAMP + 2 * DAY_MILLIS),
+  ];
+
+  let totals = aggregate(entries, &query(GroupBy::Day, Some(TIMESTAMP), Some(TIMESTAMP + 2 * DAY_MILLIS)));
+
+  assert_eq!(keys(&totals), vec![Some("2023-11-14"), Some("2023-11-15")]);
+}
+
+#[test]
+fn saturates_the_aggregated_costs() {
+  let entry = LedgerEntry {computation_cost: u64::MAX, ..ledger_entry(SuiAddress::ZERO, None, TIMESTAMP)};
+  let totals = aggregate(vec![entry.clone(), entry], &query(GroupBy::Sender, None, None));
+
+  assert_eq!(totals[0].computation_cost, u64::MAX);
+  assert_eq!(totals[0].net_gas_cost, i64::MAX);
+}
+
+async fn round_trips(backend: LedgerBackend, name: &str) {
+  let path = ledger_path(name);
+  let ledger = open(backend, path.to_str().unwrap()).unwrap();
+  let (alice, bob) = (SuiAddress::random_for_testing_only(), SuiAddress::random_for_testing_only());
+  let failed = LedgerEntry {
+    success: false,
+    error: Some("InsufficientGas".to_string()),
+    storage_rebate: 5_000_000,
+    ..ledger_entry(alice, Some("acme"), TIMESTAMP + DAY_MILLIS)
+  };
+
+  ledger.record(&ledger_entry(alice, Some("acme"), TIMESTAMP)).await.unwrap();
+  ledger.record(&failed).await.unwrap();
+  ledger.record(&ledger_entry(bob, None, TIMESTAMP)).await.unwrap();
+
+  let totals = ledger.totals(&query(GroupBy::Tenant, None, None)).await.unwrap();
+  assert_eq!(keys(&totals), vec![None, Some("acme")]);
+  assert_eq!(totals[1].tx_count, 2);
+  assert_eq!(totals[1].storage_rebate, 5_500_000);
+  assert_eq!(totals[1].net_gas_cost, 500_000);
+
+  let totals = ledger.totals(&query(GroupBy::Day, Some(TIMESTAMP + 1), None)).await.unwrap();
+  assert_eq!(keys(&totals), vec![Some("2023-11-15")]);
+  assert_eq!(totals[0].net_gas_cost, -2_000_000);
+
+  let totals = ledger.totals(&query(GroupBy::Sender, None, Some(u64::MAX))).await.unwrap();
+  assert_eq!(totals.iter().map(|t| t.tx_count).sum::<u64>(), 3);
+
+  let entries = ledger.entries(Some(TIMESTAMP + 1), None).await.unwrap();
+  assert_eq!(entries.len(), 1);
+  assert_eq!(entries[0].digest, failed.digest);
+  assert_eq!(entries[0].sender, alice);
+  assert_eq!(entries[0].gas_coin, failed.gas_coin);
+  assert_eq!(entries[0].tenant.as_deref(), Some("acme"));
+  assert_eq!(entries[0].storage_rebate, 5_000_000);
+  assert!(!entries[0].success);
+  assert_eq!(entries[0].error.as_deref(), Some("InsufficientGas"));
+  assert_eq!(entries[0].timestamp, TIMESTAMP + DAY_MILLIS);
+
+  let entries = ledger.entries(None, Some(TIMESTAMP + 1)).await.unwrap();
+  assert_eq!(entries.len(), 2);
+
+  let _ = fs::remove_file(path);
+}
+
+#[tokio::test]
+async fn round_trips_entries_through_sqlite() {
+  round_trips(LedgerBackend::Sqlite, "round-trip.db").await;
+}
+
+#[tokio::test]
+async fn round_trips_entries_through_jsonl() {
+  round_trips(LedgerBackend::Jsonl, "round-trip.jsonl").await;
+}
+
+#[tokio::test]
+async fn records_a_transaction_only_once_in_sqlite() {
+  let path = ledger_path("once.db");
+  let ledger = open(LedgerBackend::Sqlite, path.to_str().unwrap()).unwrap();
+  let entry = ledger_entry(SuiAddress::ZERO, None, TIMESTAMP);
+
+  ledger.record(&entry).await.unwrap();
+  ledger.record(&entry).await.unwrap();
+
+  let totals = ledger.totals(&query(GroupBy::Sender, None, None)).await.unwrap();
+  assert_eq!(totals[0].tx_count, 1);
+
+  let _ = fs::remove_file(path);
+}
+
+#[tokio::test]
+async fn rejects_costs_sqlite_cannot_store() {
+  let path = ledger_path("overflow.db");
+  let ledger = open(LedgerBackend::Sqlite, path.to_str().unwrap()).unwrap();
+  let entry = LedgerEntry {computation_cost: u64::MAX, ..ledger_entry(SuiAddress::ZERO, None, TIMESTAMP)};
+
+  assert!(ledger.record(&entry).await.is_err());
+
+  let _ = fs::remove_file(path);
+}
@@ -0,0 +1,161 @@
+use serde_json::json;
+use sui_types::{
+  base_types::ObjectID,
+  programmable_transaction_builder::ProgrammableTransactionBuilder,
+  transaction::{Command, ProgrammableTransaction},
+  Identifier,
+};
+use sui_sponsor_common::services::policy::{Policy, TenantPolicy, hash_api_key};
+
+const MAX_GAS_BUDGET: u64 = 50_000_000;
+
+fn policy(policy: serde_json::Value) -> eyre::Result<Policy> {
+  Policy::parse(&policy.to_string())
+}
+
+/// The policy of the `acme` tenant with the given settings
+fn tenant_policy(settings: serde_json::Value) -> TenantPolicy {
+  policy(json!({"tenants": {"acme": settings}})).unwrap().tenants.remove("acme").unwrap()
+}
+
+/// A transaction that calls the given `module::function`s of package 0xabc
+fn move_calls(functions: &[&str]) -> ProgrammableTransaction {
+  let mut builder = ProgrammableTransactionBuilder::new();
+
+  for function in functions {
+    let (module, function) = function.split_once("::").unwrap();
+    builder.command(Command::move_call(
+      ObjectID::from_hex_literal("0xabc").unwrap(),
+      Identifier::new(module).unwrap(),
+      Identifier::new(function).unwrap(),
+      vec![],
+      vec![],
+    ));
+  }
+
+  builder.finish()
+}
+
+#[test]
+fn hashes_api_keys_with_sha256() {
+  assert_eq!(hash_api_key("test"), "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08");
+}
+
+#[test]
+fn authenticates_the_tenant_of_an_api_key() {
+  let policy = policy(json!({
+    "tenants": {
+      "acme": {"apiKeyHashes": [hash_api_key("acme-1"), hash_api_key("acme-2").to_uppercase()]},
+      "globex": {"apiKeyHashes": [hash_api_key("globex")]},
+    },
+  })).unwrap();
+
+  assert_eq!(policy.authenticate(Some("acme-1")).unwrap(), Some("acme"));
+  assert_eq!(policy.authenticate(Some("acme-2")).unwrap(), Some("acme"));
+  assert_eq!(policy.authenticate(Some("globex")).unwrap(), Some("globex"));
+}
+
+#[test]
+fn requests_without_an_api_key_have_no_tenant() {
+  let policy = policy(json!({"tenants": {"acme": {"apiKeyHashes": [hash_api_key("acme")]}}})).unwrap();
+
+  assert_eq!(policy.authenticate(None).unwrap(), None);
+}
+
+#[test]
+fn rejects_unknown_api_keys_and_tenant_names() {
+  let policy = policy(json!({"tenants": {"acme": {"apiKeyHashes": [hash_api_key("acme-1")]}}})).unwrap();
+
+  assert!(policy.authenticate(Some("acme")).is_err());
+  assert!(policy.authenticate(Some(&hash_api_key("acme-1"))).is_err());
+}
+
+#[test]
+fn rejects_an_api_key_shared_by_two_tenants() {
+  let result = policy(json!({
+    "tenants": {
+      "acme": {"apiKeyHashes": [hash_api_key("shared")]},
+      "globex": {"apiKeyHashes": [hash_api_key("shared")]},
+    },
+  }));
+
+  assert!(result.is_err());
+}
+
+#[test]
+fn rejects_malformed_api_key_hashes() {
+  assert!(policy(json!({"tenants": {"acme": {"apiKeyHashes": ["acme-1"]}}})).is_err());
+}
+
+#[test]
+fn dry_runs_before_submitting_only_when_asked_to() {
+  let policy = policy(json!({
+    "default": {"dryRunBeforeSubmit": true},
+    "tenants": {
+      "acme": {"apiKeyHashes": [hash_api_key("acme")]},
+      "globex": {"apiKeyHashes": [hash_api_key("globex")], "dryRunBeforeSubmit": true},
+    },
+  })).unwrap();
+
+  assert!(policy.tenant(None).unwrap().dry_run_before_submit);
+  assert!(!policy.tenant(Some("acme")).unwrap().dry_run_before_submit);
+  assert!(policy.tenant(Some("globex")).unwrap().dry_run_before_submit);
+}
+
+#[test]
+fn does_not_dry_run_before_submitting_by_default() {
+  assert!(!policy(json!({})).unwrap().tenant(None).unwrap().dry_run_before_submit);
+}
+
+#[test]
+fn grants_the_sum_of_the_function_budgets() {
+  let policy = tenant_policy(json!({"functionBudgets": {"0xabc::game::play": 5_000_000, "0x0abc::game::claim": 2_000_000}}));
+
+  assert_eq!(policy.granted_gas_budget(&move_calls(&["game::play"]), MAX_GAS_BUDGET), Some(5_000_000));
+  assert_eq!(policy.granted_gas_budget(&move_calls(&["game::play", "game::claim"]), MAX_GAS_BUDGET), Some(7_000_000));
+  assert_eq!(policy.granted_gas_budget(&move_calls(&["game::play", "game::play"]), MAX_GAS_BUDGET), Some(10_000_000));
+}
+
+#[test]
+fn caps_the_granted_budget_at_the_max_gas_budget() {
+  let policy = tenant_policy(json!({"functionBudgets": {"0xabc::game::play": 30_000_000}}));
+
+  assert_eq!(policy.granted_gas_budget(&move_calls(&["game::play", "game::play"]), MAX_GAS_BUDGET), Some(MAX_GAS_BUDGET));
+  assert_eq!(policy.granted_gas_budget(&move_calls(&["game::play"]), 10_000_000), Some(10_000_000));
+}
+
+#[test]
+fn grants_nothing_to_calls_of_unlisted_functions() {
+  let policy = tenant_policy(json!({"functionBudgets": {"0xabc::game::play": 5_000_000}}));
+
+  assert_eq!(policy.granted_gas_budget(&move_calls(&["game::mint"]), MAX_GAS_BUDGET), None);
+  // An unlisted call must not lift the cap of the listed ones
+  assert_eq!(policy.granted_gas_budget(&move_calls(&["game::play", "game::mint"]), MAX_GAS_BUDGET), None);
+}
+
+#[test]
+fn counts_unlisted_functions_as_the_default_function_budget() {
+  let policy = tenant_policy(json!({
+    "functionBudgets": {"0xabc::game::play": 5_000_000},
+    "defaultFunctionBudget": 1_000_000,
+  }));
+
+  assert_eq!(policy.granted_gas_budget(&move_calls(&["game::mint"]), MAX_GAS_BUDGET), Some(1_000_000));
+  assert_eq!(policy.granted_gas_budget(&move_calls(&["game::play", "game::mint"]), MAX_GAS_BUDGET), Some(6_000_000));
+}
+
+#[test]
+fn applies_the_default_function_budget_without_function_budgets() {
+  let policy = tenant_policy(json!({"defaultFunctionBudget": 1_000_000}));
+
+  assert_eq!(policy.granted_gas_budget(&move_calls(&["game::play", "game::mint"]), MAX_GAS_BUDGET), Some(2_000_000));
+}
+
+#[test]
+fn grants_the_max_gas_budget_without_budgets_or_move_calls() {
+  let unlimited = tenant_policy(json!({}));
+  let limited = tenant_policy(json!({"functionBudgets": {"0xabc::game::play": 5_000_000}}));
+
+  assert_eq!(unlimited.granted_gas_budget(&move_calls(&["game::play"]), MAX_GAS_BUDGET), Some(MAX_GAS_BUDGET));
+  assert_eq!(limited.granted_gas_budget(&move_calls(&[]), MAX_GAS_BUDGET), Some(MAX_GAS_BUDGET));
+}
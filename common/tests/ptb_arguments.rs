@@ -0,0 +1,104 @@
+mod support;
+
+use sui_sponsor_common::services::ptb::{PtbRules, is_ptb_allowed};
+use sui_types::{
+  base_types::{ObjectID, SuiAddress},
+  programmable_transaction_builder::ProgrammableTransactionBuilder,
+  transaction::{Command, ProgrammableTransaction},
+  Identifier,
+};
+use support::{coin, rules};
+
+/// A transaction that transfers a coin of the sender to the given recipient
+fn transfer_to(recipient: SuiAddress) -> ProgrammableTransaction {
+  let mut builder = ProgrammableTransactionBuilder::new();
+  let coin = coin(&mut builder);
+  let recipient = builder.pure(recipient).unwrap();
+  builder.command(Command::TransferObjects(vec![coin], recipient));
+
+  builder.finish()
+}
+
+/// A transaction that splits a coin of the sender into coins of the given amounts, in one SplitCoins command per
+/// group
+fn split(groups: &[&[u64]]) -> ProgrammableTransaction {
+  let mut builder = ProgrammableTransactionBuilder::new();
+  let coin = coin(&mut builder);
+
+  for amounts in groups {
+    let amounts = amounts.iter().map(|amount| builder.pure(*amount).unwrap()).collect();
+    builder.command(Command::SplitCoins(coin, amounts));
+  }
+
+  builder.finish()
+}
+
+#[test]
+fn allows_transfers_to_listed_recipients() {
+  let sender = SuiAddress::random_for_testing_only();
+  let recipient = SuiAddress::random_for_testing_only();
+  let rules = rules(serde_json::json!({"transferRecipients": [recipient]}));
+
+  assert!(is_ptb_allowed(&transfer_to(recipient), sender, &rules));
+}
+
+#[test]
+fn rejects_transfers_to_other_recipients() {
+  let sender = SuiAddress::random_for_testing_only();
+  let rules = rules(serde_json::json!({"transferRecipients": [SuiAddress::random_for_testing_only()]}));
+
+  assert!(!is_ptb_allowed(&transfer_to(SuiAddress::random_for_testing_only()), sender, &rules));
+}
+
+#[test]
+fn allows_transfers_to_the_sender() {
+  let sender = SuiAddress::random_for_testing_only();
+  let rules = rules(serde_json::json!({"transferRecipients": []}));
+
+  assert!(is_ptb_allowed(&transfer_to(sender), sender, &rules));
+}
+
+#[test]
+fn allows_any_recipient_without_an_allowlist() {
+  let sender = SuiAddress::random_for_testing_only();
+
+  assert!(is_ptb_allowed(&transfer_to(SuiAddress::random_for_testing_only()), sender, &PtbRules::default()));
+}
+
+#[test]
+fn rejects_recipients_that_are_not_pure_inputs() {
+  let sender = SuiAddress::random_for_testing_only();
+  let mut builder = ProgrammableTransactionBuilder::new();
+  let coin = coin(&mut builder);
+  // The recipient is computed by a Move call, so it can't be known before execution
+  let recipient = builder.command(Command::move_call(
+    ObjectID::from_hex_literal("0xabc").unwrap(),
+    Identifier::new("game").unwrap(),
+    Identifier::new("winner").unwrap(),
+    vec![],
+    vec![],
+  ));
+  builder.command(Command::TransferObjects(vec![coin], recipient));
+  let rules = rules(serde_json::json!({"transferRecipients": [sender]}));
+
+  assert!(!is_ptb_allowed(&builder.finish(), sender, &rules));
+}
+
+#[test]
+fn allows_splits_up_to_the_max_split_coins() {
+  let sender = SuiAddress::random_for_testing_only();
+  let rules = rules(serde_json::json!({"maxSplitCoins": 3}));
+
+  assert!(is_ptb_allowed(&split(&[&[1, 2, 3]]), sender, &rules));
+  assert!(is_ptb_allowed(&split(&[&[1], &[2, 3]]), sender, &rules));
+}
+
+#[test]
+fn rejects_splits_beyond_the_max_split_coins() {
+  let sender = SuiAddress::random_for_testing_only();
+  let rules = rules(serde_json::json!({"maxSplitCoins": 3}));
+
+  assert!(!is_ptb_allowed(&split(&[&[1, 2, 3, 4]]), sender, &rules));
+  // The cap applies to all the SplitCoins commands of the transaction together
+  assert!(!is_ptb_allowed(&split(&[&[1, 2], &[3, 4]]), sender, &rules));
+}
@@ -0,0 +1,144 @@
+mod support;
+
+use std::collections::HashMap;
+use sui_sponsor_common::services::ptb::{
+  PtbRules, is_ptb_allowed, is_ptb_allowed_with_object_types, untyped_vector_objects,
+};
+use sui_types::{
+  base_types::{ObjectID, SuiAddress},
+  programmable_transaction_builder::ProgrammableTransactionBuilder,
+  transaction::{Command, ProgrammableTransaction},
+  parse_sui_type_tag,
+};
+use support::{COIN_TYPE, coin, package, rules};
+
+fn make_move_vec(typed: bool) -> ProgrammableTransaction {
+  let mut builder = ProgrammableTransactionBuilder::new();
+  let coin = coin(&mut builder);
+  let type_tag = typed.then(|| parse_sui_type_tag(COIN_TYPE).unwrap());
+  builder.command(Command::MakeMoveVec(type_tag, vec![coin]));
+
+  builder.finish()
+}
+
+fn publish() -> ProgrammableTransaction {
+  let mut builder = ProgrammableTransactionBuilder::new();
+  builder.command(Command::Publish(vec![vec![0]], vec![]));
+
+  builder.finish()
+}
+
+fn upgrade() -> ProgrammableTransaction {
+  let mut builder = ProgrammableTransactionBuilder::new();
+  let ticket = builder.pure(0u8).unwrap();
+  builder.command(Command::Upgrade(vec![vec![0]], vec![], package(), ticket));
+
+  builder.finish()
+}
+
+#[test]
+fn rejects_make_move_vec_publish_and_upgrade_by_default() {
+  let sender = SuiAddress::random_for_testing_only();
+  let rules = PtbRules::default();
+
+  assert!(!is_ptb_allowed(&make_move_vec(true), sender, &rules));
+  assert!(!is_ptb_allowed(&publish(), sender, &rules));
+  assert!(!is_ptb_allowed(&upgrade(), sender, &rules));
+}
+
+#[test]
+fn allows_make_move_vec_of_listed_types() {
+  let sender = SuiAddress::random_for_testing_only();
+  let rules = rules(serde_json::json!({"makeMoveVecTypes": ["0x0002::coin::Coin<0x2::sui::SUI>"]}));
+
+  assert!(is_ptb_allowed(&make_move_vec(true), sender, &rules));
+  assert!(!is_ptb_allowed(&make_move_vec(false), sender, &rules));
+}
+
+#[test]
+fn rejects_make_move_vec_of_other_types() {
+  let sender = SuiAddress::random_for_testing_only();
+  let rules = rules(serde_json::json!({"makeMoveVecTypes": ["0x2::coin::Coin<0xabc::token::TOKEN>"]}));
+
+  assert!(!is_ptb_allowed(&make_move_vec(true), sender, &rules));
+}
+
+#[test]
+fn allows_publish_when_enabled() {
+  let sender = SuiAddress::random_for_testing_only();
+  let rules = rules(serde_json::json!({"allowPublish": true}));
+
+  assert!(is_ptb_allowed(&publish(), sender, &rules));
+  assert!(!is_ptb_allowed(&upgrade(), sender, &rules));
+}
+
+#[test]
+fn allows_upgrades_of_listed_packages_by_listed_upgrade_cap_owners() {
+  let owner = SuiAddress::random_for_testing_only();
+  let rules = rules(serde_json::json!({"upgradePackages": [package()], "upgradeCapOwners": [owner]}));
+
+  assert!(is_ptb_allowed(&upgrade(), owner, &rules));
+}
+
+#[test]
+fn rejects_upgrades_of_listed_packages_by_other_senders() {
+  let rules = rules(serde_json::json!({
+    "upgradePackages": [package()],
+    "upgradeCapOwners": [SuiAddress::random_for_testing_only()],
+  }));
+
+  assert!(!is_ptb_allowed(&upgrade(), SuiAddress::random_for_testing_only(), &rules));
+}
+
+#[test]
+fn rejects_upgrades_of_other_packages_by_listed_upgrade_cap_owners() {
+  let owner = SuiAddress::random_for_testing_only();
+  let rules = rules(serde_json::json!({"upgradePackages": ["0xdef"], "upgradeCapOwners": [owner]}));
+
+  assert!(!is_ptb_allowed(&upgrade(), owner, &rules));
+}
+
+/// The types of the objects that the untyped MakeMoveVec commands of the given transaction put in vectors
+fn object_types(ptx: &ProgrammableTransaction, object_type: &str) -> HashMap<ObjectID, String> {
+  untyped_vector_objects(ptx).into_iter()
+  .map(|id| (id, object_type.to_string()))
+  .collect()
+}
+
+#[test]
+fn resolves_the_type_of_untyped_vectors_from_their_objects() {
+  let sender = SuiAddress::random_for_testing_only();
+  let rules = rules(serde_json::json!({"makeMoveVecTypes": [COIN_TYPE]}));
+  let ptx = make_move_vec(false);
+  let coin_types = object_types(&ptx, "0x0002::coin::Coin<0x2::sui::SUI>");
+
+  assert_eq!(untyped_vector_objects(&ptx).len(), 1);
+  assert!(is_ptb_allowed_with_object_types(&ptx, sender, &rules, &coin_types));
+  assert!(!is_ptb_allowed_with_object_types(&ptx, sender, &rules, &object_types(&ptx, "0xabc::token::Token")));
+}
+
+#[test]
+fn rejects_untyped_vectors_of_mixed_or_unknown_types() {
+  let sender = SuiAddress::random_for_testing_only();
+  let rules = rules(serde_json::json!({"makeMoveVecTypes": [COIN_TYPE]}));
+
+  let mut builder = ProgrammableTransactionBuilder::new();
+  // A coin and a token
+  let objects = vec![coin(&mut builder), coin(&mut builder)];
+  builder.command(Command::MakeMoveVec(None, objects));
+  let mixed = builder.finish();
+  let mut types = object_types(&mixed, COIN_TYPE);
+  types.insert(untyped_vector_objects(&mixed)[1], "0xabc::token::Token".to_string());
+
+  // The elements are the results of other commands, whose type is not known before execution
+  let mut builder = ProgrammableTransactionBuilder::new();
+  let coin = coin(&mut builder);
+  let amount = builder.pure(1u64).unwrap();
+  let split = builder.command(Command::SplitCoins(coin, vec![amount]));
+  builder.command(Command::MakeMoveVec(None, vec![split]));
+  let results = builder.finish();
+
+  assert!(!is_ptb_allowed_with_object_types(&mixed, sender, &rules, &types));
+  assert!(!is_ptb_allowed_with_object_types(&results, sender, &rules, &object_types(&results, COIN_TYPE)));
+  assert!(!is_ptb_allowed(&make_move_vec(false), sender, &rules));
+}
@@ -0,0 +1,117 @@
+mod support;
+
+use std::str;
+use serde_json::{Value, json};
+use shared_crypto::intent::{Intent, IntentMessage};
+use sui_types::{
+  base_types::{SuiAddress, random_object_ref},
+  transaction::TransactionData,
+};
+use sui_sponsor_common::{
+  utils::config::KeyPair,
+  services::signer::{Signer, keypair::KeyPairSigner, remote::RemoteSigner},
+};
+use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::TcpListener, task::JoinHandle};
+
+struct Request {
+  head: String,
+  body: Value,
+}
+
+/// Serves a single request, replying with the given status and body, and returns the request it received
+async fn mock_server(status: &'static str, response: Value) -> (String, JoinHandle<Request>) {
+  let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+  let url = format!("http://{}", listener.local_addr().unwrap());
+
+  let handle = tokio::spawn(async move {
+    let (mut stream, _) = listener.accept().await.unwrap();
+    let mut buf = vec![];
+
+    let (head, body) = loop {
+      let mut chunk = [0; 4096];
+      let n = stream.read(&mut chunk).await.unwrap();
+      assert!(n > 0, "the connection closed before the request was complete");
+      buf.extend_from_slice(&chunk[..n]);
+
+      let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") else {continue};
+      let head = str::from_utf8(&buf[..end]).unwrap().to_string();
+      let content_length = head.lines()
+      .find_map(|line| line.to_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap()))
+      .unwrap_or(0);
+
+      if buf.len() >= end + 4 + content_length {
+        break (head, buf[end + 4..end + 4 + content_length].to_vec())
+      }
+    };
+
+    let response = response.to_string();
+    stream.write_all(format!(
+      "HTTP/1.1 {status}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{response}",
+      response.len(),
+    ).as_bytes()).await.unwrap();
+
+    Request {
+      head,
+      body: serde_json::from_slice(&body).unwrap(),
+    }
+  });
+
+  (url, handle)
+}
+
+fn intent_message(sponsor: SuiAddress) -> IntentMessage<TransactionData> {
+  let tx_data = TransactionData::new_transfer_sui(
+    SuiAddress::random_for_testing_only(),
+    sponsor,
+    Some(1_000),
+    random_object_ref(),
+    10_000_000,
+    1_000,
+  );
+
+  IntentMessage::new(Intent::sui_transaction(), tx_data)
+}
+
+#[tokio::test]
+async fn returns_the_signature_of_the_remote_service() {
+  let local_signer = KeyPairSigner::new(KeyPair::from(support::keypair()));
+  let msg = intent_message(local_signer.address());
+  let expected = local_signer.sign(&msg).await.unwrap();
+
+  let (url, server) = mock_server("200 OK", json!({"signature": base64::encode(expected.as_ref())})).await;
+  let signer = RemoteSigner::new(url, Some("secret".to_string()), local_signer.address());
+  let signature = signer.sign(&msg).await.unwrap();
+  let request = server.await.unwrap();
+
+  assert_eq!(signature.as_ref(), expected.as_ref());
+  assert!(request.head.starts_with("POST /sign "));
+  assert!(request.head.lines().any(|line| line.eq_ignore_ascii_case("authorization: Bearer secret")));
+  assert_eq!(request.body["address"], json!(local_signer.address()));
+  assert_eq!(request.body["intentMessage"], json!(base64::encode(bcs::to_bytes(&msg).unwrap())));
+}
+
+#[tokio::test]
+async fn omits_the_auth_header_without_a_token() {
+  let local_signer = KeyPairSigner::new(KeyPair::from(support::keypair()));
+  let msg = intent_message(local_signer.address());
+  let expected = local_signer.sign(&msg).await.unwrap();
+
+  let (url, server) = mock_server("200 OK", json!({"signature": base64::encode(expected.as_ref())})).await;
+  let signer = RemoteSigner::new(url, None, local_signer.address());
+  signer.sign(&msg).await.unwrap();
+  let request = server.await.unwrap();
+
+  assert!(!request.head.to_lowercase().contains("authorization:"));
+}
+
+#[tokio::test]
+async fn fails_when_the_remote_service_refuses_to_sign() {
+  let local_signer = KeyPairSigner::new(KeyPair::from(support::keypair()));
+  let msg = intent_message(local_signer.address());
+
+  let (url, server) = mock_server("401 Unauthorized", json!({})).await;
+  let signer = RemoteSigner::new(url, Some("wrong".to_string()), local_signer.address());
+
+  assert!(signer.sign(&msg).await.is_err());
+  server.await.unwrap();
+}
@@ -0,0 +1,83 @@
+use std::{collections::HashMap, sync::{Arc, atomic::{AtomicUsize, Ordering}}, time::Duration};
+use eyre::Result;
+use sui_types::base_types::SuiAddress;
+use sui_sponsor_common::{
+  storage::redis::ConnectionPool,
+  services::rotation::{Rotation, RotationCache, ROTATIONS_TTL, ROTATION_GRACE_PERIOD},
+};
+
+fn cache(ttl: Duration) -> RotationCache {
+  // The pool connects lazily; the loaders below never touch Redis
+  RotationCache::with_ttl(Arc::new(ConnectionPool::new("localhost", "", 6379)), ttl)
+}
+
+fn rotation(started_at: u64) -> Rotation {
+  Rotation {
+    to: SuiAddress::random_for_testing_only(),
+    started_at,
+  }
+}
+
+/// Returns the loaded rotations, a single one started at the number of the call, and counts the calls
+async fn load(cache: &RotationCache, calls: &AtomicUsize) -> Result<Arc<HashMap<SuiAddress, Rotation>>> {
+  cache.load_with(|| async {
+    let call = calls.fetch_add(1, Ordering::SeqCst) as u64;
+    Ok(HashMap::from([(SuiAddress::ZERO, rotation(call))]))
+  }).await
+}
+
+#[tokio::test]
+async fn uses_the_cached_rotations_within_the_ttl() {
+  let cache = cache(Duration::from_secs(60));
+  let calls = AtomicUsize::new(0);
+
+  let first = load(&cache, &calls).await.unwrap();
+  let second = load(&cache, &calls).await.unwrap();
+
+  assert_eq!(calls.load(Ordering::SeqCst), 1);
+  assert!(Arc::ptr_eq(&first, &second));
+}
+
+#[tokio::test]
+async fn reloads_the_rotations_once_the_ttl_expires() {
+  let cache = cache(Duration::ZERO);
+  let calls = AtomicUsize::new(0);
+
+  load(&cache, &calls).await.unwrap();
+  let second = load(&cache, &calls).await.unwrap();
+
+  assert_eq!(calls.load(Ordering::SeqCst), 2);
+  assert_eq!(second[&SuiAddress::ZERO].started_at, 1);
+}
+
+#[tokio::test]
+async fn does_not_cache_a_failed_load() {
+  let cache = cache(Duration::from_secs(60));
+  let calls = AtomicUsize::new(0);
+
+  assert!(cache.load_with(|| async {eyre::bail!("redis is down")}).await.is_err());
+  load(&cache, &calls).await.unwrap();
+
+  assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn picks_up_a_new_rotation_before_the_grace_period_ends() {
+  assert!(ROTATIONS_TTL < ROTATION_GRACE_PERIOD);
+}
+
+#[test]
+fn keeps_the_old_sponsor_balance_during_the_grace_period() {
+  let grace_period = ROTATION_GRACE_PERIOD.as_millis() as u64;
+  let rotation = rotation(1_000);
+
+  assert!(!rotation.is_past_grace_period(1_000));
+  assert!(!rotation.is_past_grace_period(1_000 + grace_period - 1));
+  assert!(rotation.is_past_grace_period(1_000 + grace_period));
+}
+
+#[test]
+fn treats_a_rotation_started_in_the_future_as_within_the_grace_period() {
+  // e.g. the clock of the instance that started it is ahead of ours
+  assert!(!rotation(10_000).is_past_grace_period(0));
+}
@@ -0,0 +1,105 @@
+use std::str::FromStr;
+use sui_types::base_types::SuiAddress;
+use sui_sponsor_common::{
+  gas_pool::PoolKeys,
+  services::shard::{ShardSelector, ShardStrategy},
+};
+
+#[test]
+fn rotates_the_preferred_item_round_robin() {
+  let selector = ShardSelector::new(ShardStrategy::RoundRobin);
+  let items = [0, 1, 2];
+
+  assert_eq!(selector.order_by_load(&items, |_| 0), vec![&0, &1, &2]);
+  assert_eq!(selector.order_by_load(&items, |_| 0), vec![&1, &2, &0]);
+  assert_eq!(selector.order_by_load(&items, |_| 0), vec![&2, &0, &1]);
+  assert_eq!(selector.order_by_load(&items, |_| 0), vec![&0, &1, &2]);
+}
+
+#[test]
+fn ignores_the_load_round_robin() {
+  let selector = ShardSelector::new(ShardStrategy::RoundRobin);
+  let items = [5, 1];
+
+  assert_eq!(selector.order_by_load(&items, |item| *item), vec![&5, &1]);
+}
+
+#[test]
+fn prefers_the_least_loaded_item() {
+  let selector = ShardSelector::new(ShardStrategy::LeastLoaded);
+  let items = [3, 1, 2];
+
+  assert_eq!(selector.order_by_load(&items, |item| *item), vec![&1, &2, &3]);
+  // It doesn't rotate between calls
+  assert_eq!(selector.order_by_load(&items, |item| *item), vec![&1, &2, &3]);
+}
+
+#[test]
+fn keeps_the_configured_order_of_equally_loaded_items() {
+  let selector = ShardSelector::new(ShardStrategy::LeastLoaded);
+  let items = [(0, 1), (1, 0), (2, 1)];
+
+  assert_eq!(selector.order_by_load(&items, |(_, load)| *load), vec![&(1, 0), &(0, 1), &(2, 1)]);
+}
+
+#[test]
+fn orders_no_items() {
+  let selector = ShardSelector::new(ShardStrategy::RoundRobin);
+  let items: [usize; 0] = [];
+
+  assert!(selector.order_by_load(&items, |_| 0).is_empty());
+}
+
+#[test]
+fn parses_the_shard_strategy() {
+  assert!(matches!(ShardStrategy::from_str("round_robin"), Ok(ShardStrategy::RoundRobin)));
+  assert!(matches!(ShardStrategy::from_str("least_loaded"), Ok(ShardStrategy::LeastLoaded)));
+  assert!(ShardStrategy::from_str("random").is_err());
+}
+
+#[test]
+fn keeps_the_existing_keys_when_unsharded() {
+  let keys = PoolKeys::unsharded();
+
+  assert_eq!(keys.pool, "gas_pool");
+  assert_eq!(keys.outbox, "gas_pool:outbox");
+  assert_eq!(keys.checkouts, "gas_pool:checkouts");
+  assert_eq!(keys.retiring, "gas_pool:retiring");
+  assert_eq!(keys.stats, "gas_pool:stats");
+  assert_eq!(keys.queue, "coin_object");
+  assert_eq!(keys.leader_lock, "coin_manager:leader");
+  assert!(keys.is_unsharded());
+}
+
+#[test]
+fn names_the_keys_of_a_shard_after_its_sponsor() {
+  let sponsor = SuiAddress::random_for_testing_only();
+  let keys = PoolKeys::for_sponsor(sponsor);
+
+  assert_eq!(keys.pool, format!("gas_pool:{sponsor}"));
+  assert_eq!(keys.outbox, format!("gas_pool:outbox:{sponsor}"));
+  assert_eq!(keys.checkouts, format!("gas_pool:checkouts:{sponsor}"));
+  assert_eq!(keys.retiring, format!("gas_pool:retiring:{sponsor}"));
+  assert_eq!(keys.stats, format!("gas_pool:stats:{sponsor}"));
+  assert_eq!(keys.queue, format!("coin_object.{sponsor}"));
+  assert_eq!(keys.leader_lock, format!("coin_manager:leader:{sponsor}"));
+  assert!(!keys.is_unsharded());
+}
+
+#[test]
+fn shards_the_keys_only_with_multiple_sponsors() {
+  let sponsor = SuiAddress::random_for_testing_only();
+
+  assert!(PoolKeys::new(sponsor, 1).is_unsharded());
+  assert_eq!(PoolKeys::new(sponsor, 2).pool, PoolKeys::for_sponsor(sponsor).pool);
+}
+
+#[test]
+fn gives_each_sponsor_its_own_shard() {
+  let first = PoolKeys::for_sponsor(SuiAddress::random_for_testing_only());
+  let second = PoolKeys::for_sponsor(SuiAddress::random_for_testing_only());
+
+  assert_ne!(first.pool, second.pool);
+  assert_ne!(first.queue, second.queue);
+  assert_ne!(first.leader_lock, second.leader_lock);
+}
@@ -0,0 +1,52 @@
+#![allow(dead_code)]
+
+use sui_types::{
+  base_types::{ObjectID, SuiAddress, random_object_ref},
+  crypto::{SuiKeyPair, get_key_pair},
+  programmable_transaction_builder::ProgrammableTransactionBuilder,
+  transaction::{Argument, ObjectArg},
+};
+use sui_sponsor_common::{ledger::LedgerEntry, services::ptb::PtbRules};
+
+pub const COIN_TYPE: &str = "0x2::coin::Coin<0x2::sui::SUI>";
+
+/// The package that the test transactions call or upgrade
+pub fn package() -> ObjectID {
+  ObjectID::from_hex_literal("0xabc").unwrap()
+}
+
+/// A random Ed25519 keypair
+pub fn keypair() -> SuiKeyPair {
+  let (_, keypair) = get_key_pair();
+  SuiKeyPair::Ed25519(keypair)
+}
+
+/// A successful transaction of the given sender and tenant that cost the sponsor 2.5M MIST
+pub fn ledger_entry(sender: SuiAddress, tenant: Option<&str>, timestamp: u64) -> LedgerEntry {
+  LedgerEntry {
+    digest: ObjectID::random().to_string(),
+    sender,
+    sponsor: SuiAddress::ZERO,
+    tenant: tenant.map(ToString::to_string),
+    gas_coin: ObjectID::random(),
+    computation_cost: 1_000_000,
+    storage_cost: 2_000_000,
+    storage_rebate: 500_000,
+    success: true,
+    error: None,
+    timestamp,
+  }
+}
+
+/// Adds an owned coin of the sender to the given transaction
+pub fn coin(builder: &mut ProgrammableTransactionBuilder) -> Argument {
+  builder.obj(ObjectArg::ImmOrOwnedObject(random_object_ref())).unwrap()
+}
+
+/// Parses and normalizes the given PTB rules
+pub fn rules(rules: serde_json::Value) -> PtbRules {
+  let mut rules: PtbRules = serde_json::from_value(rules).unwrap();
+  rules.normalize().unwrap();
+
+  rules
+}
@@ -0,0 +1,70 @@
+mod support;
+
+use shared_crypto::intent::{Intent, IntentMessage};
+use sui_types::{
+  base_types::{SuiAddress, random_object_ref},
+  crypto::{Signature, SuiKeyPair},
+  signature::GenericSignature,
+  transaction::{GasData, TransactionData},
+};
+use sui_sponsor_common::helpers::tx::TxManager;
+
+/// A transaction of the given sender whose gas is paid by the given sponsor
+fn sponsored_tx_data(sender: &SuiKeyPair, sponsor: &SuiKeyPair) -> TransactionData {
+  let sender = SuiAddress::from(&sender.public());
+  let TransactionData::V1(tx) = TransactionData::new_transfer_sui(
+    SuiAddress::random_for_testing_only(),
+    sender,
+    Some(1_000),
+    random_object_ref(),
+    10_000_000,
+    1_000,
+  );
+
+  TransactionData::new_with_gas_data(tx.kind, sender, GasData {
+    payment: vec![random_object_ref()],
+    owner: SuiAddress::from(&sponsor.public()),
+    price: 1_000,
+    budget: 10_000_000,
+  })
+}
+
+fn sign(tx_data: &TransactionData, keypair: &SuiKeyPair) -> GenericSignature {
+  Signature::new_secure(&IntentMessage::new(Intent::sui_transaction(), tx_data.clone()), keypair).into()
+}
+
+#[test]
+fn accepts_the_signatures_of_the_sender_and_the_sponsor() {
+  let (sender, sponsor) = (support::keypair(), support::keypair());
+  let tx_data = sponsored_tx_data(&sender, &sponsor);
+  let signatures = vec![sign(&tx_data, &sender), sign(&tx_data, &sponsor)];
+
+  assert!(TxManager::verify_signatures(&tx_data, signatures).is_ok());
+}
+
+#[test]
+fn rejects_a_transaction_without_the_sponsor_signature() {
+  let (sender, sponsor) = (support::keypair(), support::keypair());
+  let tx_data = sponsored_tx_data(&sender, &sponsor);
+
+  assert!(TxManager::verify_signatures(&tx_data, vec![sign(&tx_data, &sender)]).is_err());
+}
+
+#[test]
+fn rejects_the_signature_of_someone_other_than_the_sender() {
+  let (sender, sponsor) = (support::keypair(), support::keypair());
+  let tx_data = sponsored_tx_data(&sender, &sponsor);
+  let signatures = vec![sign(&tx_data, &support::keypair()), sign(&tx_data, &sponsor)];
+
+  assert!(TxManager::verify_signatures(&tx_data, signatures).is_err());
+}
+
+#[test]
+fn rejects_signatures_of_another_transaction() {
+  let (sender, sponsor) = (support::keypair(), support::keypair());
+  let tx_data = sponsored_tx_data(&sender, &sponsor);
+  let other = sponsored_tx_data(&sender, &sponsor);
+  let signatures = vec![sign(&other, &sender), sign(&other, &sponsor)];
+
+  assert!(TxManager::verify_signatures(&tx_data, signatures).is_err());
+}
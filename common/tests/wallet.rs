@@ -0,0 +1,172 @@
+mod support;
+
+use std::{collections::HashMap, sync::Arc};
+use async_trait::async_trait;
+use envconfig::Envconfig;
+use eyre::{Result, eyre};
+use shared_crypto::intent::{Intent, IntentMessage};
+use sui_types::{
+  base_types::{SuiAddress, random_object_ref},
+  crypto::{EncodeDecodeBase64, Signature},
+  multisig::MultiSigPublicKey,
+  signature::GenericSignature,
+  transaction::{Transaction, TransactionData},
+};
+use sui_sponsor_common::{
+  utils::config::{KeyPair, SuiConfig},
+  services::{signer::{Signer, keypair::KeyPairSigner}, wallet::{Wallet, sponsor_wallets}},
+};
+
+/// A member whose signer is unavailable e.g. a remote signer that is down
+struct FailingSigner {
+  address: SuiAddress,
+}
+
+#[async_trait]
+impl Signer for FailingSigner {
+  fn address(&self) -> SuiAddress {
+    self.address
+  }
+
+  async fn sign(&self, _msg: &IntentMessage<TransactionData>) -> Result<Signature> {
+    Err(eyre!("signer unavailable"))
+  }
+}
+
+fn keypairs(count: usize) -> Vec<KeyPair> {
+  (0..count).map(|_| KeyPair::from(support::keypair())).collect()
+}
+
+fn address(keypair: &KeyPair) -> SuiAddress {
+  (&keypair.public()).into()
+}
+
+fn multisig_public_key(keypairs: &[KeyPair], threshold: u16) -> MultiSigPublicKey {
+  let public_keys = keypairs.iter().map(|kp| kp.public()).collect::<Vec<_>>();
+  MultiSigPublicKey::new(public_keys, vec![1; keypairs.len()], threshold).unwrap()
+}
+
+fn config(vars: &[(&str, String)]) -> SuiConfig {
+  let vars = [("SUI_RPC", "http://localhost:9000".to_string())].into_iter()
+  .chain(vars.iter().cloned())
+  .map(|(name, value)| (name.to_string(), value))
+  .collect::<HashMap<_, _>>();
+
+  SuiConfig::init_from_hashmap(&vars).unwrap()
+}
+
+fn join(values: impl Iterator<Item = String>) -> String {
+  values.collect::<Vec<_>>().join(",")
+}
+
+fn multisig_config(keypairs: &[KeyPair], weights: Option<&str>, threshold: Option<u16>) -> SuiConfig {
+  let mut vars = vec![
+    ("SPONSOR_PRIV_KEY", join(keypairs.iter().map(|kp| kp.encode_base64()))),
+    ("MULTISIG_PUBLIC_KEYS", join(keypairs.iter().map(|kp| kp.public().encode_base64()))),
+  ];
+  if let Some(weights) = weights {
+    vars.push(("MULTISIG_WEIGHTS", weights.to_string()));
+  }
+  if let Some(threshold) = threshold {
+    vars.push(("MULTISIG_THRESHOLD", threshold.to_string()));
+  }
+
+  config(&vars)
+}
+
+fn tx_data(sponsor: SuiAddress) -> TransactionData {
+  TransactionData::new_transfer_sui(
+    SuiAddress::random_for_testing_only(),
+    sponsor,
+    Some(1_000),
+    random_object_ref(),
+    10_000_000,
+    1_000,
+  )
+}
+
+/// Whether the given signature is a valid signature of the sponsor, which is both the sender and the gas owner
+fn verifies(tx_data: TransactionData, signature: GenericSignature) -> bool {
+  Transaction::from_generic_sig_data(tx_data, Intent::sui_transaction(), vec![signature])
+  .verify()
+  .is_ok()
+}
+
+#[tokio::test]
+async fn combines_the_member_signatures_into_a_valid_multisig() {
+  let keypairs = keypairs(3);
+  let wallets = sponsor_wallets(&multisig_config(&keypairs, None, Some(2))).unwrap();
+  let wallet = &wallets[0];
+  let tx_data = tx_data(wallet.address());
+
+  let signature = wallet.sign(&tx_data, Intent::sui_transaction()).await.unwrap();
+
+  assert_eq!(wallets.len(), 1);
+  assert_eq!(wallet.address(), SuiAddress::from(&multisig_public_key(&keypairs, 2)));
+  assert!(matches!(signature, GenericSignature::MultiSig(_)));
+  assert!(verifies(tx_data, signature));
+}
+
+#[tokio::test]
+async fn skips_members_that_fail_to_sign() {
+  let keypairs = keypairs(3);
+  let public_key = multisig_public_key(&keypairs, 2);
+  let signers: Vec<Arc<dyn Signer>> = vec![
+    Arc::new(FailingSigner {address: address(&keypairs[0])}),
+    Arc::new(KeyPairSigner::new(keypairs[1].clone())),
+    Arc::new(KeyPairSigner::new(keypairs[2].clone())),
+  ];
+  let wallet = Wallet::multisig(public_key, signers).unwrap();
+  let tx_data = tx_data(wallet.address());
+
+  let signature = wallet.sign(&tx_data, Intent::sui_transaction()).await.unwrap();
+
+  assert!(verifies(tx_data, signature));
+}
+
+#[tokio::test]
+async fn fails_when_the_members_that_sign_do_not_reach_the_threshold() {
+  let keypairs = keypairs(3);
+  let public_key = multisig_public_key(&keypairs, 2);
+  let signers: Vec<Arc<dyn Signer>> = vec![
+    Arc::new(FailingSigner {address: address(&keypairs[0])}),
+    Arc::new(FailingSigner {address: address(&keypairs[1])}),
+    Arc::new(KeyPairSigner::new(keypairs[2].clone())),
+  ];
+  let wallet = Wallet::multisig(public_key, signers).unwrap();
+
+  assert!(wallet.sign(&tx_data(wallet.address()), Intent::sui_transaction()).await.is_err());
+}
+
+#[test]
+fn creates_one_wallet_per_key_without_a_multisig() {
+  let keypairs = keypairs(2);
+  let config = config(&[("SPONSOR_PRIV_KEY", join(keypairs.iter().map(|kp| kp.encode_base64())))]);
+
+  let addresses = sponsor_wallets(&config).unwrap().iter().map(Wallet::address).collect::<Vec<_>>();
+
+  assert_eq!(addresses, keypairs.iter().map(address).collect::<Vec<_>>());
+}
+
+#[test]
+fn rejects_a_weight_count_that_does_not_match_the_keys() {
+  let keypairs = keypairs(3);
+
+  assert!(sponsor_wallets(&multisig_config(&keypairs, Some("1,1"), Some(2))).is_err());
+}
+
+#[test]
+fn rejects_a_multisig_without_a_threshold() {
+  let keypairs = keypairs(3);
+
+  assert!(sponsor_wallets(&multisig_config(&keypairs, None, None)).is_err());
+}
+
+#[test]
+fn rejects_members_that_do_not_reach_the_threshold() {
+  let keypairs = keypairs(3);
+  let mut config = multisig_config(&keypairs, None, Some(3));
+  config.sponsor_keypairs.as_mut().unwrap().0.truncate(2);
+
+  assert!(sponsor_wallets(&config).is_err());
+}